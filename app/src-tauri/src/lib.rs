@@ -1,6 +1,5 @@
 use anyhow::Error;
 use chrono::{DateTime, FixedOffset, Utc};
-use glob::glob;
 use process_mining::{
     export_ocel_json_path,
     ocel::ocel_struct::{
@@ -10,17 +9,24 @@ use process_mining::{
     OCEL,
 };
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use slurry::{
     self,
-    data_extraction::{get_squeue_res_ssh, squeue::SqueueRow, squeue_diff, SqueueMode},
+    data_extraction::{
+        get_squeue_res, record_finished_jobs, squeue::SqueueRow, squeue_diff, DirectoryStore,
+        History, SnapshotStore, SqliteStore, SqueueDiffState, SqueueMode, SqueueRowDelta,
+    },
     job_management::{
-        get_job_status, submit_job, JobFilesToUpload, JobLocalForwarding, JobOptions, JobStatus,
+        forward_to_job, get_job_status, submit_job, JobFilesToUpload, JobOptions, JobStatus,
+    },
+    ssh::{
+        HostKeyCallback, HostKeyInfo, KeyboardInteractiveCallback, KeyboardInteractivePrompt,
+        SessionEventHandler, SessionPool,
     },
-    login_with_cfg, Client, ConnectionConfig, JobState,
+    ConnectionConfig, JobState, PortForward,
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fs::File,
     io::BufWriter,
     path::PathBuf,
@@ -33,8 +39,10 @@ use tauri::{async_runtime::RwLock, State};
 
 #[tauri::command]
 async fn run_squeue<'a>(state: State<'a, Arc<RwLock<AppState>>>) -> Result<String, CmdError> {
-    if let Some(client) = &state.read().await.client {
-        let (time, jobs) = get_squeue_res_ssh(client, &SqueueMode::ALL).await?;
+    // Clone the session handle, so the state is not locked while the command runs
+    let client = state.read().await.client.clone();
+    if let Some(client) = &client {
+        let (time, jobs) = get_squeue_res(&SqueueMode::ALL, client).await?;
         serde_json::to_writer_pretty(
             BufWriter::new(
                 File::create(format!("{}.json", time.to_rfc3339().replace(":", "_"))).unwrap(),
@@ -54,6 +62,7 @@ async fn start_squeue_loop<'a>(
     app: AppHandle,
     state: State<'a, Arc<RwLock<AppState>>>,
     looping_interval: u64,
+    store: StoreKind,
 ) -> Result<String, CmdError> {
     let path = app
         .dialog()
@@ -66,19 +75,26 @@ async fn start_squeue_loop<'a>(
             .into_path()
             .map_err(|e| Error::msg(format!("Could not handle this folder path: {:?}", e)))?
             .join(format!(
-                "squeue_results_{}",
+                "squeue_results_{}{}",
                 DateTime::<Utc>::from(SystemTime::now())
                     .to_rfc3339()
-                    .replace(":", "_")
+                    .replace(":", "_"),
+                match store {
+                    StoreKind::Directory => "",
+                    StoreKind::Sqlite => ".sqlite",
+                }
             ));
+        let mut snapshot_store: Box<dyn SnapshotStore> = match store {
+            StoreKind::Directory => Box::new(DirectoryStore::new(&path)),
+            StoreKind::Sqlite => Box::new(SqliteStore::open(&path)?),
+        };
         state.write().await.looping_info = Some(LoopingInfo {
             second_interval: looping_interval,
             running_since: std::time::SystemTime::now().into(),
             path: path.clone(),
         });
         async_runtime::spawn(async move {
-            let mut known_jobs = HashMap::default();
-            let mut all_ids = HashSet::default();
+            let mut diff_state = SqueueDiffState::default();
             let mut i = 0;
             'inf_loop: loop {
                 // if let Some(LoopingInfo {
@@ -86,18 +102,35 @@ async fn start_squeue_loop<'a>(
                 // }) = &state.read().await.looping_info.clone()
                 // {
                 let l = state.read().await;
-                if let Some(client) = &l.client {
-                    let res = squeue_diff(
-                        || get_squeue_res_ssh(client, &SqueueMode::ALL),
-                        &path,
-                        &mut known_jobs,
-                        &mut all_ids,
+                if let Some(client) = l.client.clone() {
+                    drop(l);
+                    // The session reconnects on its own, so failures only skip this iteration
+                    match squeue_diff(
+                        || get_squeue_res(&SqueueMode::ALL, &client),
+                        snapshot_store.as_mut(),
+                        &mut diff_state,
                     )
                     .await
-                    .unwrap();
-                    app.emit("squeue-rows", &res).unwrap();
-                    i += 1;
-                    drop(l);
+                    {
+                        Ok(res) => {
+                            let _ = app.emit("squeue-rows", &res);
+                            i += 1;
+                            // Capture the final state of jobs which left the queue
+                            if let Err(e) = record_finished_jobs(
+                                &client,
+                                snapshot_store.as_mut(),
+                                &mut diff_state,
+                            )
+                            .await
+                            {
+                                eprintln!("Failed to run sacct: {e:?}");
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to run squeue: {e:?}");
+                            let _ = app.emit("squeue-loop-error", format!("{e:#}"));
+                        }
+                    }
                     println!("Ran for {} iterations, sleeping...", i);
                     for _ in 1..looping_interval {
                         if state.read().await.looping_info.is_none() {
@@ -147,8 +180,10 @@ async fn get_loop_info<'a>(
 async fn get_squeue<'a>(
     state: State<'a, Arc<RwLock<AppState>>>,
 ) -> Result<(DateTime<Utc>, Vec<SqueueRow>), CmdError> {
-    if let Some(client) = &state.read().await.client {
-        let (time, jobs) = get_squeue_res_ssh(client, &SqueueMode::ALL).await?;
+    // Clone the session handle, so the state is not locked while the command runs
+    let client = state.read().await.client.clone();
+    if let Some(client) = &client {
+        let (time, jobs) = get_squeue_res(&SqueueMode::ALL, client).await?;
         Ok((time, jobs))
     } else {
         Err(Error::msg("No logged-in client available.").into())
@@ -157,14 +192,86 @@ async fn get_squeue<'a>(
 
 #[tauri::command]
 async fn login<'a>(
+    app: AppHandle,
     state: State<'a, Arc<RwLock<AppState>>>,
     cfg: ConnectionConfig,
 ) -> Result<String, CmdError> {
-    let client = login_with_cfg(&cfg).await?;
-    state.write().await.client = Some(client);
+    // Ask the user whether to trust unknown host keys
+    let host_key_app = app.clone();
+    let cfg = cfg.with_host_key_callback(HostKeyCallback::new(move |info: HostKeyInfo| {
+        let app = host_key_app.clone();
+        async move {
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            app.state::<HostKeyPrompt>()
+                .0
+                .lock()
+                .unwrap()
+                .entry(format!("{}:{}", info.host, info.port))
+                .or_default()
+                .push(sender);
+            if app.emit("host-key-prompt", &info).is_err() {
+                return false;
+            }
+            receiver.await.unwrap_or(false)
+        }
+    }));
+    // Ask the user for answers to unknown login prompts (e.g., MFA codes)
+    let auth_app = app.clone();
+    let cfg = cfg.with_keyboard_interactive_callback(KeyboardInteractiveCallback::new(
+        move |prompt: KeyboardInteractivePrompt| {
+            let app = auth_app.clone();
+            async move {
+                let (sender, receiver) = tokio::sync::oneshot::channel();
+                app.state::<AuthPrompt>()
+                    .0
+                    .lock()
+                    .unwrap()
+                    .entry(prompt.host.clone())
+                    .or_default()
+                    .push_back(sender);
+                app.emit("auth-prompt", &prompt).ok()?;
+                receiver.await.ok().flatten()
+            }
+        },
+    ));
+    // Reconnect automatically (e.g., for long-running squeue loops), reporting the connection status
+    // The pool is shared by all commands (e.g., the squeue loop and job submission)
+    let session = SessionPool::new(cfg).with_event_handler(SessionEventHandler::new(
+        move |event| {
+            let _ = app.emit("ssh-session-event", &event);
+        },
+    ));
+    session.connect().await?;
+    state.write().await.client = Some(session);
     Ok(String::from("OK"))
 }
 
+#[tauri::command]
+fn answer_host_key_prompt(
+    prompt: State<'_, HostKeyPrompt>,
+    host: String,
+    port: u16,
+    trusted: bool,
+) {
+    let senders = prompt.0.lock().unwrap().remove(&format!("{host}:{port}"));
+    for sender in senders.unwrap_or_default() {
+        let _ = sender.send(trusted);
+    }
+}
+
+#[tauri::command]
+fn answer_auth_prompt(prompt: State<'_, AuthPrompt>, host: String, answer: Option<String>) {
+    let sender = prompt
+        .0
+        .lock()
+        .unwrap()
+        .get_mut(&host)
+        .and_then(|senders| senders.pop_front());
+    if let Some(sender) = sender {
+        let _ = sender.send(answer);
+    }
+}
+
 #[tauri::command]
 async fn is_logged_in<'a>(state: State<'a, Arc<RwLock<AppState>>>) -> Result<bool, CmdError> {
     Ok(state.read().await.client.is_some())
@@ -172,7 +279,9 @@ async fn is_logged_in<'a>(state: State<'a, Arc<RwLock<AppState>>>) -> Result<boo
 
 #[tauri::command]
 async fn logout<'a>(state: State<'a, Arc<RwLock<AppState>>>) -> Result<String, CmdError> {
-    if let Some(client) = state.write().await.client.take() {
+    // Release the lock before disconnecting, so other commands are not blocked meanwhile
+    let client = state.write().await.client.take();
+    if let Some(client) = client {
         if let Err(e) = client.disconnect().await {
             return Err(Error::from(e).into());
         }
@@ -543,14 +652,9 @@ async fn extract_ocel(app: AppHandle) -> Result<String, CmdError> {
             //     "Gathered jobs per time in {:?}",
             //     now.elapsed()
             // );
-            let all_jobs_ids: HashSet<String> = glob(&src_path.join("*/").to_string_lossy())
-                .expect("Glob failed")
-                .par_bridge()
-                .flat_map(|entry| match entry {
-                    Ok(j) => j.file_name().and_then(|n| n.to_str().map(String::from)),
-                    Err(_) => None,
-                })
-                .collect();
+            let history = History::new(DirectoryStore::new(src_path)).unwrap();
+            let all_jobs_ids: HashSet<String> =
+                history.source().job_ids().unwrap().into_iter().collect();
             println!("First job ID: {:?}", all_jobs_ids.iter().next());
             // let all_jobs_ids: HashSet<&String> = jobs_per_time.values().flatten().collect();
             println!(
@@ -570,21 +674,15 @@ async fn extract_ocel(app: AppHandle) -> Result<String, CmdError> {
                 .par_iter()
                 .flat_map(|job_id| {
                     let mut events: Vec<_> = Vec::new();
-                    let mut g = glob(&src_path.join(job_id).join("*.json").to_string_lossy())
-                        .expect("Glob failed");
+                    // All observed states of the job (i.e., with the changes applied in order)
+                    let mut states = history
+                        .job_history(job_id)
+                        .inspect_err(|e| eprintln!("Failed to read job {job_id}: {e:?}"))
+                        .ok()?
+                        .into_iter();
                     let mut start_ev: Option<OCELEvent> = None;
-                    if let Some(Ok(d)) = g.next() {
-                        let dt = extract_timestamp(
-                            &d.file_name()
-                                .unwrap()
-                                .to_string_lossy()
-                                .replace(".json", ""),
-                        );
+                    if let Some((dt, mut row)) = states.next() {
                         // Initial Job Data
-                        // This is assumed to then be the first result (i.e., initial job data)
-                        let mut row: SqueueRow = serde_json::from_reader(File::open(&d).unwrap())
-                            .inspect_err(|e| eprintln!("Failed to deser.: {d:?}, {e:?}"))
-                            .unwrap();
 
                         let account = match row.account.as_str() {
                             "default" => {
@@ -698,30 +796,15 @@ async fn extract_ocel(app: AppHandle) -> Result<String, CmdError> {
                             }
                         }
                         let mut last_dt = dt;
-                        for d in g.flatten() {
-                            let file_name = d.file_name().unwrap().to_string_lossy();
-                            if !file_name.contains("DELTA") {
-                                // eprintln!("JobID: [{}] No DELTA in filename {}", job_id, file_name);
-                                continue;
-                            }
-                            let dt = extract_timestamp(
-                                &file_name.replace("DELTA-", "").replace(".json", ""),
-                            );
+                        for (dt, next_row) in states {
                             if last_dt > dt {
                                 eprintln!("Going backwards in time! {} {last_dt} -> {dt}", o.id);
                             }
 
                             last_dt = dt;
-                            type D = <SqueueRow as StructDiff>::Diff;
-                            let delta: Vec<D> = serde_json::from_reader(File::open(&d).unwrap())
-                                .inspect_err(|e| {
-                                    println!(
-                                        "Serde deser. failed for {} in file {:?}; {e:?}",
-                                        job_id, d
-                                    )
-                                })
-                                .unwrap();
-                            row.apply_mut(delta.clone());
+                            type D = SqueueRowDelta;
+                            let delta: Vec<D> = row.diff(&next_row);
+                            row = next_row;
                             for df in delta {
                                 // println!("{:?}", df);
                                 match df {
@@ -890,6 +973,9 @@ async fn extract_ocel(app: AppHandle) -> Result<String, CmdError> {
                                         }
                                     }
                                     D::submit_time(_) => {}
+                                    D::user(_) => {}
+                                    D::qos(_) => {}
+                                    D::node_list(_) => {}
                                 };
                             }
                         }
@@ -956,36 +1042,45 @@ async fn extract_ocel(app: AppHandle) -> Result<String, CmdError> {
 
 #[tauri::command]
 async fn start_test_job<'a>(state: State<'a, Arc<RwLock<AppState>>>) -> Result<String, CmdError> {
-    let mut x = state.write().await;
-    if let Some(client) = x.client.take() {
-        let arc = Arc::new(client);
+    // Clone the session handle, so the state is not locked while the command runs
+    let client = state.read().await.client.clone();
+    if let Some(client) = &client {
         let res = submit_job(
-            arc.clone(),
-            JobOptions {
-                root_dir: "hpc_experiments".to_string(),
-                num_cpus: 12,
-                time: "0-00:01:00".to_string(),
-                local_forwarding: Some(JobLocalForwarding { local_port: 3000, relay_port: 3000, relay_addr: "login23-1".to_string() }),
-                command: "./ocpq-server".to_string(),
-                files_to_upload: vec![
-                    JobFilesToUpload {
+            client,
+            JobOptions::new("hpc_experiments", "./ocpq-server")
+                .with_num_cpus(12)
+                .with_time("0-00:01:00")
+                .with_file_to_upload(JobFilesToUpload {
                     local_path: PathBuf::from("/home/aarkue/doc/projects/OCPQ/backend/target/x86_64-unknown-linux-gnu/release/ocedeclare-web-server"),
                     remote_subpath: "".to_string(),
                     remote_file_name: "ocpq-server".to_string(),
-                },
-            //     JobFilesToUpload {
+                }),
+            //     .with_file_to_upload(JobFilesToUpload {
             //     local_path: PathBuf::from("/home/aarkue/dow/ocel/bpic2017-o2o-workflow-qualifier.json"),
             //     remote_subpath: "../data".to_string(),
             //     remote_file_name: "bpic2017-o2o-workflow-qualifier.json".to_string(),
-            // }
-                ].into_iter().collect(),
-            },
+            // }),
         )
         .await;
-        // Get our client back
-        x.client = Some(Arc::into_inner(arc).unwrap());
         return match res {
-            Ok((_folder_id, job_id)) => Ok(job_id),
+            Ok((_folder_id, job_id)) => {
+                // Forward the port of the server to the local machine once the job is running
+                let state = Arc::clone(state.inner());
+                let session = client.session().clone();
+                let forward_job_id = job_id.clone();
+                tauri::async_runtime::spawn(async move {
+                    let res = async {
+                        let client = session.client().await?;
+                        forward_to_job(&client, &forward_job_id, 3000, 3000).await
+                    }
+                    .await;
+                    match res {
+                        Ok(forward) => state.write().await.job_forward = Some(forward),
+                        Err(e) => eprintln!("Could not forward port of job {forward_job_id}: {e:?}"),
+                    }
+                });
+                Ok(job_id)
+            }
             Err(e) => Err(e.into()),
         };
     }
@@ -997,7 +1092,8 @@ async fn check_job_status<'a>(
     state: State<'a, Arc<RwLock<AppState>>>,
     job_id: String,
 ) -> Result<JobStatus, CmdError> {
-    match &state.read().await.client {
+    let client = state.read().await.client.clone();
+    match &client {
         Some(client) => {
             let status = get_job_status(client, &job_id).await?;
             Ok(status)
@@ -1039,6 +1135,8 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .manage(Arc::new(RwLock::new(AppState::default())))
+        .manage(HostKeyPrompt::default())
+        .manage(AuthPrompt::default())
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             run_squeue,
@@ -1047,6 +1145,8 @@ pub fn run() {
             get_loop_info,
             extract_ocel,
             login,
+            answer_host_key_prompt,
+            answer_auth_prompt,
             logout,
             is_logged_in,
             get_squeue,
@@ -1057,12 +1157,35 @@ pub fn run() {
         .expect("error while running tauri application");
 }
 
+/// Pending answers to host key prompts by `{host}:{port}` (see `login`)
+///
+/// All pending prompts of a host are answered at once.
+#[derive(Debug, Default)]
+struct HostKeyPrompt(std::sync::Mutex<HashMap<String, Vec<tokio::sync::oneshot::Sender<bool>>>>);
+
+/// Pending answers to keyboard-interactive login prompts by host (see `login`)
+///
+/// Prompts of the same host are answered in order.
+#[derive(Debug, Default)]
+struct AuthPrompt(
+    std::sync::Mutex<HashMap<String, VecDeque<tokio::sync::oneshot::Sender<Option<String>>>>>,
+);
+
 #[derive(Debug, Default)]
 struct AppState {
-    pub client: Option<Client>,
+    pub client: Option<SessionPool>,
+    pub job_forward: Option<PortForward>,
     pub looping_info: Option<LoopingInfo>,
 }
 
+/// Storage backend of the `squeue` loop
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum StoreKind {
+    Directory,
+    Sqlite,
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct LoopingInfo {
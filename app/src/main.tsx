@@ -1,5 +1,5 @@
 import App from "@/App";
-import { SqueueRow } from "@/AppContext";
+import { AuthPromptInfo, HostKeyInfo, SessionEvent, SqueueRow } from "@/AppContext";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import React from "react";
@@ -11,8 +11,8 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
       runSqueue: async () => {
         return await invoke("run_squeue");
       },
-      startSqueueLoop: async (loopingInterval) => {
-        return await invoke("start_squeue_loop", { loopingInterval })
+      startSqueueLoop: async (loopingInterval, store) => {
+        return await invoke("start_squeue_loop", { loopingInterval, store })
       },
       stopSqueueLoop: async () => {
         return await invoke("stop_squeue_loop")
@@ -29,6 +29,18 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
       login: async (cfg) => {
         return await invoke("login", { cfg });
       },
+      listenHostKeyPrompt: (listener) => {
+        return listen<HostKeyInfo>("host-key-prompt", (e) => listener(e.payload))
+      },
+      answerHostKeyPrompt: async (host, port, trusted) => {
+        return await invoke("answer_host_key_prompt", { host, port, trusted })
+      },
+      listenAuthPrompt: (listener) => {
+        return listen<AuthPromptInfo>("auth-prompt", (e) => listener(e.payload))
+      },
+      answerAuthPrompt: async (host, answer) => {
+        return await invoke("answer_auth_prompt", { host, answer })
+      },
       logout: async () => {
         return await invoke("logout");
       },
@@ -38,6 +50,9 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
       listenSqueue: (listener) => {
         return listen<[string,SqueueRow[]]>("squeue-rows", (e) => listener(e.payload))
       },
+      listenSessionEvents: (listener) => {
+        return listen<SessionEvent>("ssh-session-event", (e) => listener(e.payload))
+      },
       startTestJob: async () => {
         return await invoke("start_test_job")
       },
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, read_dir, remove_file, rename, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use zstd::stream::{read::Decoder, write::Encoder};
+
+use super::{
+    squeue::JobFinished,
+    store::{JobRecord, SnapshotDiff, SnapshotStore},
+};
+
+/// File extension of sealed segments (a single zstd frame)
+const SEALED_EXTENSION: &str = ".jsonl.zst";
+
+/// File extension of the segment currently written to (one zstd frame per snapshot)
+const OPEN_EXTENSION: &str = ".jsonl.zst.open";
+
+/// Default zstd compression level of an [`ArchiveStore`]
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A single entry of an archive, saved as one JSON line
+pub enum ArchiveRecord {
+    /// IDs of all jobs listed in a snapshot
+    Snapshot {
+        /// Time of the snapshot
+        time: DateTime<Utc>,
+        /// IDs of all listed jobs
+        job_ids: HashSet<String>,
+    },
+    /// A record of a single job
+    Job {
+        /// ID of the job
+        job_id: String,
+        /// The saved record
+        record: JobRecord,
+    },
+}
+
+impl ArchiveRecord {
+    /// Time of the record
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            ArchiveRecord::Snapshot { time, .. } => *time,
+            ArchiveRecord::Job { record, .. } => record.time(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// When an [`ArchiveStore`] starts a new segment
+pub enum SegmentRotation {
+    /// Start a new segment every day (in UTC)
+    #[default]
+    Daily,
+    /// Start a new segment once the active one exceeds the given compressed size (in bytes)
+    Size(u64),
+}
+
+#[derive(Debug)]
+struct ActiveSegment {
+    name: String,
+    date: NaiveDate,
+    file: File,
+    size: u64,
+}
+
+#[derive(Debug)]
+/// Store appending snapshots and changes to zstd-compressed segment files (e.g., for long-term collection)
+///
+/// Records (see [`ArchiveRecord`]) are saved as JSON lines, each snapshot as its own zstd frame,
+/// into the active segment `{date}-{n}.jsonl.zst.open`.
+/// When the segment is rotated (see [`SegmentRotation`]), it is sealed: recompressed as a single frame into `{date}-{n}.jsonl.zst`.
+/// Segments left open (e.g., after a crash) are sealed when the archive is opened again.
+/// Use an [`ArchiveReader`] to read the records back.
+pub struct ArchiveStore {
+    path: PathBuf,
+    rotation: SegmentRotation,
+    compression_level: i32,
+    active: Option<ActiveSegment>,
+}
+
+impl ArchiveStore {
+    /// Open (or create) the archive in the given directory, sealing segments left open
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        create_dir_all(&path)?;
+        for (name, sealed) in list_segments(&path)? {
+            if !sealed {
+                seal_segment(&path, &name, DEFAULT_COMPRESSION_LEVEL)?;
+            }
+        }
+        Ok(Self {
+            path,
+            rotation: SegmentRotation::default(),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            active: None,
+        })
+    }
+
+    /// Use the passed rotation of segments
+    pub fn with_rotation(self, rotation: SegmentRotation) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Use the passed zstd compression level (`1` to `22`, see [`DEFAULT_COMPRESSION_LEVEL`])
+    pub fn with_compression_level(self, compression_level: i32) -> Self {
+        Self {
+            compression_level,
+            ..self
+        }
+    }
+
+    /// Directory of the archive
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Seal the active segment (e.g., before shutting down), the next records start a new segment
+    pub fn seal(&mut self) -> Result<(), Error> {
+        if let Some(segment) = self.active.take() {
+            drop(segment.file);
+            seal_segment(&self.path, &segment.name, self.compression_level)?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, time: DateTime<Utc>, records: &[ArchiveRecord]) -> Result<(), Error> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let rotate = self
+            .active
+            .as_ref()
+            .is_some_and(|segment| match self.rotation {
+                SegmentRotation::Daily => segment.date != time.date_naive(),
+                SegmentRotation::Size(max_size) => segment.size >= max_size,
+            });
+        if rotate {
+            self.seal()?;
+        }
+        let segment = match &mut self.active {
+            Some(segment) => segment,
+            None => self
+                .active
+                .insert(create_segment(&self.path, time.date_naive())?),
+        };
+        let mut lines = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut lines, record)?;
+            lines.push(b'\n');
+        }
+        let frame = zstd::encode_all(lines.as_slice(), self.compression_level)?;
+        segment.file.write_all(&frame)?;
+        segment.size += frame.len() as u64;
+        Ok(())
+    }
+}
+
+impl SnapshotStore for ArchiveStore {
+    fn save_diff(&mut self, diff: &SnapshotDiff<'_>) -> Result<(), Error> {
+        let mut records = vec![ArchiveRecord::Snapshot {
+            time: diff.time,
+            job_ids: diff.job_ids.clone(),
+        }];
+        let job = |job_id: &str, record| ArchiveRecord::Job {
+            job_id: job_id.to_string(),
+            record,
+        };
+        for disappeared in diff.disappeared {
+            records.push(job(
+                &disappeared.job_id,
+                JobRecord::Disappeared(disappeared.clone()),
+            ));
+        }
+        for row in &diff.new_jobs {
+            records.push(job(
+                &row.job_id,
+                JobRecord::Observed(diff.time, Box::new((*row).clone())),
+            ));
+        }
+        for (job_id, delta) in &diff.deltas {
+            records.push(job(job_id, JobRecord::Delta(diff.time, delta.clone())));
+        }
+        self.append(diff.time, &records)
+    }
+
+    fn save_finished(&mut self, finished: &[JobFinished]) -> Result<(), Error> {
+        let Some(time) = finished.iter().map(|f| f.time).max() else {
+            return Ok(());
+        };
+        let records: Vec<_> = finished
+            .iter()
+            .map(|f| ArchiveRecord::Job {
+                job_id: f.job_id.clone(),
+                record: JobRecord::Finished(f.clone()),
+            })
+            .collect();
+        self.append(time, &records)
+    }
+}
+
+/// Names of all segments of an archive (sorted), and whether they are sealed
+fn list_segments(path: &Path) -> Result<Vec<(String, bool)>, Error> {
+    let mut segments = Vec::new();
+    for entry in read_dir(path)? {
+        let file_name = entry?.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        if let Some(name) = file_name.strip_suffix(OPEN_EXTENSION) {
+            segments.push((name.to_string(), false));
+        } else if let Some(name) = file_name.strip_suffix(SEALED_EXTENSION) {
+            segments.push((name.to_string(), true));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+fn create_segment(path: &Path, date: NaiveDate) -> Result<ActiveSegment, Error> {
+    let prefix = format!("{date}-");
+    let n = list_segments(path)?
+        .iter()
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .count();
+    let name = format!("{prefix}{n:04}");
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.join(format!("{name}{OPEN_EXTENSION}")))?;
+    let size = file.metadata()?.len();
+    Ok(ActiveSegment {
+        name,
+        date,
+        file,
+        size,
+    })
+}
+
+/// Recompress an open segment into a single frame
+///
+/// An incomplete last frame (e.g., after a crash) is dropped.
+fn seal_segment(path: &Path, name: &str, compression_level: i32) -> Result<(), Error> {
+    let open_path = path.join(format!("{name}{OPEN_EXTENSION}"));
+    let sealed_path = path.join(format!("{name}{SEALED_EXTENSION}"));
+    let tmp_path = path.join(format!("{name}{SEALED_EXTENSION}.tmp"));
+    let mut encoder = Encoder::new(BufWriter::new(File::create(&tmp_path)?), compression_level)?;
+    for line in BufReader::new(Decoder::new(File::open(&open_path)?)?).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        encoder.write_all(line.as_bytes())?;
+        encoder.write_all(b"\n")?;
+    }
+    encoder.finish()?.flush()?;
+    rename(&tmp_path, &sealed_path)?;
+    remove_file(&open_path)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+/// Reader of the records of an archive written by an [`ArchiveStore`]
+pub struct ArchiveReader {
+    path: PathBuf,
+    segments: Vec<(String, bool)>,
+}
+
+impl ArchiveReader {
+    /// Open the archive in the given directory
+    ///
+    /// Segments created afterwards (e.g., by a running loop) are not read.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let segments = list_segments(&path)?;
+        Ok(Self { path, segments })
+    }
+
+    /// Paths of all segments (sorted by time)
+    pub fn segment_paths(&self) -> Vec<PathBuf> {
+        self.segments
+            .iter()
+            .map(|(name, sealed)| segment_path(&self.path, name, *sealed))
+            .collect()
+    }
+
+    /// Iterate over all records in time order, reading one segment after another
+    ///
+    /// An incomplete end of the active segment (i.e., while it is written to) is skipped.
+    pub fn records(&self) -> ArchiveRecords {
+        ArchiveRecords {
+            segments: self
+                .segments
+                .iter()
+                .map(|(name, sealed)| (segment_path(&self.path, name, *sealed), *sealed))
+                .collect::<Vec<_>>()
+                .into_iter(),
+            current: None,
+        }
+    }
+}
+
+fn segment_path(path: &Path, name: &str, sealed: bool) -> PathBuf {
+    match sealed {
+        true => path.join(format!("{name}{SEALED_EXTENSION}")),
+        false => path.join(format!("{name}{OPEN_EXTENSION}")),
+    }
+}
+
+type SegmentLines = Lines<BufReader<Decoder<'static, BufReader<File>>>>;
+
+/// Iterator over the records of an archive (see [`ArchiveReader::records`])
+pub struct ArchiveRecords {
+    segments: std::vec::IntoIter<(PathBuf, bool)>,
+    current: Option<(SegmentLines, bool)>,
+}
+
+impl std::fmt::Debug for ArchiveRecords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveRecords")
+            .field("segments", &self.segments)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for ArchiveRecords {
+    type Item = Result<ArchiveRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((lines, sealed)) = &mut self.current else {
+                let (path, sealed) = self.segments.next()?;
+                match File::open(&path).and_then(Decoder::new) {
+                    Ok(decoder) => self.current = Some((BufReader::new(decoder).lines(), sealed)),
+                    Err(e) => return Some(Err(e.into())),
+                }
+                continue;
+            };
+            let sealed = *sealed;
+            let record = match lines.next() {
+                Some(line) => line
+                    .map_err(Error::from)
+                    .and_then(|line| Ok(serde_json::from_str(&line)?)),
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            if record.is_err() {
+                self.current = None;
+                if !sealed {
+                    continue;
+                }
+            }
+            return Some(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        data_extraction::{
+            get_squeue_res, squeue_diff, ArchiveReader, ArchiveRecord, ArchiveStore,
+            DirectoryStore, SegmentRotation, SqueueDiffState, SqueueMode,
+        },
+        executor::MockExecutor,
+    };
+
+    #[tokio::test]
+    async fn test_archive() {
+        let path = std::env::temp_dir().join("slurry_test_archive");
+        // Remove leftovers of previous (failed) runs
+        let _ = std::fs::remove_dir_all(&path);
+        let mut dir_store = DirectoryStore::new(path.join("squeue"));
+        let mut state = SqueueDiffState::default();
+        for state_name in ["PENDING", "RUNNING"] {
+            let executor = MockExecutor::new().with_stdout(
+                "squeue",
+                format!("acc|123|n001|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|{state_name}|None|N/A|2025-01-14T09:59:00|/home/user|./run.sh\n"),
+            );
+            squeue_diff(
+                || get_squeue_res(&SqueueMode::ALL, &executor),
+                &mut dir_store,
+                &mut state,
+            )
+            .await
+            .unwrap();
+        }
+        squeue_diff(
+            || async { Ok((Utc::now(), Vec::new())) },
+            &mut dir_store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+
+        // Every snapshot starts a new segment
+        let archive_path = path.join("archive");
+        let mut archive = ArchiveStore::open(&archive_path)
+            .unwrap()
+            .with_rotation(SegmentRotation::Size(0));
+        assert_eq!(dir_store.copy_to(&mut archive).unwrap(), 3);
+        let reader = ArchiveReader::open(&archive_path).unwrap();
+        assert_eq!(reader.segment_paths().len(), 3);
+        // The last segment is still open, but can be read
+        let records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 6);
+        assert!(records.windows(2).all(|r| r[0].time() <= r[1].time()));
+        assert!(matches!(records[0], ArchiveRecord::Snapshot { .. }));
+
+        // Reopening seals the open segment
+        drop(archive);
+        ArchiveStore::open(&archive_path).unwrap();
+        let reader = ArchiveReader::open(&archive_path).unwrap();
+        assert!(reader
+            .segment_paths()
+            .iter()
+            .all(|p| p.to_string_lossy().ends_with(".jsonl.zst")));
+        assert_eq!(reader.records().count(), 6);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use structdiff::StructDiff;
+
+use super::{
+    squeue::SqueueRow,
+    store::{cleaned_time, read_json, DirectoryStore, JobRecord},
+};
+
+/// Saved snapshots and changes which can be read back (see [`History`])
+///
+/// Implemented by [`DirectoryStore`] and `SqliteStore` (requires the `sqlite` feature).
+pub trait HistorySource {
+    /// Times of all saved snapshots (sorted)
+    fn snapshot_times(&self) -> Result<Vec<DateTime<Utc>>, Error>;
+
+    /// IDs of all jobs listed in the snapshot at the given time
+    fn snapshot_job_ids(&self, time: DateTime<Utc>) -> Result<HashSet<String>, Error>;
+
+    /// All saved records of a job (sorted by time)
+    fn job_records(&self, job_id: &str) -> Result<Vec<JobRecord>, Error>;
+}
+
+impl HistorySource for DirectoryStore {
+    fn snapshot_times(&self) -> Result<Vec<DateTime<Utc>>, Error> {
+        // Directories without index (e.g., not written by a `DirectoryStore`) are listed instead
+        match self.read_index()? {
+            Some(times) => Ok(times),
+            None => self.scan_snapshot_times(),
+        }
+    }
+
+    fn snapshot_job_ids(&self, time: DateTime<Utc>) -> Result<HashSet<String>, Error> {
+        read_json(&self.path().join(format!("{}.json", cleaned_time(&time))))
+    }
+
+    fn job_records(&self, job_id: &str) -> Result<Vec<JobRecord>, Error> {
+        self.read_job(job_id)
+    }
+}
+
+#[derive(Debug)]
+/// Reconstructs past states of jobs and of the whole queue by applying the saved changes in order
+///
+/// The times of all snapshots are read once from the index of the source (e.g., `index.jsonl` of a [`DirectoryStore`])
+/// when the history is created and kept in memory (see [`History::refresh`]),
+/// so that [`History::queue_at`] only reads the jobs listed at the requested time.
+pub struct History<S> {
+    source: S,
+    snapshot_times: Vec<DateTime<Utc>>,
+}
+
+impl<S: HistorySource> History<S> {
+    /// Create a new history, reading the snapshot times of the given source
+    pub fn new(source: S) -> Result<Self, Error> {
+        let snapshot_times = source.snapshot_times()?;
+        Ok(Self {
+            source,
+            snapshot_times,
+        })
+    }
+
+    /// Read the snapshot times again (e.g., if the source is still written to by a running loop)
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        self.snapshot_times = self.source.snapshot_times()?;
+        Ok(())
+    }
+
+    /// The underlying source of the history
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Times of all snapshots, as read by [`History::new`] or [`History::refresh`] (sorted)
+    pub fn snapshot_times(&self) -> &[DateTime<Utc>] {
+        &self.snapshot_times
+    }
+
+    /// All observed states of a job (i.e., when it was first listed and after every change), sorted by time
+    pub fn job_history(&self, job_id: &str) -> Result<Vec<(DateTime<Utc>, SqueueRow)>, Error> {
+        Ok(replay(
+            self.source.job_records(job_id)?,
+            DateTime::<Utc>::MAX_UTC,
+        ))
+    }
+
+    /// All jobs listed in the queue at the given time (i.e., in the latest snapshot at or before it), sorted by job ID
+    pub fn queue_at(&self, time: DateTime<Utc>) -> Result<Vec<SqueueRow>, Error> {
+        let index = self.snapshot_times.partition_point(|t| *t <= time);
+        let Some(snapshot_time) = index.checked_sub(1).map(|i| self.snapshot_times[i]) else {
+            return Ok(Vec::new());
+        };
+        let mut job_ids: Vec<_> = self
+            .source
+            .snapshot_job_ids(snapshot_time)?
+            .into_iter()
+            .collect();
+        job_ids.sort();
+        let mut rows = Vec::with_capacity(job_ids.len());
+        for job_id in job_ids {
+            let records = self.source.job_records(&job_id)?;
+            if let Some((_, row)) = replay(records, snapshot_time).pop() {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Apply the records of a job in order, returning every observed state until the given time
+fn replay(records: Vec<JobRecord>, until: DateTime<Utc>) -> Vec<(DateTime<Utc>, SqueueRow)> {
+    let mut states: Vec<(DateTime<Utc>, SqueueRow)> = Vec::new();
+    for record in records {
+        match record {
+            JobRecord::Observed(time, row) if time <= until => states.push((time, *row)),
+            JobRecord::Delta(time, delta) if time <= until => {
+                // Changes can only be applied to a known row
+                if let Some((_, row)) = states.last() {
+                    let row = row.apply_ref(delta);
+                    states.push((time, row));
+                }
+            }
+            _ => {}
+        }
+    }
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        data_extraction::{
+            get_squeue_res, squeue_diff, DirectoryStore, History, HistorySource, SnapshotStore,
+            SqueueDiffState, SqueueMode,
+        },
+        executor::MockExecutor,
+        JobState,
+    };
+
+    const ROW: &str = "acc|{id}|n001|1|2|1|N/A|(null)|(null)|{id}|grp|{id}|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|{state}|None|N/A|2025-01-14T09:59:00|/home/user|./run.sh\n";
+
+    /// Record three snapshots: job 1 pending, job 1 running and job 2 pending, only job 2 pending
+    async fn record_snapshots<S: SnapshotStore>(store: &mut S) -> Vec<chrono::DateTime<Utc>> {
+        let mut state = SqueueDiffState::default();
+        let mut times = Vec::new();
+        for jobs in [
+            vec![("1", "PENDING")],
+            vec![("1", "RUNNING"), ("2", "PENDING")],
+            vec![("2", "PENDING")],
+        ] {
+            let stdout: String = jobs
+                .iter()
+                .map(|(id, state)| ROW.replace("{id}", id).replace("{state}", state))
+                .collect();
+            let executor = MockExecutor::new().with_stdout("squeue", stdout);
+            let (time, _) = squeue_diff(
+                || get_squeue_res(&SqueueMode::ALL, &executor),
+                store,
+                &mut state,
+            )
+            .await
+            .unwrap();
+            times.push(time);
+            // Keep snapshot times distinct, even with millisecond precision
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+        times
+    }
+
+    fn check_history<S: HistorySource>(history: &History<S>, times: &[chrono::DateTime<Utc>]) {
+        assert_eq!(history.snapshot_times().len(), 3);
+        let job = history.job_history("1").unwrap();
+        assert_eq!(job.len(), 2);
+        assert_eq!(job[0].1.state, JobState::PENDING);
+        assert_eq!(job[1].1.state, JobState::RUNNING);
+
+        assert!(history
+            .queue_at(times[0] - chrono::Duration::seconds(1))
+            .unwrap()
+            .is_empty());
+        let queue = history.queue_at(times[0]).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].state, JobState::PENDING);
+        let queue = history
+            .queue_at(times[1] + chrono::Duration::microseconds(500))
+            .unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].job_id, "1");
+        assert_eq!(queue[0].state, JobState::RUNNING);
+        let queue = history.queue_at(Utc::now()).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].job_id, "2");
+    }
+
+    #[tokio::test]
+    async fn test_directory_history() {
+        let path = std::env::temp_dir().join("slurry_test_directory_history");
+        // Remove leftovers of previous (failed) runs
+        let _ = std::fs::remove_dir_all(&path);
+        let mut store = DirectoryStore::new(&path);
+        let times = record_snapshots(&mut store).await;
+        assert_eq!(store.read_index().unwrap(), Some(times.clone()));
+        check_history(&History::new(store).unwrap(), &times);
+
+        // Directories without index are indexed again when saving into them
+        std::fs::remove_file(path.join("index.jsonl")).unwrap();
+        let mut store = DirectoryStore::new(&path);
+        assert_eq!(store.snapshot_times().unwrap(), times);
+        let mut times = [times, record_snapshots(&mut store).await].concat();
+        times.sort();
+        assert_eq!(store.read_index().unwrap(), Some(times));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_history() {
+        let mut store = crate::data_extraction::SqliteStore::open_in_memory().unwrap();
+        let times = record_snapshots(&mut store).await;
+        check_history(&History::new(store).unwrap(), &times);
+    }
+}
@@ -1,7 +1,69 @@
 /// Module for extracting data using the `squeue` command
 pub mod squeue;
 
-pub use squeue::{get_squeue_res, get_squeue_res_locally, squeue_diff, SqueueMode};
+/// Module for extracting job accounting data using the `sacct` command
+pub mod sacct;
+
+/// Module for storing `squeue` snapshots and their changes (e.g., in a directory)
+pub mod store;
+
+/// Module for storing `squeue` snapshots and their changes in an `SQLite` database
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+
+/// Module for exporting saved `squeue` snapshots as Parquet tables (e.g., for pandas, polars or `DuckDB`)
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
+
+/// Module for archiving `squeue` snapshots in compressed, rotating segment files
+#[cfg(feature = "archive")]
+pub mod archive;
+
+/// Module for reconstructing past states of jobs and the queue from saved snapshots
+pub mod history;
+
+/// Module for parsing the `--json` output of SLURM commands
+pub mod slurm_json;
+
+/// Module for extracting node and partition information using `scontrol`
+pub mod nodes;
+
+pub use squeue::{
+    get_squeue_res, get_squeue_res_json, get_squeue_res_locally, get_squeue_res_with_format,
+    record_finished_jobs, squeue_diff, JobDisappeared, JobFinished, SqueueDiffState, SqueueMode,
+    FINISHED_LOOKUP_HOURS,
+};
+
+pub use store::{DirectoryStore, JobRecord, SnapshotDiff, SnapshotStore, SqueueRowDelta};
+
+#[cfg(feature = "sqlite")]
+pub use sqlite_store::SqliteStore;
+
+#[cfg(feature = "parquet")]
+pub use parquet_export::{export_parquet, ParquetExportReport};
+
+pub use history::{History, HistorySource};
+
+#[cfg(feature = "archive")]
+pub use archive::{ArchiveReader, ArchiveRecord, ArchiveRecords, ArchiveStore, SegmentRotation};
+
+pub use sacct::{
+    get_sacct_res, get_sacct_res_json, get_sacct_res_locally, get_sacct_res_with_format, SacctMode,
+    SacctRow,
+};
+
+pub use slurm_json::{detect_output_format, SlurmOutputFormat};
+
+pub use nodes::{
+    expand_hostlist, get_node_info, get_node_info_locally, get_partition_info,
+    get_partition_info_locally, NodeInfo, NodeState, PartitionInfo,
+};
 
 #[cfg(feature = "ssh")]
 pub use squeue::get_squeue_res_ssh;
+
+#[cfg(feature = "ssh")]
+pub use sacct::get_sacct_res_ssh;
+
+#[cfg(feature = "ssh")]
+pub use nodes::{get_node_info_ssh, get_partition_info_ssh};
@@ -0,0 +1,422 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssh")]
+use crate::Client;
+
+use crate::{
+    executor::{CommandExecutor, LocalExecutor},
+    parse_slurm_duration,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Base state of a SLURM node (according to `scontrol show node`)
+///
+/// Documentation taken from <https://slurm.schedmd.com/sinfo.html#SECTION_NODE-STATE-CODES>.
+pub enum NodeState {
+    /// The node is not allocated to any jobs and is available for use.
+    IDLE,
+    /// The node has some of its CPUs allocated while others are idle.
+    MIXED,
+    /// The node has been allocated to one or more jobs.
+    ALLOCATED,
+    /// All CPUs allocated to the job(s) on this node are in the process of completing.
+    COMPLETING,
+    /// The node is unavailable for use.
+    DOWN,
+    /// The node has been removed from service by the administrator (and no longer runs any jobs).
+    DRAINED,
+    /// The node is in the process of being removed from service (but still runs jobs).
+    DRAINING,
+    /// The node is currently not fully configured, but expected to be available in the future.
+    FUTURE,
+    /// The SLURM controller has just started and the node's state has not yet been determined.
+    UNKNOWN,
+    /// Other node state, specifying the concrete node state as a [`String`]
+    OTHER(String),
+}
+
+impl FromStr for NodeState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "IDLE" => Ok(Self::IDLE),
+            "MIXED" => Ok(Self::MIXED),
+            "ALLOCATED" => Ok(Self::ALLOCATED),
+            "COMPLETING" => Ok(Self::COMPLETING),
+            "DOWN" => Ok(Self::DOWN),
+            "DRAINED" => Ok(Self::DRAINED),
+            "DRAINING" => Ok(Self::DRAINING),
+            "FUTURE" => Ok(Self::FUTURE),
+            "UNKNOWN" => Ok(Self::UNKNOWN),
+            s => Ok(Self::OTHER(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Information about a node of the SLURM cluster (parsed from `scontrol show node`)
+pub struct NodeInfo {
+    /// "`NodeName`"
+    pub name: String,
+    /// Base state of the node (first part of "State")
+    pub state: NodeState,
+    /// Additional state flags (e.g., `DRAIN`, `NOT_RESPONDING`, `RESERVED` or `MAINTENANCE`)
+    pub state_flags: Vec<String>,
+    /// Total number of CPUs ("`CPUTot`")
+    pub cpus_total: usize,
+    /// Number of allocated CPUs ("`CPUAlloc`")
+    pub cpus_alloc: usize,
+    /// Current CPU load ("`CPULoad`")
+    pub cpu_load: Option<f64>,
+    /// Configured memory in megabytes ("`RealMemory`")
+    pub real_memory: u64,
+    /// Allocated memory in megabytes ("`AllocMem`")
+    pub alloc_memory: u64,
+    /// Free memory in megabytes ("`FreeMem`")
+    pub free_memory: Option<u64>,
+    /// Generic resources (e.g., `gpu:a100:4`) of the node ("Gres")
+    pub gres: Vec<String>,
+    /// Active features of the node ("`ActiveFeatures`")
+    pub features: Vec<String>,
+    /// Partitions containing this node ("Partitions")
+    pub partitions: Vec<String>,
+    /// Reason why the node is unavailable, e.g., why it is drained ("Reason")
+    pub reason: Option<String>,
+}
+
+impl NodeInfo {
+    /// Whether the node is drained or currently draining
+    pub fn is_drained(&self) -> bool {
+        matches!(self.state, NodeState::DRAINED | NodeState::DRAINING)
+            || self.state_flags.iter().any(|f| f == "DRAIN")
+    }
+
+    /// Whether new jobs can currently be scheduled on this node
+    pub fn is_available(&self) -> bool {
+        matches!(self.state, NodeState::IDLE | NodeState::MIXED)
+            && !self.is_drained()
+            && !self.state_flags.iter().any(|f| f == "NOT_RESPONDING")
+    }
+
+    fn parse_from_map(map: &HashMap<&str, String>) -> Result<Self, Error> {
+        let name = get_value(map, "NodeName").ok_or(Error::msg("Missing node name."))?;
+        // e.g., MIXED+DRAIN or DOWN* (* = not responding)
+        let state_str = get_value(map, "State").unwrap_or_default();
+        let (state_str, not_responding) = match state_str.strip_suffix("*") {
+            Some(s) => (s, true),
+            None => (state_str, false),
+        };
+        let mut states = state_str.split("+");
+        let state = states.next().unwrap_or_default().parse()?;
+        let mut state_flags: Vec<String> = states.map(String::from).collect();
+        if not_responding {
+            state_flags.push(String::from("NOT_RESPONDING"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            state,
+            state_flags,
+            cpus_total: get_value(map, "CPUTot").unwrap_or("0").parse()?,
+            cpus_alloc: get_value(map, "CPUAlloc").unwrap_or("0").parse()?,
+            cpu_load: get_value(map, "CPULoad").and_then(|s| s.parse().ok()),
+            real_memory: get_value(map, "RealMemory").unwrap_or("0").parse()?,
+            alloc_memory: get_value(map, "AllocMem").unwrap_or("0").parse()?,
+            free_memory: get_value(map, "FreeMem").and_then(|s| s.parse().ok()),
+            gres: get_list(map, "Gres"),
+            features: get_list(map, "ActiveFeatures"),
+            partitions: get_list(map, "Partitions"),
+            reason: get_value(map, "Reason").map(String::from),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Information about a partition of the SLURM cluster (parsed from `scontrol show partition`)
+pub struct PartitionInfo {
+    /// "`PartitionName`"
+    pub name: String,
+    /// State of the partition, e.g., `UP`, `DOWN`, `DRAIN` or `INACTIVE` ("State")
+    pub state: String,
+    /// Whether this is the default partition ("Default")
+    pub default: bool,
+    /// Nodes of the partition as a hostlist expression, e.g., `n[001-100]` ("Nodes")
+    ///
+    /// Use [`expand_hostlist`] to get the individual node names.
+    pub nodes: String,
+    /// Total number of nodes ("`TotalNodes`")
+    pub total_nodes: usize,
+    /// Total number of CPUs ("`TotalCPUs`")
+    pub total_cpus: usize,
+    /// Maximum time limit for jobs (`None` if unlimited, "`MaxTime`")
+    pub max_time: Option<Duration>,
+    /// Default time limit for jobs ("`DefaultTime`")
+    pub default_time: Option<Duration>,
+    /// Accounts allowed to use the partition ("`AllowAccounts`")
+    pub allow_accounts: Vec<String>,
+    /// QOS allowed to be used in the partition ("`AllowQos`")
+    pub allow_qos: Vec<String>,
+}
+
+impl PartitionInfo {
+    fn parse_from_map(map: &HashMap<&str, String>) -> Result<Self, Error> {
+        let name = get_value(map, "PartitionName").ok_or(Error::msg("Missing partition name."))?;
+        let parse_time = |key: &str| match get_value(map, key) {
+            None | Some("UNLIMITED") | Some("NONE") => None,
+            Some(s) => parse_slurm_duration(s).ok(),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            state: get_value(map, "State").unwrap_or_default().to_string(),
+            default: get_value(map, "Default") == Some("YES"),
+            nodes: get_value(map, "Nodes").unwrap_or_default().to_string(),
+            total_nodes: get_value(map, "TotalNodes").unwrap_or("0").parse()?,
+            total_cpus: get_value(map, "TotalCPUs").unwrap_or("0").parse()?,
+            max_time: parse_time("MaxTime"),
+            default_time: parse_time("DefaultTime"),
+            allow_accounts: get_list(map, "AllowAccounts"),
+            allow_qos: get_list(map, "AllowQos"),
+        })
+    }
+}
+
+// Values which are not set are reported as "(null)", "N/A" or "None"
+fn get_value<'a>(map: &'a HashMap<&str, String>, key: &str) -> Option<&'a str> {
+    match map.get(key).map(String::as_str) {
+        None | Some("") | Some("(null)") | Some("N/A") | Some("None") => None,
+        Some(s) => Some(s),
+    }
+}
+
+fn get_list(map: &HashMap<&str, String>, key: &str) -> Vec<String> {
+    get_value(map, key)
+        .map(|s| s.split(",").map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Parse a single line of `scontrol show ... --oneliner` output into its key-value pairs
+///
+/// Values may contain spaces (e.g., the `Reason` of a drained node),
+/// so tokens without a `=` are appended to the previous value.
+fn parse_scontrol_line(line: &str) -> HashMap<&str, String> {
+    let mut map: HashMap<&str, String> = HashMap::new();
+    let mut last_key: Option<&str> = None;
+    for token in line.split_whitespace() {
+        match (token.split_once("="), last_key) {
+            (Some((key, value)), _) if !key.is_empty() => {
+                map.insert(key, value.to_string());
+                last_key = Some(key);
+            }
+            (_, Some(key)) => {
+                if let Some(v) = map.get_mut(key) {
+                    v.push(' ');
+                    v.push_str(token);
+                }
+            }
+            _ => {}
+        }
+    }
+    map
+}
+
+/// Expand a SLURM hostlist expression into the individual host names
+///
+/// For example, `n[001-003,007],login1` is expanded to `n001`, `n002`, `n003`, `n007` and `login1`.
+pub fn expand_hostlist(hostlist: &str) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in hostlist.char_indices() {
+        match c {
+            '[' => depth += 1,
+            // Stray closing brackets must not stop the splitting
+            ']' => depth = (depth - 1).max(0),
+            ',' if depth == 0 => {
+                expand_host(&hostlist[start..i], &mut hosts);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    expand_host(&hostlist[start..], &mut hosts);
+    hosts
+}
+
+fn expand_host(host: &str, hosts: &mut Vec<String>) {
+    if host.is_empty() {
+        return;
+    }
+    // Malformed names (e.g., without closing bracket) are kept as they are
+    let Some((open, close)) = host
+        .find("[")
+        .and_then(|open| Some((open, open + host[open..].find("]")?)))
+    else {
+        hosts.push(host.to_string());
+        return;
+    };
+    let (prefix, suffix) = (&host[..open], &host[close + 1..]);
+    for range in host[open + 1..close].split(",") {
+        match range.split_once("-") {
+            Some((from, to)) => {
+                let width = from.len();
+                match (from.parse::<u64>(), to.parse::<u64>()) {
+                    (Ok(from), Ok(to)) => {
+                        for n in from..=to {
+                            // Recursively expand further bracket groups in the suffix
+                            expand_host(&format!("{prefix}{n:0width$}{suffix}"), hosts);
+                        }
+                    }
+                    _ => hosts.push(format!("{prefix}{range}{suffix}")),
+                }
+            }
+            None => expand_host(&format!("{prefix}{range}{suffix}"), hosts),
+        }
+    }
+}
+
+/// Get information on all nodes of the SLURM cluster using the provided [`CommandExecutor`]
+pub async fn get_node_info<E: CommandExecutor>(
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<NodeInfo>), Error> {
+    let result = executor
+        .execute("scontrol show node --oneliner")
+        .await?
+        .stdout;
+    let time: DateTime<Utc> = SystemTime::now().into();
+    let nodes = result
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(
+            |line| match NodeInfo::parse_from_map(&parse_scontrol_line(line)) {
+                Ok(node) => Some(node),
+                Err(err) => {
+                    println!("[!] {:?} for {:?}", err, &line);
+                    None
+                }
+            },
+        )
+        .collect();
+    Ok((time, nodes))
+}
+
+/// Get information on all partitions of the SLURM cluster using the provided [`CommandExecutor`]
+pub async fn get_partition_info<E: CommandExecutor>(
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<PartitionInfo>), Error> {
+    let result = executor
+        .execute("scontrol show partition --oneliner")
+        .await?
+        .stdout;
+    let time: DateTime<Utc> = SystemTime::now().into();
+    let partitions = result
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(
+            |line| match PartitionInfo::parse_from_map(&parse_scontrol_line(line)) {
+                Ok(partition) => Some(partition),
+                Err(err) => {
+                    println!("[!] {:?} for {:?}", err, &line);
+                    None
+                }
+            },
+        )
+        .collect();
+    Ok((time, partitions))
+}
+
+/// Get information on all nodes locally (i.e., not via SSH)
+pub async fn get_node_info_locally() -> Result<(DateTime<Utc>, Vec<NodeInfo>), Error> {
+    get_node_info(&LocalExecutor).await
+}
+
+/// Get information on all partitions locally (i.e., not via SSH)
+pub async fn get_partition_info_locally() -> Result<(DateTime<Utc>, Vec<PartitionInfo>), Error> {
+    get_partition_info(&LocalExecutor).await
+}
+
+#[cfg(feature = "ssh")]
+/// Get information on all nodes over SSH
+pub async fn get_node_info_ssh(client: &Client) -> Result<(DateTime<Utc>, Vec<NodeInfo>), Error> {
+    get_node_info(client).await
+}
+
+#[cfg(feature = "ssh")]
+/// Get information on all partitions over SSH
+pub async fn get_partition_info_ssh(
+    client: &Client,
+) -> Result<(DateTime<Utc>, Vec<PartitionInfo>), Error> {
+    get_partition_info(client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        data_extraction::nodes::{expand_hostlist, get_node_info, get_partition_info, NodeState},
+        executor::MockExecutor,
+    };
+
+    #[tokio::test]
+    async fn test_nodes_mock() {
+        let executor = MockExecutor::new()
+            .with_stdout(
+                "scontrol show node",
+                "NodeName=n001 Arch=x86_64 CoresPerSocket=24 CPUAlloc=8 CPUEfctv=48 CPUTot=48 CPULoad=7.95 AvailableFeatures=skylake,ib ActiveFeatures=skylake,ib Gres=gpu:v100:2 NodeAddr=n001 OS=Linux 5.14.0 #1 SMP RealMemory=190000 AllocMem=32000 FreeMem=150000 State=MIXED+DRAIN Partitions=batch,gpu Reason=Kernel update [root@2025-01-10T10:00:00]
+NodeName=n002 CPUAlloc=0 CPUTot=48 CPULoad=N/A ActiveFeatures=(null) Gres=(null) RealMemory=190000 AllocMem=0 FreeMem=N/A State=DOWN* Partitions=batch
+",
+            )
+            .with_stdout(
+                "scontrol show partition",
+                "PartitionName=batch AllowGroups=ALL AllowAccounts=ALL AllowQos=ALL Default=YES DefaultTime=01:00:00 MaxTime=2-00:00:00 Nodes=n[001-002] State=UP TotalCPUs=96 TotalNodes=2\n",
+            );
+        let (_time, nodes) = get_node_info(&executor).await.unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].state, NodeState::MIXED);
+        assert!(nodes[0].is_drained());
+        assert!(!nodes[0].is_available());
+        assert_eq!(nodes[0].gres, vec!["gpu:v100:2"]);
+        assert_eq!(nodes[0].partitions, vec!["batch", "gpu"]);
+        assert_eq!(
+            nodes[0].reason.as_deref(),
+            Some("Kernel update [root@2025-01-10T10:00:00]")
+        );
+        assert_eq!(nodes[1].state, NodeState::DOWN);
+        assert_eq!(nodes[1].state_flags, vec!["NOT_RESPONDING"]);
+        assert_eq!(nodes[1].cpu_load, None);
+        assert!(nodes[1].features.is_empty());
+
+        let (_time, partitions) = get_partition_info(&executor).await.unwrap();
+        assert_eq!(partitions.len(), 1);
+        assert!(partitions[0].default);
+        assert_eq!(
+            partitions[0].max_time,
+            Some(Duration::from_secs(2 * 24 * 60 * 60))
+        );
+        assert_eq!(expand_hostlist(&partitions[0].nodes), vec!["n001", "n002"]);
+    }
+
+    #[test]
+    fn test_expand_hostlist() {
+        assert_eq!(
+            expand_hostlist("n[001-003,007],login1"),
+            vec!["n001", "n002", "n003", "n007", "login1"]
+        );
+        assert_eq!(
+            expand_hostlist("r[1-2]n[01-02]"),
+            vec!["r1n01", "r1n02", "r2n01", "r2n02"]
+        );
+        // Malformed names do not panic
+        assert_eq!(expand_hostlist("a]b[1-2],c"), vec!["a]b1", "a]b2", "c"]);
+        assert_eq!(expand_hostlist("a]b[1"), vec!["a]b[1"]);
+        assert_eq!(expand_hostlist("n[1-x]"), vec!["n1-x"]);
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    fs::{create_dir_all, File},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Error;
+use arrow_array::{
+    ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use structdiff::StructDiff;
+
+use crate::JobState;
+
+use super::{
+    squeue::SqueueRow,
+    store::{DirectoryStore, JobRecord},
+};
+
+/// File name of the table with one row per job and observation (see [`export_parquet`])
+pub const OBSERVATIONS_FILE: &str = "observations.parquet";
+
+/// File name of the table with one row per state change of a job (see [`export_parquet`])
+pub const TRANSITIONS_FILE: &str = "transitions.parquet";
+
+/// Number of rows buffered before they are passed to the Parquet writer
+const BATCH_SIZE: usize = 8192;
+
+/// Number of rows per row group, bounding the memory used by the Parquet writer
+const ROW_GROUP_SIZE: usize = 65536;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Number of exported jobs and rows (see [`export_parquet`])
+pub struct ParquetExportReport {
+    /// Number of exported jobs
+    pub jobs: usize,
+    /// Number of rows of the observation table
+    pub observations: usize,
+    /// Number of rows of the state transition table
+    pub transitions: usize,
+}
+
+/// A change of the state of a job
+struct StateTransition {
+    job_id: String,
+    time: DateTime<Utc>,
+    from: Option<JobState>,
+    to: JobState,
+}
+
+/// Export the results of [`super::squeue_diff`] saved in a directory (see [`DirectoryStore`]) as Parquet tables
+///
+/// Two tables are written into `dest`:
+/// - [`OBSERVATIONS_FILE`]: one row per job and observation (i.e., when the job was first listed or changed) with all [`SqueueRow`] columns
+/// - [`TRANSITIONS_FILE`]: one row per state change of a job (`job_id`, `time`, `from_state`, `to_state`),
+///   including the final state looked up by [`super::record_finished_jobs`]
+///
+/// Jobs are read and written one after another, so the directory does not need to fit into memory.
+/// Times of observations are in UTC, times reported by SLURM (e.g., `start_time`) in the local time of the cluster.
+/// Durations are saved as seconds.
+/// Note that `time_left_secs` and `elapsed_secs` are not tracked by the saved changes, so they show the values of the first observation.
+pub fn export_parquet(src: &Path, dest: &Path) -> Result<ParquetExportReport, Error> {
+    let store = DirectoryStore::new(src);
+    create_dir_all(dest)?;
+    let mut observations = TableWriter::create(
+        &dest.join(OBSERVATIONS_FILE),
+        observation_schema(),
+        observation_columns,
+    )?;
+    let mut transitions = TableWriter::create(
+        &dest.join(TRANSITIONS_FILE),
+        transition_schema(),
+        transition_columns,
+    )?;
+    let mut jobs = 0;
+    for job_id in store.job_ids()? {
+        let mut current: Option<SqueueRow> = None;
+        for record in store.read_job(&job_id)? {
+            let from = current.as_ref().map(|row| row.state.clone());
+            let (time, to) = match record {
+                JobRecord::Observed(time, row) => {
+                    let state = row.state.clone();
+                    observations.push((time, (*row).clone()))?;
+                    current = Some(*row);
+                    (time, state)
+                }
+                JobRecord::Delta(time, delta) => {
+                    // Changes can only be applied to a known row
+                    let Some(row) = current.as_mut() else {
+                        continue;
+                    };
+                    row.apply_mut(delta);
+                    observations.push((time, row.clone()))?;
+                    (time, row.state.clone())
+                }
+                JobRecord::Finished(finished) => (finished.time, finished.state),
+                JobRecord::Disappeared(_) => continue,
+            };
+            if from.as_ref() != Some(&to) {
+                transitions.push(StateTransition {
+                    job_id: job_id.clone(),
+                    time,
+                    from,
+                    to,
+                })?;
+            }
+        }
+        jobs += 1;
+    }
+    Ok(ParquetExportReport {
+        jobs,
+        observations: observations.close()?,
+        transitions: transitions.close()?,
+    })
+}
+
+/// Parquet file written in batches of [`BATCH_SIZE`] rows
+struct TableWriter<T> {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    rows: Vec<T>,
+    to_columns: fn(&[T]) -> Vec<ArrayRef>,
+    written: usize,
+}
+
+impl<T> TableWriter<T> {
+    fn create(
+        path: &Path,
+        schema: Schema,
+        to_columns: fn(&[T]) -> Vec<ArrayRef>,
+    ) -> Result<Self, Error> {
+        let schema = Arc::new(schema);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_max_row_group_size(ROW_GROUP_SIZE)
+            .build();
+        let writer = ArrowWriter::try_new(File::create(path)?, Arc::clone(&schema), Some(props))?;
+        Ok(Self {
+            writer,
+            schema,
+            rows: Vec::with_capacity(BATCH_SIZE),
+            to_columns,
+            written: 0,
+        })
+    }
+
+    fn push(&mut self, row: T) -> Result<(), Error> {
+        self.rows.push(row);
+        if self.rows.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if !self.rows.is_empty() {
+            let batch =
+                RecordBatch::try_new(Arc::clone(&self.schema), (self.to_columns)(&self.rows))?;
+            self.writer.write(&batch)?;
+            self.written += self.rows.len();
+            self.rows.clear();
+        }
+        Ok(())
+    }
+
+    /// Write the remaining rows and return the total number of rows
+    fn close(mut self) -> Result<usize, Error> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(self.written)
+    }
+}
+
+fn utc_timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+}
+
+fn local_timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, None)
+}
+
+fn observation_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("job_id", DataType::Utf8, false),
+        Field::new("time", utc_timestamp(), false),
+        Field::new("account", DataType::Utf8, false),
+        Field::new("exec_host", DataType::Utf8, true),
+        Field::new("min_cpus", DataType::UInt64, false),
+        Field::new("cpus", DataType::UInt64, false),
+        Field::new("nodes", DataType::UInt64, false),
+        Field::new("end_time", local_timestamp(), true),
+        Field::new("dependency", DataType::Utf8, true),
+        Field::new("features", DataType::Utf8, false),
+        Field::new("array_job_id", DataType::Utf8, false),
+        Field::new("group", DataType::Utf8, false),
+        Field::new("step_job_id", DataType::Utf8, false),
+        Field::new("array_task_id", DataType::Utf8, true),
+        Field::new("time_limit_secs", DataType::UInt64, true),
+        Field::new("time_left_secs", DataType::UInt64, true),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("min_memory", DataType::Utf8, false),
+        Field::new("elapsed_secs", DataType::UInt64, true),
+        Field::new("priority", DataType::Float64, false),
+        Field::new("partition", DataType::Utf8, false),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("reason", DataType::Utf8, false),
+        Field::new("start_time", local_timestamp(), true),
+        Field::new("submit_time", local_timestamp(), false),
+        Field::new("work_dir", DataType::Utf8, false),
+        Field::new("command", DataType::Utf8, false),
+        Field::new("user", DataType::Utf8, true),
+        Field::new("qos", DataType::Utf8, true),
+        Field::new("node_list", DataType::Utf8, true),
+    ])
+}
+
+fn observation_columns(rows: &[(DateTime<Utc>, SqueueRow)]) -> Vec<ArrayRef> {
+    vec![
+        string_column(rows, |(_, r)| Some(&r.job_id)),
+        timestamp_column(rows, |(time, _)| Some(time.timestamp_millis()), true),
+        string_column(rows, |(_, r)| Some(&r.account)),
+        string_column(rows, |(_, r)| r.exec_host.as_ref()),
+        u64_column(rows, |(_, r)| r.min_cpus as u64),
+        u64_column(rows, |(_, r)| r.cpus as u64),
+        u64_column(rows, |(_, r)| r.nodes as u64),
+        timestamp_column(
+            rows,
+            |(_, r)| r.end_time.map(|t| t.and_utc().timestamp_millis()),
+            false,
+        ),
+        string_column(rows, |(_, r)| r.dependency.as_ref()),
+        string_column(rows, |(_, r)| Some(&r.features)),
+        string_column(rows, |(_, r)| Some(&r.array_job_id)),
+        string_column(rows, |(_, r)| Some(&r.group)),
+        string_column(rows, |(_, r)| Some(&r.step_job_id.0)),
+        string_column(rows, |(_, r)| r.step_job_id.1.as_ref()),
+        secs_column(rows, |(_, r)| r.time_limit),
+        secs_column(rows, |(_, r)| r.time_left),
+        string_column(rows, |(_, r)| Some(&r.name)),
+        string_column(rows, |(_, r)| Some(&r.min_memory)),
+        secs_column(rows, |(_, r)| r.time),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, r)| r.priority),
+        )),
+        string_column(rows, |(_, r)| Some(&r.partition)),
+        string_column(rows, |(_, r)| Some(r.state.to_string())),
+        string_column(rows, |(_, r)| Some(&r.reason)),
+        timestamp_column(
+            rows,
+            |(_, r)| r.start_time.map(|t| t.and_utc().timestamp_millis()),
+            false,
+        ),
+        timestamp_column(
+            rows,
+            |(_, r)| Some(r.submit_time.and_utc().timestamp_millis()),
+            false,
+        ),
+        string_column(rows, |(_, r)| Some(r.work_dir.to_string_lossy())),
+        string_column(rows, |(_, r)| Some(&r.command)),
+        string_column(rows, |(_, r)| r.user.as_ref()),
+        string_column(rows, |(_, r)| r.qos.as_ref()),
+        string_column(rows, |(_, r)| r.node_list.as_ref()),
+    ]
+}
+
+fn transition_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("job_id", DataType::Utf8, false),
+        Field::new("time", utc_timestamp(), false),
+        Field::new("from_state", DataType::Utf8, true),
+        Field::new("to_state", DataType::Utf8, false),
+    ])
+}
+
+fn transition_columns(rows: &[StateTransition]) -> Vec<ArrayRef> {
+    vec![
+        string_column(rows, |t| Some(&t.job_id)),
+        timestamp_column(rows, |t| Some(t.time.timestamp_millis()), true),
+        string_column(rows, |t| t.from.as_ref().map(|s| s.to_string())),
+        string_column(rows, |t| Some(t.to.to_string())),
+    ]
+}
+
+fn string_column<'a, T, S: AsRef<str>>(rows: &'a [T], f: impl Fn(&'a T) -> Option<S>) -> ArrayRef {
+    Arc::new(rows.iter().map(f).collect::<StringArray>())
+}
+
+fn u64_column<T>(rows: &[T], f: impl Fn(&T) -> u64) -> ArrayRef {
+    Arc::new(UInt64Array::from_iter_values(rows.iter().map(f)))
+}
+
+fn timestamp_column<T>(rows: &[T], f: impl Fn(&T) -> Option<i64>, utc: bool) -> ArrayRef {
+    let array = rows.iter().map(f).collect::<TimestampMillisecondArray>();
+    Arc::new(match utc {
+        true => array.with_timezone("UTC"),
+        false => array,
+    })
+}
+
+fn secs_column<T>(rows: &[T], f: impl Fn(&T) -> Option<std::time::Duration>) -> ArrayRef {
+    Arc::new(
+        rows.iter()
+            .map(|row| f(row).map(|d| d.as_secs()))
+            .collect::<UInt64Array>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use arrow_array::{cast::AsArray, RecordBatch};
+    use chrono::Utc;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::{
+        data_extraction::{
+            export_parquet, get_squeue_res, record_finished_jobs, squeue_diff, DirectoryStore,
+            ParquetExportReport, SqueueDiffState, SqueueMode,
+        },
+        executor::MockExecutor,
+    };
+
+    fn read_table(path: &std::path::Path) -> RecordBatch {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(batches.len(), 1);
+        batches.remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_export_parquet() {
+        let path = std::env::temp_dir().join("slurry_test_export_parquet");
+        // Remove leftovers of previous (failed) runs
+        let _ = std::fs::remove_dir_all(&path);
+        let mut store = DirectoryStore::new(path.join("squeue"));
+        let mut state = SqueueDiffState::default();
+        for (state_name, time) in [("PENDING", "N/A"), ("RUNNING", "2025-01-14T10:00:00")] {
+            let executor = MockExecutor::new().with_stdout(
+                "squeue",
+                format!("acc|123|n001|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|{state_name}|None|{time}|2025-01-14T09:59:00|/home/user|./run.sh\n"),
+            );
+            squeue_diff(
+                || get_squeue_res(&SqueueMode::ALL, &executor),
+                &mut store,
+                &mut state,
+            )
+            .await
+            .unwrap();
+        }
+        squeue_diff(
+            || async { Ok((Utc::now(), Vec::new())) },
+            &mut store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        let executor = MockExecutor::new().with_stdout(
+            "sacct",
+            "123|123|myjob|user|grp|acc|part|COMPLETED|0:0|1-00:00:00|2025-01-14T09:59:00|2025-01-14T10:00:00|2025-01-14T11:00:00|2|1|n001|4G||/home/user\n",
+        );
+        record_finished_jobs(&executor, &mut store, &mut state)
+            .await
+            .unwrap();
+
+        let report = export_parquet(store.path(), &path.join("export")).unwrap();
+        assert_eq!(
+            report,
+            ParquetExportReport {
+                jobs: 1,
+                observations: 2,
+                transitions: 3,
+            }
+        );
+        let observations = read_table(&path.join("export").join(super::OBSERVATIONS_FILE));
+        let states = observations
+            .column_by_name("state")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(states.value(0), "PENDING");
+        assert_eq!(states.value(1), "RUNNING");
+        assert!(observations
+            .column_by_name("start_time")
+            .unwrap()
+            .is_null(0));
+        let transitions = read_table(&path.join("export").join(super::TRANSITIONS_FILE));
+        let to_states = transitions
+            .column_by_name("to_state")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(to_states.value(2), "COMPLETED");
+        assert!(transitions.column_by_name("from_state").unwrap().is_null(0));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "ssh")]
+use crate::Client;
+
+use crate::{
+    data_extraction::slurm_json::{
+        json_int, json_opt_string, json_str, json_string, json_time, parse_slurm_json,
+        SlurmOutputFormat,
+    },
+    executor::{CommandExecutor, LocalExecutor},
+    parse_slurm_duration, parse_slurm_memory, JobState,
+};
+
+// https://slurm.schedmd.com/sacct.html#SECTION_Job-Accounting-Fields
+pub(crate) const SACCT_FORMAT_STR: &str = "JobID,JobIDRaw,JobName,User,Group,Account,Partition,State,ExitCode,Elapsed,Submit,Start,End,AllocCPUS,NNodes,NodeList,ReqMem,MaxRSS,WorkDir";
+const SACCT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Struct for parsed output row of `sacct` command
+///
+/// Contains accounting information about a SLURM job or one of its steps (e.g., `batch`, `extern`, or `0`)
+pub struct SacctRow {
+    /// "`JobID`" without the step part
+    /// e.g., 49848561 or `49869434_2`
+    pub job_id: String,
+    /// Step of the job (if this row describes a job step)
+    /// e.g., `batch`, `extern` or `0`
+    pub step: Option<String>,
+    /// "`JobIDRaw`"
+    pub job_id_raw: String,
+    /// "`JobName`"
+    pub name: String,
+    /// "User"
+    pub user: String,
+    /// "Group"
+    pub group: String,
+    /// "Account"
+    pub account: String,
+    /// "Partition"
+    pub partition: String,
+    /// "State"
+    pub state: JobState,
+    /// Exit code of the job (first part of "`ExitCode`")
+    pub exit_code: i32,
+    /// Signal which caused the job to terminate (second part of "`ExitCode`")
+    pub exit_signal: i32,
+    /// "Elapsed"
+    pub elapsed: Option<Duration>,
+    /// "Submit"
+    pub submit_time: Option<NaiveDateTime>,
+    /// "Start"
+    pub start_time: Option<NaiveDateTime>,
+    /// "End"
+    pub end_time: Option<NaiveDateTime>,
+    /// "`AllocCPUS`"
+    pub alloc_cpus: usize,
+    /// "`NNodes`"
+    pub nodes: usize,
+    /// "`NodeList`"
+    pub node_list: Option<String>,
+    /// "`ReqMem`"
+    pub req_mem: String,
+    /// "`MaxRSS`" in bytes (usually only available for job steps)
+    pub max_rss: Option<u64>,
+    /// "`WorkDir`"
+    pub work_dir: PathBuf,
+}
+
+fn parse_sacct_time(s: &str) -> Result<Option<NaiveDateTime>, Error> {
+    match s {
+        "" | "Unknown" | "None" => Ok(None),
+        s => Ok(Some(NaiveDateTime::parse_from_str(s, SACCT_TIME_FORMAT)?)),
+    }
+}
+
+impl SacctRow {
+    /// Whether this row describes a job step (and not the job allocation itself)
+    pub fn is_step(&self) -> bool {
+        self.step.is_some()
+    }
+
+    fn parse_from_strs(vals: &[&str]) -> Result<Self, Error> {
+        if vals.len() != 19 {
+            return Err(Error::msg("Invalid length of values."));
+        }
+        let (job_id, step) = match vals[0].split_once(".") {
+            Some((job_id, step)) => (job_id.to_string(), Some(step.to_string())),
+            None => (vals[0].to_string(), None),
+        };
+        let (exit_code, exit_signal) = vals[8]
+            .split_once(":")
+            .ok_or(Error::msg("Invalid exit code."))?;
+        Ok(Self {
+            job_id,
+            step,
+            job_id_raw: vals[1].to_string(),
+            name: vals[2].to_string(),
+            user: vals[3].to_string(),
+            group: vals[4].to_string(),
+            account: vals[5].to_string(),
+            partition: vals[6].to_string(),
+            // e.g., "CANCELLED by 12345"
+            state: vals[7].split(" ").next().unwrap_or_default().parse()?,
+            exit_code: exit_code.parse()?,
+            exit_signal: exit_signal.parse()?,
+            elapsed: parse_slurm_duration(vals[9]).ok(),
+            submit_time: parse_sacct_time(vals[10])?,
+            start_time: parse_sacct_time(vals[11])?,
+            end_time: parse_sacct_time(vals[12])?,
+            alloc_cpus: vals[13].parse()?,
+            nodes: vals[14].parse()?,
+            node_list: match vals[15] {
+                "" | "None assigned" => None,
+                s => Some(s.to_string()),
+            },
+            req_mem: vals[16].to_string(),
+            max_rss: match vals[17] {
+                "" => None,
+                s => Some(parse_slurm_memory(s)?),
+            },
+            work_dir: vals[18].parse()?,
+        })
+    }
+
+    /// Parse a job of the `--json` output of `sacct`, returning rows for the job and all of its steps
+    fn parse_from_json(job: &Value) -> Result<Vec<Self>, Error> {
+        let raw_job_id = json_int(job.get("job_id"))
+            .ok_or(Error::msg("Missing job ID."))?
+            .to_string();
+        let array = job.get("array");
+        let job_id = match (
+            json_int(array.and_then(|a| a.get("job_id"))),
+            json_int(array.and_then(|a| a.get("task_id"))),
+        ) {
+            (Some(array_id), Some(task_id)) if array_id != 0 => format!("{array_id}_{task_id}"),
+            _ => raw_job_id.clone(),
+        };
+        let job_row = Self {
+            job_id: job_id.clone(),
+            step: None,
+            job_id_raw: raw_job_id.clone(),
+            name: json_string(job.get("name")),
+            user: json_string(job.get("user")),
+            group: json_string(job.get("group")),
+            account: json_string(job.get("account")),
+            partition: json_string(job.get("partition")),
+            state: parse_json_state(job)?,
+            exit_code: json_int(job.pointer("/exit_code/return_code")).unwrap_or_default() as i32,
+            exit_signal: json_int(job.pointer("/exit_code/signal/id")).unwrap_or_default() as i32,
+            elapsed: json_int(job.pointer("/time/elapsed")).map(|s| Duration::from_secs(s as u64)),
+            submit_time: json_time(job.pointer("/time/submission")),
+            start_time: json_time(job.pointer("/time/start")),
+            end_time: json_time(job.pointer("/time/end")),
+            alloc_cpus: json_tres_count(job.pointer("/tres/allocated"), "cpu").unwrap_or_default()
+                as usize,
+            nodes: json_int(job.get("allocation_nodes")).unwrap_or_default() as usize,
+            node_list: json_opt_string(job.get("nodes")).filter(|n| n != "None assigned"),
+            // memory is given in megabytes
+            req_mem: json_int(job.pointer("/required/memory_per_node"))
+                .or(json_int(job.pointer("/required/memory_per_cpu")))
+                .map(|m| format!("{m}M"))
+                .unwrap_or_default(),
+            max_rss: None,
+            work_dir: json_string(job.get("working_directory")).parse()?,
+        };
+        let mut rows = Vec::new();
+        for step in job
+            .get("steps")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let step_name = json_string(step.pointer("/step/name"));
+            // e.g., "4242.batch"
+            let step_id = match json_str(step.pointer("/step/id")) {
+                Some(id) => id
+                    .split_once(".")
+                    .map(|(_, s)| s.to_string())
+                    .unwrap_or(step_name.clone()),
+                None => step_name.clone(),
+            };
+            rows.push(Self {
+                job_id: job_id.clone(),
+                step: Some(step_id.clone()),
+                job_id_raw: format!("{raw_job_id}.{step_id}"),
+                name: step_name,
+                user: String::new(),
+                group: String::new(),
+                account: job_row.account.clone(),
+                partition: String::new(),
+                state: parse_json_state(step)?,
+                exit_code: json_int(step.pointer("/exit_code/return_code")).unwrap_or_default()
+                    as i32,
+                exit_signal: json_int(step.pointer("/exit_code/signal/id")).unwrap_or_default()
+                    as i32,
+                elapsed: json_int(step.pointer("/time/elapsed"))
+                    .map(|s| Duration::from_secs(s as u64)),
+                submit_time: json_time(step.pointer("/time/start")),
+                start_time: json_time(step.pointer("/time/start")),
+                end_time: json_time(step.pointer("/time/end")),
+                alloc_cpus: json_tres_count(step.pointer("/tres/allocated"), "cpu")
+                    .unwrap_or_default() as usize,
+                nodes: json_int(step.pointer("/nodes/count")).unwrap_or_default() as usize,
+                node_list: json_opt_string(step.pointer("/nodes/range")),
+                req_mem: String::new(),
+                max_rss: json_tres_count(step.pointer("/tres/requested/max"), "mem")
+                    .map(|m| m as u64),
+                work_dir: PathBuf::new(),
+            });
+        }
+        rows.insert(0, job_row);
+        Ok(rows)
+    }
+}
+
+// e.g., {"current": ["COMPLETED"], "reason": "None"} or "COMPLETED"
+fn parse_json_state(v: &Value) -> Result<JobState, Error> {
+    let state = v.get("state").ok_or(Error::msg("Missing state."))?;
+    json_str(state.get("current").or(Some(state)))
+        .ok_or(Error::msg("Invalid state."))?
+        .parse()
+}
+
+// e.g., [{"type": "cpu", "count": 8}, {"type": "mem", "count": 16384}]
+fn json_tres_count(v: Option<&Value>, tres_type: &str) -> Option<i64> {
+    v?.as_array()?
+        .iter()
+        .find(|t| t.get("type").and_then(Value::as_str) == Some(tres_type))
+        .and_then(|t| json_int(t.get("count")))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Parameter for `sacct` extraction, specifying what SLURM jobs to include
+///
+/// By default, jobs of all users are included, using the default time window of `sacct`
+/// (i.e., jobs since midnight of the current day).
+pub struct SacctMode {
+    /// Only include jobs of these users (all users if empty)
+    pub users: Vec<String>,
+    /// Only include jobs of these accounts (all accounts if empty)
+    pub accounts: Vec<String>,
+    /// Only include jobs with these IDs (all jobs if empty)
+    pub job_ids: Vec<String>,
+    /// Only include jobs in one of these states (all states if empty)
+    pub states: Vec<JobState>,
+    /// Start of the time window (`--starttime`)
+    pub start_time: Option<NaiveDateTime>,
+    /// End of the time window (`--endtime`)
+    pub end_time: Option<NaiveDateTime>,
+}
+
+impl SacctMode {
+    /// Only include jobs of the passed users
+    pub fn with_users(mut self, users: Vec<String>) -> Self {
+        self.users = users;
+        self
+    }
+
+    /// Only include jobs of the passed accounts
+    pub fn with_accounts(mut self, accounts: Vec<String>) -> Self {
+        self.accounts = accounts;
+        self
+    }
+
+    /// Only include jobs with the passed IDs
+    pub fn with_job_ids(mut self, job_ids: Vec<String>) -> Self {
+        self.job_ids = job_ids;
+        self
+    }
+
+    /// Only include jobs in one of the passed states
+    pub fn with_states(mut self, states: Vec<JobState>) -> Self {
+        self.states = states;
+        self
+    }
+
+    /// Only include jobs in the passed time window
+    pub fn with_time_window(
+        mut self,
+        start_time: Option<NaiveDateTime>,
+        end_time: Option<NaiveDateTime>,
+    ) -> Self {
+        self.start_time = start_time;
+        self.end_time = end_time;
+        self
+    }
+
+    fn to_args(&self) -> String {
+        let mut args = Vec::new();
+        if self.users.is_empty() {
+            args.push(String::from("--allusers"));
+        } else {
+            args.push(format!("--user={}", self.users.join(",")));
+        }
+        if !self.accounts.is_empty() {
+            args.push(format!("--accounts={}", self.accounts.join(",")));
+        }
+        if !self.job_ids.is_empty() {
+            args.push(format!("--jobs={}", self.job_ids.join(",")));
+        }
+        if !self.states.is_empty() {
+            let states: Vec<_> = self.states.iter().map(|s| s.to_string()).collect();
+            args.push(format!("--state={}", states.join(",")));
+        }
+        if let Some(start_time) = &self.start_time {
+            args.push(format!(
+                "--starttime={}",
+                start_time.format(SACCT_TIME_FORMAT)
+            ));
+        }
+        if let Some(end_time) = &self.end_time {
+            args.push(format!("--endtime={}", end_time.format(SACCT_TIME_FORMAT)));
+        }
+        args.join(" ")
+    }
+}
+
+/// Parse the (`--parsable2`) output of `sacct` using [`SACCT_FORMAT_STR`]
+pub(crate) fn parse_sacct_output(output: &str) -> Vec<SacctRow> {
+    output
+        .split("\n")
+        .filter_map(|line| {
+            if line.is_empty() {
+                return None;
+            }
+            match SacctRow::parse_from_strs(&line.split("|").collect::<Vec<_>>()) {
+                Ok(row) => Some(row),
+                Err(err) => {
+                    println!("[!] {:?} for {:?}", err, &line);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Get sacct results using the provided [`CommandExecutor`] and the given output format
+///
+/// See also [`crate::data_extraction::detect_output_format`] for detecting which output formats are supported.
+pub async fn get_sacct_res_with_format<E: CommandExecutor>(
+    mode: &SacctMode,
+    format: SlurmOutputFormat,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SacctRow>), Error> {
+    match format {
+        SlurmOutputFormat::FormatString => get_sacct_res(mode, executor).await,
+        SlurmOutputFormat::Json => get_sacct_res_json(mode, executor).await,
+    }
+}
+
+/// Get sacct results using the provided [`CommandExecutor`], parsing the `--json` output of `sacct`
+///
+/// Requires a SLURM version supporting `sacct --json`.
+/// Unlike the times of [`get_sacct_res`] (which are in the local time of the SLURM system), all times are in UTC.
+pub async fn get_sacct_res_json<E: CommandExecutor>(
+    mode: &SacctMode,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SacctRow>), Error> {
+    let out = executor
+        .execute(&format!("sacct --json {}", mode.to_args()))
+        .await?;
+    if !out.success() {
+        return Err(Error::msg(format!("sacct --json failed: {}", out.stderr)));
+    }
+    let time: DateTime<Utc> = SystemTime::now().into();
+    let rows = parse_slurm_json(&out.stdout)?
+        .iter()
+        .flat_map(|job| match SacctRow::parse_from_json(job) {
+            Ok(rows) => rows,
+            Err(err) => {
+                println!("[!] {:?} for {:?}", err, job.get("job_id"));
+                Vec::new()
+            }
+        })
+        .collect();
+    Ok((time, rows))
+}
+
+/// Get sacct results using the provided [`CommandExecutor`]
+pub async fn get_sacct_res<E: CommandExecutor>(
+    mode: &SacctMode,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SacctRow>), Error> {
+    let result = executor
+        .execute(&format!(
+            "sacct -n -P --format='{SACCT_FORMAT_STR}' {}",
+            mode.to_args()
+        ))
+        .await?
+        .stdout;
+    let time: DateTime<Utc> = SystemTime::now().into();
+    Ok((time, parse_sacct_output(&result)))
+}
+
+/// Run and parse `sacct` result locally (i.e., not via SSH)
+pub async fn get_sacct_res_locally(
+    mode: &SacctMode,
+) -> Result<(DateTime<Utc>, Vec<SacctRow>), Error> {
+    let d = Instant::now();
+    let res = get_sacct_res(mode, &LocalExecutor).await;
+    println!("Running sacct took {:?}", d.elapsed());
+    res
+}
+
+#[cfg(feature = "ssh")]
+/// Run and parse `sacct` result over SSH
+pub async fn get_sacct_res_ssh(
+    client: &Client,
+    mode: &SacctMode,
+) -> Result<(DateTime<Utc>, Vec<SacctRow>), Error> {
+    get_sacct_res(mode, client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        data_extraction::{get_sacct_res, get_sacct_res_json, SacctMode},
+        executor::MockExecutor,
+        JobState,
+    };
+
+    #[tokio::test]
+    async fn test_sacct_mock() {
+        let executor = MockExecutor::new().with_stdout(
+            "sacct",
+            "4242|4242|train|alice|grp|acc|gpu|COMPLETED|0:0|01:02:03|2025-01-14T10:00:00|2025-01-14T10:05:00|2025-01-14T11:07:03|8|1|n001|16G||/home/alice
+4242.batch|4242.batch|batch|||acc||COMPLETED|0:0|01:02:03|2025-01-14T10:05:00|2025-01-14T10:05:00|2025-01-14T11:07:03|8|1|n001||2048K|
+4243|4243|sweep|alice|grp|acc|gpu|CANCELLED by 1000|0:15|00:00:00|2025-01-14T10:00:00|Unknown|2025-01-14T10:01:00|0|0|None assigned|16G||/home/alice
+",
+        );
+        let mode = SacctMode::default().with_users(vec!["alice".to_string()]);
+        let (_time, rows) = get_sacct_res(&mode, &executor).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].elapsed, Some(Duration::from_secs(3723)));
+        assert!(!rows[0].is_step());
+        assert_eq!(rows[1].job_id, "4242");
+        assert_eq!(rows[1].step.as_deref(), Some("batch"));
+        assert_eq!(rows[1].max_rss, Some(2048 * 1024));
+        assert_eq!(rows[2].state, JobState::CANCELLED);
+        assert_eq!(rows[2].exit_signal, 15);
+        assert_eq!(rows[2].start_time, None);
+        assert_eq!(rows[2].node_list, None);
+        assert!(executor.executed_commands()[0].contains("--user=alice"));
+    }
+
+    #[tokio::test]
+    async fn test_sacct_json_mock() {
+        let executor = MockExecutor::new().with_stdout(
+            "sacct --json",
+            r#"{"jobs": [{"job_id": 4242, "name": "train", "user": "alice", "group": "grp", "account": "acc", "partition": "gpu",
+                "array": {"job_id": 0, "task_id": {"set": false, "infinite": false, "number": 0}},
+                "state": {"current": ["OUT_OF_MEMORY"], "reason": "None"},
+                "exit_code": {"status": ["ERROR"], "return_code": {"set": true, "infinite": false, "number": 1}, "signal": {"id": {"set": false, "infinite": false, "number": 0}}},
+                "time": {"elapsed": 65, "submission": 1736848700, "start": 1736848800, "end": 1736848865},
+                "tres": {"allocated": [{"type": "cpu", "count": 8}, {"type": "mem", "count": 16384}]},
+                "allocation_nodes": 1, "nodes": "n001", "required": {"memory_per_node": {"set": true, "infinite": false, "number": 16384}},
+                "working_directory": "/home/alice",
+                "steps": [{"step": {"id": "4242.batch", "name": "batch"}, "state": ["OUT_OF_MEMORY"],
+                    "exit_code": {"return_code": {"set": true, "infinite": false, "number": 0}, "signal": {"id": {"set": true, "infinite": false, "number": 9}}},
+                    "time": {"elapsed": 65, "start": {"set": true, "infinite": false, "number": 1736848800}, "end": {"set": true, "infinite": false, "number": 1736848865}},
+                    "nodes": {"count": 1, "range": "n001"},
+                    "tres": {"allocated": [{"type": "cpu", "count": 8}], "requested": {"max": [{"type": "mem", "count": 17179869184}]}}}]}],
+              "errors": [], "warnings": []}"#,
+        );
+        let (_time, rows) = get_sacct_res_json(&SacctMode::default(), &executor)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].state, JobState::OUT_OF_MEMORY);
+        assert_eq!(rows[0].exit_code, 1);
+        assert_eq!(rows[0].alloc_cpus, 8);
+        assert_eq!(rows[0].req_mem, "16384M");
+        assert_eq!(rows[1].job_id_raw, "4242.batch");
+        assert_eq!(rows[1].exit_signal, 9);
+        assert_eq!(rows[1].max_rss, Some(17179869184));
+        assert_eq!(
+            rows[0].start_time.unwrap().to_string(),
+            "2025-01-14 10:00:00"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Error;
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::executor::CommandExecutor;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+/// Output format used for parsing the results of SLURM commands (e.g., `squeue` or `sacct`)
+pub enum SlurmOutputFormat {
+    #[default]
+    /// Pipe-delimited output of a custom format string (supported by all SLURM versions)
+    FormatString,
+    /// Output of the `--json` flag (supported by newer SLURM versions)
+    ///
+    /// This format is robust against special characters (e.g., `|`) in job names or commands.
+    /// Times are in UTC, instead of the local time of the SLURM system.
+    Json,
+}
+
+/// Detect whether the SLURM system supports `--json` output
+///
+/// Returns [`SlurmOutputFormat::Json`] if `squeue --json` succeeds and returns a valid JSON result,
+/// and [`SlurmOutputFormat::FormatString`] otherwise (e.g., on older SLURM versions).
+pub async fn detect_output_format<E: CommandExecutor>(executor: &E) -> SlurmOutputFormat {
+    match executor.execute("squeue --json --me").await {
+        Ok(out) if out.success() => match parse_slurm_json(&out.stdout) {
+            Ok(_) => SlurmOutputFormat::Json,
+            Err(_) => SlurmOutputFormat::FormatString,
+        },
+        _ => SlurmOutputFormat::FormatString,
+    }
+}
+
+/// Parse the JSON output of a SLURM command, returning the contained `jobs`
+///
+/// Reports all errors contained in the output.
+pub(crate) fn parse_slurm_json(s: &str) -> Result<Vec<Value>, Error> {
+    let mut v: Value = serde_json::from_str(s)?;
+    if let Some(errors) = v.get("errors").and_then(|e| e.as_array()) {
+        if !errors.is_empty() {
+            return Err(Error::msg(format!("SLURM reported errors: {errors:?}")));
+        }
+    }
+    match v.get_mut("jobs").map(Value::take) {
+        Some(Value::Array(jobs)) => Ok(jobs),
+        _ => Err(Error::msg("No jobs contained in JSON output.")),
+    }
+}
+
+// Depending on the data_parser version, numbers are either plain values
+// or objects of the form {"set": true, "infinite": false, "number": 42}
+pub(crate) fn json_number(v: Option<&Value>) -> Option<f64> {
+    match v? {
+        Value::Number(n) => n.as_f64(),
+        Value::Object(o) => {
+            if o.get("set").and_then(Value::as_bool) == Some(false)
+                || o.get("infinite").and_then(Value::as_bool) == Some(true)
+            {
+                return None;
+            }
+            o.get("number").and_then(Value::as_f64)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn json_int(v: Option<&Value>) -> Option<i64> {
+    json_number(v).map(|n| n as i64)
+}
+
+// Strings are sometimes wrapped in arrays (e.g., job states in newer data_parser versions)
+pub(crate) fn json_str(v: Option<&Value>) -> Option<&str> {
+    match v? {
+        Value::String(s) => Some(s.as_str()),
+        Value::Array(a) => a.first().and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+pub(crate) fn json_string(v: Option<&Value>) -> String {
+    json_str(v).unwrap_or_default().to_string()
+}
+
+// Empty strings are treated as not set
+pub(crate) fn json_opt_string(v: Option<&Value>) -> Option<String> {
+    json_str(v).filter(|s| !s.is_empty()).map(String::from)
+}
+
+// UNIX timestamps (`0` meaning not set) are kept in UTC: converting them to the local time of the SLURM system
+// would require its time zone rules, as a single UTC offset is wrong for times on the other side of a DST change
+pub(crate) fn json_time(v: Option<&Value>) -> Option<NaiveDateTime> {
+    match json_int(v)? {
+        0 => None,
+        t => DateTime::from_timestamp(t, 0).map(|t| t.naive_utc()),
+    }
+}
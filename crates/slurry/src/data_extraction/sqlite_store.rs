@@ -0,0 +1,263 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{
+    history::HistorySource,
+    squeue::JobFinished,
+    store::{sort_records, JobRecord, SnapshotDiff, SnapshotStore},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    time INTEGER NOT NULL,
+    job_ids TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snapshots_time ON snapshots (time);
+CREATE TABLE IF NOT EXISTS jobs (
+    job_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    row TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS jobs_job_id ON jobs (job_id);
+CREATE INDEX IF NOT EXISTS jobs_time ON jobs (time);
+CREATE TABLE IF NOT EXISTS deltas (
+    job_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    delta TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS deltas_job_id ON deltas (job_id);
+CREATE INDEX IF NOT EXISTS deltas_time ON deltas (time);
+CREATE TABLE IF NOT EXISTS events (
+    job_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_job_id ON events (job_id);
+CREATE INDEX IF NOT EXISTS events_time ON events (time);
+";
+
+#[derive(Debug)]
+/// Store saving snapshots and changes into a single `SQLite` database
+///
+/// Times are saved as milliseconds since the Unix epoch, rows and deltas as JSON.
+/// The database contains the tables
+/// - `snapshots` (`time`, `job_ids`): IDs of all listed jobs per snapshot
+/// - `jobs` (`job_id`, `time`, `row`): jobs when they were first listed
+/// - `deltas` (`job_id`, `time`, `delta`): changes of known jobs
+/// - `events` (`job_id`, `time`, `kind`, `data`): `DISAPPEARED` and `FINISHED` records of jobs
+///
+/// Each snapshot is saved in a single transaction.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at the given path
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Create a temporary database in memory (e.g., for testing)
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, Error> {
+        // Write-ahead logging allows reading the database while the loop is running
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// The underlying database connection (e.g., for custom queries)
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl SnapshotStore for SqliteStore {
+    fn save_diff(&mut self, diff: &SnapshotDiff<'_>) -> Result<(), Error> {
+        let time = diff.time.timestamp_millis();
+        let tx = self.conn.transaction()?;
+        {
+            tx.execute(
+                "INSERT INTO snapshots (time, job_ids) VALUES (?1, ?2)",
+                params![time, serde_json::to_string(diff.job_ids)?],
+            )?;
+            let mut insert_job =
+                tx.prepare_cached("INSERT INTO jobs (job_id, time, row) VALUES (?1, ?2, ?3)")?;
+            for row in &diff.new_jobs {
+                insert_job.execute(params![row.job_id, time, serde_json::to_string(row)?])?;
+            }
+            let mut insert_delta =
+                tx.prepare_cached("INSERT INTO deltas (job_id, time, delta) VALUES (?1, ?2, ?3)")?;
+            for (job_id, delta) in &diff.deltas {
+                insert_delta.execute(params![job_id, time, serde_json::to_string(delta)?])?;
+            }
+            let mut insert_event = tx.prepare_cached(
+                "INSERT INTO events (job_id, time, kind, data) VALUES (?1, ?2, 'DISAPPEARED', ?3)",
+            )?;
+            for disappeared in diff.disappeared {
+                insert_event.execute(params![
+                    disappeared.job_id,
+                    time,
+                    serde_json::to_string(disappeared)?
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_finished(&mut self, finished: &[JobFinished]) -> Result<(), Error> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_event = tx.prepare_cached(
+                "INSERT INTO events (job_id, time, kind, data) VALUES (?1, ?2, 'FINISHED', ?3)",
+            )?;
+            for record in finished {
+                insert_event.execute(params![
+                    record.job_id,
+                    record.time.timestamp_millis(),
+                    serde_json::to_string(record)?
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl HistorySource for SqliteStore {
+    fn snapshot_times(&self) -> Result<Vec<DateTime<Utc>>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT DISTINCT time FROM snapshots ORDER BY time")?;
+        let times = stmt
+            .query_map([], |r| r.get(0))?
+            .map(|time| from_millis(time?))
+            .collect();
+        times
+    }
+
+    fn snapshot_job_ids(&self, time: DateTime<Utc>) -> Result<HashSet<String>, Error> {
+        let job_ids: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT job_ids FROM snapshots WHERE time = ?1 ORDER BY rowid DESC LIMIT 1",
+                params![time.timestamp_millis()],
+                |r| r.get(0),
+            )
+            .optional()?;
+        match job_ids {
+            Some(job_ids) => Ok(serde_json::from_str(&job_ids)?),
+            None => Err(Error::msg(format!("No snapshot saved at {time}."))),
+        }
+    }
+
+    fn job_records(&self, job_id: &str) -> Result<Vec<JobRecord>, Error> {
+        let mut records = Vec::new();
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT time, row FROM jobs WHERE job_id = ?1")?;
+        for res in stmt.query_map([job_id], |r| Ok((r.get(0)?, r.get::<_, String>(1)?)))? {
+            let (time, row) = res?;
+            records.push(JobRecord::Observed(
+                from_millis(time)?,
+                serde_json::from_str(&row)?,
+            ));
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT time, delta FROM deltas WHERE job_id = ?1")?;
+        for res in stmt.query_map([job_id], |r| Ok((r.get(0)?, r.get::<_, String>(1)?)))? {
+            let (time, delta) = res?;
+            records.push(JobRecord::Delta(
+                from_millis(time)?,
+                serde_json::from_str(&delta)?,
+            ));
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT kind, data FROM events WHERE job_id = ?1")?;
+        for res in stmt.query_map([job_id], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })? {
+            let (kind, data) = res?;
+            records.push(match kind.as_str() {
+                "DISAPPEARED" => JobRecord::Disappeared(serde_json::from_str(&data)?),
+                _ => JobRecord::Finished(serde_json::from_str(&data)?),
+            });
+        }
+        sort_records(&mut records);
+        Ok(records)
+    }
+}
+
+fn from_millis(millis: i64) -> Result<DateTime<Utc>, Error> {
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| Error::msg(format!("Invalid time: {millis}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::Utc;
+
+    use crate::data_extraction::{
+        squeue_diff, SnapshotStore, SqliteStore, SqueueDiffState, SqueueMode,
+    };
+    use crate::{data_extraction::get_squeue_res, executor::MockExecutor};
+
+    fn count(store: &SqliteStore, table: &str) -> i64 {
+        store
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut state = SqueueDiffState::default();
+        for (state_name, time) in [("PENDING", "N/A"), ("RUNNING", "2025-01-14T10:00:00")] {
+            let executor = MockExecutor::new().with_stdout(
+                "squeue",
+                format!("acc|123|n001|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|{state_name}|None|{time}|2025-01-14T09:59:00|/home/user|./run.sh\n"),
+            );
+            squeue_diff(
+                || get_squeue_res(&SqueueMode::ALL, &executor),
+                &mut store,
+                &mut state,
+            )
+            .await
+            .unwrap();
+        }
+        squeue_diff(
+            || async { Ok((Utc::now(), Vec::new())) },
+            &mut store as &mut dyn SnapshotStore,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        assert_eq!(count(&store, "snapshots"), 3);
+        assert_eq!(count(&store, "jobs"), 1);
+        assert_eq!(count(&store, "deltas"), 1);
+        assert_eq!(count(&store, "events"), 1);
+        let job_ids: String = store
+            .connection()
+            .query_row(
+                "SELECT job_ids FROM snapshots ORDER BY time LIMIT 1",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        let job_ids: HashSet<String> = serde_json::from_str(&job_ids).unwrap();
+        assert!(job_ids.contains("123"));
+    }
+}
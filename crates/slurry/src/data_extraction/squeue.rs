@@ -3,23 +3,27 @@ use std::{path::PathBuf, time::Duration};
 use anyhow::Error;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use structdiff::{Difference, StructDiff};
 
-use crate::{parse_slurm_duration, JobState};
+use crate::{
+    data_extraction::slurm_json::{
+        json_int, json_number, json_opt_string, json_str, json_string, json_time, parse_slurm_json,
+        SlurmOutputFormat,
+    },
+    executor::{CommandExecutor, LocalExecutor},
+    job_management::{ArraySpec, JobDependency},
+    parse_slurm_duration, JobState,
+};
 use std::{
     collections::{HashMap, HashSet},
-    fs::{create_dir_all, File},
     future::Future,
-    io::BufWriter,
-    path::Path,
-    process::Command,
     time::{Instant, SystemTime},
 };
 
 #[cfg(feature = "ssh")]
-use async_ssh2_tokio::Client;
+use crate::Client;
 use chrono::{DateTime, Utc};
-use rayon::iter::IntoParallelRefIterator;
 
 // https://slurm.schedmd.com/squeue.html
 pub(crate) const SQUEUE_FORMAT_STR: &str =
@@ -110,9 +114,32 @@ pub struct SqueueRow {
     pub work_dir: PathBuf,
     /// "COMMAND",
     pub command: String,
+    /// "USER" (only available when parsed from `--json` output)
+    #[serde(default)]
+    pub user: Option<String>,
+    /// "QOS" (only available when parsed from `--json` output)
+    #[serde(default)]
+    pub qos: Option<String>,
+    /// "NODELIST" (only available when parsed from `--json` output)
+    #[serde(default)]
+    pub node_list: Option<String>,
 }
 
 impl SqueueRow {
+    /// The array tasks represented by this row (e.g., `3-10%1` for `49616001_[3-10%1]`)
+    ///
+    /// Returns `None` for jobs which are not part of a job array.
+    pub fn array_spec(&self) -> Option<ArraySpec> {
+        self.step_job_id.1.as_ref().and_then(|s| s.parse().ok())
+    }
+
+    /// The parsed dependencies of this job (see [`SqueueRow::dependency`])
+    ///
+    /// Returns `None` if the job has no dependencies or they could not be parsed.
+    pub fn parsed_dependency(&self) -> Option<JobDependency> {
+        self.dependency.as_ref().and_then(|s| s.parse().ok())
+    }
+
     fn parse_from_strs(vals: &[&str]) -> Result<Self, Error> {
         if vals.len() != 25 {
             return Err(Error::msg("Invalid length of values."));
@@ -170,6 +197,77 @@ impl SqueueRow {
             submit_time: NaiveDateTime::parse_from_str(vals[22], "%Y-%m-%dT%H:%M:%S")?,
             work_dir: vals[23].parse()?,
             command: vals[24].to_string(),
+            user: None,
+            qos: None,
+            node_list: None,
+        })
+    }
+
+    fn parse_from_json(job: &Value, now: NaiveDateTime) -> Result<Self, Error> {
+        let job_id = json_int(job.get("job_id"))
+            .ok_or(Error::msg("Missing job ID."))?
+            .to_string();
+        let array_job_id = match json_int(job.get("array_job_id")) {
+            None | Some(0) => job_id.clone(),
+            Some(id) => id.to_string(),
+        };
+        let step_job_id = match (
+            json_opt_string(job.get("array_task_string")),
+            json_int(job.get("array_task_id")),
+        ) {
+            (Some(tasks), _) => (array_job_id.clone(), Some(format!("[{tasks}]"))),
+            (None, Some(task)) if array_job_id != job_id => {
+                (array_job_id.clone(), Some(task.to_string()))
+            }
+            _ => (job_id.clone(), None),
+        };
+        let state: JobState = json_str(job.get("job_state"))
+            .ok_or(Error::msg("Missing job state."))?
+            .parse()?;
+        let start_time = json_time(job.get("start_time"));
+        // time_limit is given in minutes
+        let time_limit =
+            json_int(job.get("time_limit")).map(|m| Duration::from_secs(m as u64 * 60));
+        let time = match (&state, start_time) {
+            (JobState::RUNNING, Some(start)) => (now - start).to_std().ok(),
+            _ => None,
+        };
+        Ok(Self {
+            account: json_string(job.get("account")),
+            exec_host: json_opt_string(job.get("batch_host")),
+            min_cpus: json_int(job.get("minimum_cpus_per_node")).unwrap_or_default() as usize,
+            cpus: json_int(job.get("cpus")).unwrap_or_default() as usize,
+            nodes: json_int(job.get("node_count")).unwrap_or_default() as usize,
+            end_time: json_time(job.get("end_time")),
+            dependency: json_opt_string(job.get("dependency")),
+            features: json_string(job.get("features")),
+            array_job_id,
+            group: json_string(job.get("group_name")),
+            step_job_id,
+            time_limit,
+            time_left: time_limit
+                .zip(time)
+                .map(|(limit, time)| limit.saturating_sub(time)),
+            name: json_string(job.get("name")),
+            // memory is given in megabytes
+            min_memory: json_int(job.get("memory_per_node"))
+                .or(json_int(job.get("memory_per_cpu")))
+                .map(|m| format!("{m}M"))
+                .unwrap_or_default(),
+            time,
+            priority: json_number(job.get("priority")).unwrap_or_default(),
+            partition: json_string(job.get("partition")),
+            state,
+            reason: json_string(job.get("state_reason")),
+            start_time,
+            submit_time: json_time(job.get("submit_time"))
+                .ok_or(Error::msg("Missing submit time."))?,
+            work_dir: json_string(job.get("current_working_directory")).parse()?,
+            command: json_string(job.get("command")),
+            user: json_opt_string(job.get("user_name")),
+            qos: json_opt_string(job.get("qos")),
+            node_list: json_opt_string(job.get("nodes")),
+            job_id,
         })
     }
 }
@@ -185,24 +283,77 @@ pub enum SqueueMode {
     /// Include only the specified SLURM jobs (given by their IDs)
     JOBIDS(Vec<String>),
 }
-/// Get squeue results using the provided `execute_cmd` function
-pub async fn get_squeue_res<F, Fut>(
+impl SqueueMode {
+    fn to_arg(&self) -> String {
+        match self {
+            SqueueMode::ALL => String::default(),
+            SqueueMode::MINE => String::from("--me"),
+            SqueueMode::JOBIDS(vec) => format!("-j {}", vec.join(",")),
+        }
+    }
+}
+
+/// Get squeue results using the provided [`CommandExecutor`] and the given output format
+///
+/// See also [`crate::data_extraction::detect_output_format`] for detecting which output formats are supported.
+pub async fn get_squeue_res_with_format<E: CommandExecutor>(
     mode: &SqueueMode,
-    execute_cmd: F,
-) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error>
-where
-    F: FnOnce(String) -> Fut,
-    Fut: Future<Output = Result<String, Error>>,
-{
-    let extra_arg = match mode {
-        SqueueMode::ALL => String::default(),
-        SqueueMode::MINE => String::from("--me"),
-        SqueueMode::JOBIDS(vec) => format!("-j {}", vec.join(",")),
-    };
-    let result = execute_cmd(format!(
-        "squeue -h -a -M all -t all --format='{SQUEUE_FORMAT_STR}' {extra_arg}"
-    ))
-    .await?;
+    format: SlurmOutputFormat,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error> {
+    match format {
+        SlurmOutputFormat::FormatString => get_squeue_res(mode, executor).await,
+        SlurmOutputFormat::Json => get_squeue_res_json(mode, executor).await,
+    }
+}
+
+/// Get squeue results using the provided [`CommandExecutor`], parsing the `--json` output of `squeue`
+///
+/// Requires a SLURM version supporting `squeue --json`.
+/// Unlike the times of [`get_squeue_res`] (which are in the local time of the SLURM system), all times are in UTC.
+pub async fn get_squeue_res_json<E: CommandExecutor>(
+    mode: &SqueueMode,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error> {
+    let out = executor
+        .execute(&format!("squeue --json -a -M all -t all {}", mode.to_arg()))
+        .await?;
+    if !out.success() {
+        return Err(Error::msg(format!("squeue --json failed: {}", out.stderr)));
+    }
+    let time: DateTime<Utc> = SystemTime::now().into();
+    let now = time.naive_utc();
+    let d: Vec<SqueueRow> = parse_slurm_json(&out.stdout)?
+        .iter()
+        .filter_map(|job| match SqueueRow::parse_from_json(job, now) {
+            Ok(row) => Some(row),
+            Err(err) => {
+                println!("[!] {:?} for {:?}", err, job.get("job_id"));
+                None
+            }
+        })
+        .collect();
+    Ok((time, d))
+}
+
+/// Get squeue results using the provided [`CommandExecutor`]
+///
+/// Fails if `squeue` exits with a non-zero exit code (e.g., if the SLURM controller timed out),
+/// instead of returning an empty queue.
+pub async fn get_squeue_res<E: CommandExecutor>(
+    mode: &SqueueMode,
+    executor: &E,
+) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error> {
+    let extra_arg = mode.to_arg();
+    let out = executor
+        .execute(&format!(
+            "squeue -h -a -M all -t all --format='{SQUEUE_FORMAT_STR}' {extra_arg}"
+        ))
+        .await?;
+    if !out.success() {
+        return Err(Error::msg(format!("squeue failed: {}", out.stderr.trim())));
+    }
+    let result = out.stdout;
     let res_lines = result.split("\n");
 
     // For checking columns:
@@ -240,20 +391,10 @@ where
 pub async fn get_squeue_res_locally(
     mode: &SqueueMode,
 ) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error> {
-    get_squeue_res(mode, |cmd_s| async move {
-        // let splits: Vec<&str> = cmd.split(" ").collect();
-        // println!("{:#?}",splits);
-        // cmd.args(splits.iter().skip(1));
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c").arg(&cmd_s);
-        let d = Instant::now();
-        let out = cmd.output()?;
-        let s = String::from_utf8(out.stdout)?;
-        // println!("{:?}",out);
-        println!("Running squeue took {:?}", d.elapsed());
-        Ok(s)
-    })
-    .await
+    let d = Instant::now();
+    let res = get_squeue_res(mode, &LocalExecutor).await;
+    println!("Running squeue took {:?}", d.elapsed());
+    res
 }
 
 #[cfg(feature = "ssh")]
@@ -262,28 +403,81 @@ pub async fn get_squeue_res_ssh(
     client: &Client,
     mode: &SqueueMode,
 ) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error> {
-    get_squeue_res(mode, |cmd| async move {
-        let r = client.execute(&cmd).await?;
-        Ok(r.stdout)
-    })
-    .await
+    get_squeue_res(mode, client).await
+}
+use super::{
+    sacct::{get_sacct_res, SacctMode},
+    store::{SnapshotDiff, SnapshotStore},
+};
+
+#[derive(Debug, Clone, Default)]
+/// State carried between [`squeue_diff`] calls (e.g., of a polling loop)
+pub struct SqueueDiffState {
+    /// Jobs of the previous snapshot (by job ID)
+    pub known_jobs: HashMap<String, SqueueRow>,
+    /// IDs of all jobs seen so far
+    pub all_ids: HashSet<String>,
+    /// Time of the previous snapshot
+    pub last_time: Option<DateTime<Utc>>,
+    /// Disappeared jobs whose terminal state was not recorded yet (see [`record_finished_jobs`])
+    ///
+    /// Jobs are dropped [`FINISHED_LOOKUP_HOURS`] after they disappeared.
+    pub disappeared: Vec<JobDisappeared>,
+}
+
+/// Hours after which the terminal state of a disappeared job is no longer looked up (see [`SqueueDiffState::disappeared`])
+pub const FINISHED_LOOKUP_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Record of a job which is no longer listed by `squeue`
+///
+/// Saved as `DISAPPEARED-{time}.json` in the folder of the job by a [`DirectoryStore`](super::DirectoryStore) (next to its `DELTA-{time}.json` files).
+pub struct JobDisappeared {
+    /// ID of the job
+    pub job_id: String,
+    /// State the job was last seen in
+    pub last_state: JobState,
+    /// Time of the last snapshot containing the job
+    pub last_seen: DateTime<Utc>,
+    /// Time of the first snapshot no longer containing the job
+    pub disappeared: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Terminal state of a disappeared job according to `sacct` (see [`record_finished_jobs`])
+///
+/// Saved as `FINISHED-{time}.json` in the folder of the job by a [`DirectoryStore`](super::DirectoryStore).
+pub struct JobFinished {
+    /// ID of the job
+    pub job_id: String,
+    /// Final state of the job
+    pub state: JobState,
+    /// Exit code of the job
+    pub exit_code: i32,
+    /// Signal which terminated the job (`0` if none)
+    pub exit_signal: i32,
+    /// End time of the job (if available)
+    pub end_time: Option<NaiveDateTime>,
+    /// Time of the `sacct` lookup
+    pub time: DateTime<Utc>,
 }
-use rayon::prelude::*;
 
 /// Execute `squeue` and compare the output with (optional) data from previous executions
-pub async fn squeue_diff<'b, F, Fut>(
+///
+/// New jobs, changes of known jobs and jobs no longer listed (see [`JobDisappeared`]) are saved into the passed store
+/// (e.g., a [`DirectoryStore`](super::DirectoryStore) or `SqliteStore`).
+/// The state is only updated if the changes were saved successfully.
+pub async fn squeue_diff<F, Fut, S>(
     get_squeue: F,
-    path: &Path,
-    known_jobs: &'b mut HashMap<String, SqueueRow>,
-    all_ids: &'b mut HashSet<String>,
+    store: &mut S,
+    state: &mut SqueueDiffState,
 ) -> Result<(DateTime<Utc>, Vec<SqueueRow>), Error>
 where
     F: FnOnce() -> Fut,
     Fut: Future<Output = Result<(DateTime<Utc>, Vec<SqueueRow>), Error>>,
+    S: SnapshotStore + ?Sized,
 {
     let (time, rows) = get_squeue().await?;
-    // let (time, rows) = get_squeue_res(client).await?;
-    let cleaned_time = time.to_rfc3339().replace(":", "_");
     let row_ids = rows
         .iter()
         .map(|r| r.job_id.clone())
@@ -292,92 +486,128 @@ where
     if rows.len() != row_ids.len() {
         eprintln!("Count mismatch: {} != {}", rows.len(), row_ids.len());
     }
-    create_dir_all(path)?;
-    let id_save_path = path.join(format!("{cleaned_time}.json"));
-    if let Err(e) = serde_json::to_writer(
-        BufWriter::new(File::create(id_save_path).unwrap()),
-        &row_ids,
-    ) {
-        eprintln!("Failed to create file for all jobs ids: {e:?}");
-    }
-    *known_jobs = rows
-        .par_iter()
-        .map(|row| {
-            if let Some(prev_row) = known_jobs.get(&row.job_id) {
-                // Job is known!
-                // Compute delta
-                let diff = prev_row.diff(row);
-                if !diff.is_empty() {
-                    // Save job delta (e.g., as JSON)
-                    let save_path = path
-                        .join(&row.job_id)
-                        .join(format!("DELTA-{cleaned_time}.json"));
-                    if let Err(e) = serde_json::to_writer(
-                        BufWriter::new(File::create(save_path).unwrap()),
-                        &diff,
-                    ) {
-                        eprintln!("Failed to create file for {}: {:?}", row.job_id, e);
-                    }
-                }
-                // Update prev_row in known_jobs
-                (row.job_id.clone(), row.clone())
-                // rw.write().unwrap().insert(row.job_id.clone(), row.clone());
-                // *prev_row = row.clone();
-            } else {
-                // Job is new!
-                // Double check with all_ids:
-                if all_ids.contains(&row.job_id) {
-                    eprintln!("Job re-appeared! Maybe IDs get reused?");
-                }
-                let folder_path = path.join(&row.job_id);
-                create_dir_all(&folder_path).unwrap();
-                // Save job (e.g., as JSON)
-                let save_path = folder_path.join(format!("{cleaned_time}.json"));
-                if let Err(e) =
-                    serde_json::to_writer(BufWriter::new(File::create(save_path).unwrap()), &row)
-                {
-                    eprintln!("Failed to create file for {}: {:?}", row.job_id, e);
-                }
-                // rw.write().unwrap().insert(row.job_id.clone(), row.clone());
-                (row.job_id.clone(), row.clone())
-            }
+    // Jobs which were listed in the previous snapshot, but are no longer
+    let disappeared: Vec<_> = state
+        .known_jobs
+        .values()
+        .filter(|prev_row| !row_ids.contains(&prev_row.job_id))
+        .map(|prev_row| JobDisappeared {
+            job_id: prev_row.job_id.clone(),
+            last_state: prev_row.state.clone(),
+            last_seen: state.last_time.unwrap_or(time),
+            disappeared: time,
         })
         .collect();
-    // let known_jobs = rw.into_inner().unwrap();
-    // Remove all known jobs which
-    // known_jobs.retain(|j_id, _| row_ids.contains(j_id));
-    all_ids.extend(row_ids);
+    let mut new_jobs = Vec::new();
+    let mut deltas = Vec::new();
+    for row in &rows {
+        if let Some(prev_row) = state.known_jobs.get(&row.job_id) {
+            // Job is known!
+            // Compute delta
+            let diff = prev_row.diff(row);
+            if !diff.is_empty() {
+                deltas.push((row.job_id.as_str(), diff));
+            }
+        } else {
+            // Job is new!
+            // Double check with all_ids:
+            if state.all_ids.contains(&row.job_id) {
+                eprintln!("Job re-appeared! Maybe IDs get reused?");
+            }
+            new_jobs.push(row);
+        }
+    }
+    store.save_diff(&SnapshotDiff {
+        time,
+        job_ids: &row_ids,
+        new_jobs,
+        deltas,
+        disappeared: &disappeared,
+    })?;
+    state.known_jobs = rows
+        .iter()
+        .map(|row| (row.job_id.clone(), row.clone()))
+        .collect();
+    state.all_ids.extend(row_ids);
+    state.last_time = Some(time);
+    state
+        .disappeared
+        .retain(|job| time - job.disappeared < chrono::Duration::hours(FINISHED_LOOKUP_HOURS));
+    state.disappeared.extend(disappeared);
     Ok((time, rows))
 }
 
+/// Look up the terminal state of disappeared jobs (see [`SqueueDiffState::disappeared`]) using `sacct`
+///
+/// The results (see [`JobFinished`]) are saved into the passed store and the recorded jobs are removed from the state.
+/// Jobs which are not finished according to `sacct` (e.g., still `COMPLETING`) or unknown to it
+/// (e.g., because accounting is lagging or disabled) are kept, so that the next call tries again.
+pub async fn record_finished_jobs<E: CommandExecutor, S: SnapshotStore + ?Sized>(
+    executor: &E,
+    store: &mut S,
+    state: &mut SqueueDiffState,
+) -> Result<Vec<JobFinished>, Error> {
+    if state.disappeared.is_empty() {
+        return Ok(Vec::new());
+    }
+    let job_ids: Vec<_> = state.disappeared.iter().map(|d| d.job_id.clone()).collect();
+    let (time, rows) = get_sacct_res(&SacctMode::default().with_job_ids(job_ids), executor).await?;
+    let finished: Vec<_> = state
+        .disappeared
+        .iter()
+        .filter_map(|job| {
+            // Array tasks are listed by their raw ID
+            let row = rows
+                .iter()
+                .filter(|row| !row.is_step() && row.state.is_finished())
+                .find(|row| row.job_id_raw == job.job_id || row.job_id == job.job_id)?;
+            Some(JobFinished {
+                job_id: job.job_id.clone(),
+                state: row.state.clone(),
+                exit_code: row.exit_code,
+                exit_signal: row.exit_signal,
+                end_time: row.end_time,
+                time,
+            })
+        })
+        .collect();
+    store.save_finished(&finished)?;
+    state
+        .disappeared
+        .retain(|job| !finished.iter().any(|f| f.job_id == job.job_id));
+    Ok(finished)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{
-        collections::{HashMap, HashSet},
-        path::PathBuf,
-    };
+    use chrono::Utc;
 
-    use crate::data_extraction::{get_squeue_res_locally, SqueueMode};
     #[cfg(feature = "ssh")]
     use crate::login_with_cfg;
+    use crate::{
+        data_extraction::{
+            get_squeue_res, get_squeue_res_json, get_squeue_res_locally, record_finished_jobs,
+            squeue_diff, DirectoryStore, JobDisappeared, SqueueDiffState, SqueueMode,
+        },
+        executor::{CommandOutput, MockExecutor},
+        JobState,
+    };
 
     #[cfg(feature = "ssh")]
     #[tokio::test]
     async fn test_squeue_loop() {
         let login_cfg = crate::misc::get_config_from_env();
         let client = login_with_cfg(&login_cfg).await.unwrap();
-        let mut known_jobs = HashMap::default();
-        let mut all_ids = HashSet::default();
-        let path = PathBuf::new().join("test_squeue_loop-14-01-2025");
+        use crate::data_extraction::get_squeue_res_ssh;
+
+        let mut state = SqueueDiffState::default();
+        let mut store = DirectoryStore::new("test_squeue_loop-14-01-2025");
         let mut i = 0;
         loop {
-            use crate::data_extraction::{get_squeue_res_ssh, squeue_diff};
-
             squeue_diff(
                 || get_squeue_res_ssh(&client, &SqueueMode::ALL),
-                &path,
-                &mut known_jobs,
-                &mut all_ids,
+                &mut store,
+                &mut state,
             )
             .await
             .unwrap();
@@ -387,9 +617,189 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_squeue_diff_disappeared() {
+        let executor = MockExecutor::new().with_stdout(
+            "squeue",
+            "acc|123|n001|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|RUNNING|None|2025-01-14T10:00:00|2025-01-14T09:59:00|/home/user|./run.sh\n",
+        );
+        let path = std::env::temp_dir().join("slurry_test_squeue_diff_disappeared");
+        let mut store = DirectoryStore::new(&path);
+        let mut state = SqueueDiffState::default();
+        let (first_time, _) = squeue_diff(
+            || get_squeue_res(&SqueueMode::ALL, &executor),
+            &mut store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        assert!(state.disappeared.is_empty());
+        // The job left the queue
+        let (time, _) = squeue_diff(
+            || async { Ok((Utc::now(), Vec::new())) },
+            &mut store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            state.disappeared,
+            vec![JobDisappeared {
+                job_id: String::from("123"),
+                last_state: JobState::RUNNING,
+                last_seen: first_time,
+                disappeared: time,
+            }]
+        );
+        let cleaned_time = time.to_rfc3339().replace(":", "_");
+        assert!(path
+            .join("123")
+            .join(format!("DISAPPEARED-{cleaned_time}.json"))
+            .exists());
+
+        // Unknown to sacct or not finished yet, so the lookup is retried
+        for stdout in ["", "123|123|myjob|user|grp|acc|part|COMPLETING|0:0|1-00:00:00|2025-01-14T09:59:00|2025-01-14T10:00:00|Unknown|2|1|n001|4G||/home/user\n"] {
+            let executor = MockExecutor::new().with_stdout("sacct", stdout);
+            let finished = record_finished_jobs(&executor, &mut store, &mut state)
+                .await
+                .unwrap();
+            assert!(finished.is_empty());
+            assert_eq!(state.disappeared.len(), 1);
+        }
+        // Still pending after the next snapshot
+        squeue_diff(
+            || async { Ok((Utc::now(), Vec::new())) },
+            &mut store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        assert_eq!(state.disappeared.len(), 1);
+
+        let executor = MockExecutor::new().with_stdout(
+            "sacct",
+            "123|123|myjob|user|grp|acc|part|TIMEOUT|0:15|1-00:00:00|2025-01-14T09:59:00|2025-01-14T10:00:00|2025-01-15T10:00:00|2|1|n001|4G||/home/user\n",
+        );
+        let finished = record_finished_jobs(&executor, &mut store, &mut state)
+            .await
+            .unwrap();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].state, JobState::TIMEOUT);
+        assert_eq!(finished[0].exit_signal, 15);
+        assert!(state.disappeared.is_empty());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_squeue_diff_failed() {
+        let executor = MockExecutor::new().with_responses(
+            "squeue",
+            [
+                CommandOutput::from_stdout("acc|123|n001|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|RUNNING|None|2025-01-14T10:00:00|2025-01-14T09:59:00|/home/user|./run.sh\n"),
+                CommandOutput {
+                    stdout: String::new(),
+                    stderr: String::from("slurm_load_jobs error: Socket timed out on send/recv operation"),
+                    exit_code: 1,
+                },
+            ],
+        );
+        let path = std::env::temp_dir().join("slurry_test_squeue_diff_failed");
+        // Remove leftovers of previous (failed) runs
+        let _ = std::fs::remove_dir_all(&path);
+        let mut store = DirectoryStore::new(&path);
+        let mut state = SqueueDiffState::default();
+        squeue_diff(
+            || get_squeue_res(&SqueueMode::ALL, &executor),
+            &mut store,
+            &mut state,
+        )
+        .await
+        .unwrap();
+        let last_time = state.last_time;
+        // A failed squeue must not be mistaken for an empty queue
+        assert!(squeue_diff(
+            || get_squeue_res(&SqueueMode::ALL, &executor),
+            &mut store,
+            &mut state,
+        )
+        .await
+        .is_err());
+        assert!(state.known_jobs.contains_key("123"));
+        assert_eq!(state.last_time, last_time);
+        assert!(state.disappeared.is_empty());
+        assert_eq!(std::fs::read_dir(path.join("123")).unwrap().count(), 1);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
     #[tokio::test]
     async fn test_local() {
+        // Only possible on a SLURM system
+        if std::process::Command::new("squeue")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            println!("squeue is not available, skipping");
+            return;
+        }
         let res = get_squeue_res_locally(&SqueueMode::ALL).await.unwrap();
         println!("Got {} results", res.1.len())
     }
+
+    #[tokio::test]
+    async fn test_mock() {
+        let executor = MockExecutor::new().with_stdout(
+            "squeue",
+            "acc|123|n/a|1|2|1|N/A|(null)|(null)|123|grp|123_4|1-00:00:00|1-00:00:00|myjob|4G|0:00|0.5|part|PENDING|Priority|N/A|2025-01-14T10:00:00|/home/user|./run.sh\n",
+        );
+        let (_time, rows) = get_squeue_res(&SqueueMode::MINE, &executor).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].job_id, "123");
+        assert_eq!(rows[0].cpus, 2);
+        assert_eq!(rows[0].state, JobState::PENDING);
+        assert_eq!(
+            rows[0].step_job_id,
+            ("123".to_string(), Some("4".to_string()))
+        );
+        assert!(executor.executed_commands()[0].ends_with("--me"));
+    }
+
+    #[tokio::test]
+    async fn test_json_mock() {
+        let executor = MockExecutor::new().with_stdout(
+            "squeue --json",
+            r#"{"jobs": [{"account": "acc", "job_id": 124, "array_job_id": {"set": true, "infinite": false, "number": 123},
+                "array_task_id": {"set": true, "infinite": false, "number": 4}, "array_task_string": "",
+                "batch_host": "n001", "command": "./run.sh --name 'a|b'", "cpus": {"set": true, "infinite": false, "number": 8},
+                "minimum_cpus_per_node": {"set": true, "infinite": false, "number": 8}, "node_count": {"set": true, "infinite": false, "number": 1},
+                "end_time": {"set": true, "infinite": false, "number": 1736852400}, "dependency": "", "features": "",
+                "group_name": "grp", "time_limit": {"set": true, "infinite": false, "number": 60}, "name": "a|b",
+                "memory_per_node": {"set": true, "infinite": false, "number": 4096}, "priority": {"set": true, "infinite": false, "number": 10},
+                "partition": "gpu", "job_state": ["RUNNING"], "state_reason": "None",
+                "start_time": {"set": true, "infinite": false, "number": 1736848800},
+                "submit_time": {"set": true, "infinite": false, "number": 1736848700},
+                "current_working_directory": "/home/user", "user_name": "user", "qos": "normal", "nodes": "n001"}],
+              "meta": {"plugin": {"data_parser": "data_parser/v0.0.40"}}, "errors": [], "warnings": []}"#,
+        );
+        let (_time, rows) = get_squeue_res_json(&SqueueMode::ALL, &executor)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].job_id, "124");
+        assert_eq!(rows[0].name, "a|b");
+        assert_eq!(rows[0].array_job_id, "123");
+        assert_eq!(
+            rows[0].step_job_id,
+            ("123".to_string(), Some("4".to_string()))
+        );
+        assert_eq!(rows[0].state, JobState::RUNNING);
+        assert_eq!(rows[0].min_memory, "4096M");
+        assert_eq!(rows[0].time_limit.unwrap().as_secs(), 3600);
+        assert_eq!(rows[0].node_list.as_deref(), Some("n001"));
+        assert_eq!(
+            rows[0].start_time.unwrap().to_string(),
+            "2025-01-14 10:00:00"
+        );
+        assert!(executor.executed_commands()[0].contains(" -M all "));
+    }
 }
@@ -0,0 +1,435 @@
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, read_dir, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use structdiff::StructDiff;
+
+use super::{
+    history::HistorySource,
+    squeue::{JobDisappeared, JobFinished, SqueueRow},
+};
+
+/// A single change of a [`SqueueRow`] (as computed by [`StructDiff::diff`])
+pub type SqueueRowDelta = <SqueueRow as StructDiff>::Diff;
+
+#[derive(Debug)]
+/// Changes of one `squeue` snapshot compared to the previous one (see [`super::squeue_diff`])
+pub struct SnapshotDiff<'a> {
+    /// Time of the snapshot
+    pub time: DateTime<Utc>,
+    /// IDs of all listed jobs
+    pub job_ids: &'a HashSet<String>,
+    /// Jobs listed for the first time
+    pub new_jobs: Vec<&'a SqueueRow>,
+    /// Changes of already known jobs (by job ID)
+    pub deltas: Vec<(&'a str, Vec<SqueueRowDelta>)>,
+    /// Jobs which are no longer listed
+    pub disappeared: &'a [JobDisappeared],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A saved record of a single job (see [`DirectoryStore::read_job`])
+pub enum JobRecord {
+    /// The job was listed for the first time
+    Observed(DateTime<Utc>, Box<SqueueRow>),
+    /// The job changed
+    Delta(DateTime<Utc>, Vec<SqueueRowDelta>),
+    /// The job was no longer listed
+    Disappeared(JobDisappeared),
+    /// The terminal state of the job was looked up
+    Finished(JobFinished),
+}
+
+impl JobRecord {
+    /// Time of the record
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            JobRecord::Observed(time, _) | JobRecord::Delta(time, _) => *time,
+            JobRecord::Disappeared(disappeared) => disappeared.disappeared,
+            JobRecord::Finished(finished) => finished.time,
+        }
+    }
+}
+
+/// Storage backend for `squeue` snapshots and their changes
+///
+/// Implemented by [`DirectoryStore`] (one JSON file per change), `SqliteStore` (a single database file, requires the `sqlite` feature)
+/// and `ArchiveStore` (compressed segment files, requires the `archive` feature).
+pub trait SnapshotStore: Send {
+    /// Save the changes of a snapshot
+    fn save_diff(&mut self, diff: &SnapshotDiff<'_>) -> Result<(), Error>;
+
+    /// Save the terminal states of finished jobs (see [`super::record_finished_jobs`])
+    fn save_finished(&mut self, finished: &[JobFinished]) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone)]
+/// Store saving every snapshot and change as a separate JSON file
+///
+/// New jobs are saved as `{job_id}/{time}.json`, changes of known jobs as `{job_id}/DELTA-{time}.json`,
+/// jobs no longer listed as `{job_id}/DISAPPEARED-{time}.json` and finished jobs as `{job_id}/FINISHED-{time}.json`.
+/// The IDs of all listed jobs are saved as `{time}.json`.
+/// The times of all snapshots are appended to `index.jsonl`, so that they can be read without listing the whole directory.
+///
+/// Files which cannot be written are skipped (and reported on stderr), so that the rest of the snapshot is still saved
+/// and later snapshots do not save the same changes again.
+pub struct DirectoryStore {
+    path: PathBuf,
+}
+
+impl DirectoryStore {
+    /// Create a new store saving into the given directory (created if missing)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Directory of the store
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn save_job_file<T: Serialize>(
+        &self,
+        job_id: &str,
+        file_name: &str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let folder_path = self.path.join(job_id);
+        create_dir_all(&folder_path)?;
+        write_json(&folder_path.join(file_name), value)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.path.join(INDEX_FILE)
+    }
+
+    /// Times of all snapshots according to the index (sorted), or `None` if there is no index yet
+    ///
+    /// Lines which cannot be parsed (e.g., partially written ones) are skipped.
+    pub fn read_index(&self) -> Result<Option<Vec<DateTime<Utc>>>, Error> {
+        let file = match File::open(self.index_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut times = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str::<IndexEntry>(&line?) {
+                times.push(entry.time);
+            }
+        }
+        times.sort();
+        times.dedup();
+        Ok(Some(times))
+    }
+
+    /// Write the index again from the snapshot files (`{time}.json`) of the directory
+    ///
+    /// Done automatically when saving into a directory without index (e.g., one saved by an older version).
+    /// Returns the number of indexed snapshots.
+    pub fn rebuild_index(&self) -> Result<usize, Error> {
+        let times = self.scan_snapshot_times()?;
+        let mut writer = BufWriter::new(File::create(self.index_path())?);
+        for time in &times {
+            serde_json::to_writer(&mut writer, &IndexEntry { time: *time })?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(times.len())
+    }
+
+    fn append_index(&self, time: DateTime<Utc>) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(&IndexEntry { time })?;
+        line.push(b'\n');
+        // A single write, so that concurrent readers never see a partial line of a successful append
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.index_path())?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Times of all snapshot files (`{time}.json`) in the directory (sorted)
+    pub(crate) fn scan_snapshot_times(&self) -> Result<Vec<DateTime<Utc>>, Error> {
+        let mut times = Vec::new();
+        for entry in read_dir(&self.path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name();
+            if let Some(time) = name
+                .to_str()
+                .and_then(|n| n.strip_suffix(".json"))
+                .and_then(parse_cleaned_time)
+            {
+                times.push(time);
+            }
+        }
+        times.sort();
+        Ok(times)
+    }
+
+    /// IDs of all saved jobs (sorted)
+    pub fn job_ids(&self) -> Result<Vec<String>, Error> {
+        let mut job_ids = Vec::new();
+        for entry in read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                job_ids.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        job_ids.sort();
+        Ok(job_ids)
+    }
+
+    /// All saved records of a job (sorted by time)
+    ///
+    /// Unknown files in the folder of the job are ignored.
+    pub fn read_job(&self, job_id: &str) -> Result<Vec<JobRecord>, Error> {
+        let mut records = Vec::new();
+        for entry in read_dir(self.path.join(job_id))? {
+            let path = entry?.path();
+            if let Some((kind, time)) = parse_job_file_name(&path) {
+                records.push(read_job_file(&path, kind, time)?);
+            }
+        }
+        sort_records(&mut records);
+        Ok(records)
+    }
+
+    /// Save all snapshots and changes of this directory into another store (e.g., to convert it into an archive)
+    ///
+    /// The snapshots are passed in time order, only the records of a single snapshot are kept in memory.
+    /// Returns the number of passed snapshots.
+    pub fn copy_to<S: SnapshotStore + ?Sized>(&self, dest: &mut S) -> Result<usize, Error> {
+        // Index all files by time first, so that they can be read one snapshot after another
+        let mut files = Vec::new();
+        for job_id in self.job_ids()? {
+            for entry in read_dir(self.path.join(&job_id))? {
+                let path = entry?.path();
+                if let Some((kind, time)) = parse_job_file_name(&path) {
+                    files.push((time, kind, job_id.clone(), path));
+                }
+            }
+        }
+        files.sort_by_key(|(time, kind, _, _)| (*time, !kind.is_empty()));
+        let snapshot_times: HashSet<_> = self.snapshot_times()?.into_iter().collect();
+        let mut times: Vec<_> = snapshot_times
+            .iter()
+            .copied()
+            .chain(files.iter().map(|(time, _, _, _)| *time))
+            .collect();
+        times.sort();
+        times.dedup();
+        let mut files = files.into_iter().peekable();
+        let mut snapshots = 0;
+        for time in times {
+            let job_ids = match snapshot_times.contains(&time) {
+                true => self.snapshot_job_ids(time)?,
+                false => HashSet::new(),
+            };
+            let (mut new_jobs, mut deltas, mut disappeared, mut finished) =
+                (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+            while let Some((time, kind, job_id, path)) = files.next_if(|(t, _, _, _)| *t == time) {
+                match read_job_file(&path, kind, time)? {
+                    JobRecord::Observed(_, row) => new_jobs.push(*row),
+                    JobRecord::Delta(_, delta) => deltas.push((job_id, delta)),
+                    JobRecord::Disappeared(record) => disappeared.push(record),
+                    JobRecord::Finished(record) => finished.push(record),
+                }
+            }
+            // Records of `sacct` lookups are not part of a snapshot
+            if snapshot_times.contains(&time)
+                || !new_jobs.is_empty()
+                || !deltas.is_empty()
+                || !disappeared.is_empty()
+            {
+                dest.save_diff(&SnapshotDiff {
+                    time,
+                    job_ids: &job_ids,
+                    new_jobs: new_jobs.iter().collect(),
+                    deltas: deltas
+                        .iter_mut()
+                        .map(|(job_id, delta)| (job_id.as_str(), std::mem::take(delta)))
+                        .collect(),
+                    disappeared: &disappeared,
+                })?;
+                snapshots += 1;
+            }
+            if !finished.is_empty() {
+                dest.save_finished(&finished)?;
+            }
+        }
+        Ok(snapshots)
+    }
+}
+
+/// File name of the snapshot index of a [`DirectoryStore`]
+const INDEX_FILE: &str = "index.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Line of the snapshot index of a [`DirectoryStore`]
+struct IndexEntry {
+    time: DateTime<Utc>,
+}
+
+/// Kind (i.e., the prefix) and time of a file in the folder of a job
+fn parse_job_file_name(path: &Path) -> Option<(&'static str, DateTime<Utc>)> {
+    let name = path.file_name()?.to_str()?.strip_suffix(".json")?;
+    let (kind, time) = match name.split_once('-') {
+        Some(("DELTA", time)) => ("DELTA", time),
+        Some(("DISAPPEARED", time)) => ("DISAPPEARED", time),
+        Some(("FINISHED", time)) => ("FINISHED", time),
+        // Initial rows have no prefix
+        _ => ("", name),
+    };
+    Some((kind, parse_cleaned_time(time)?))
+}
+
+fn read_job_file(path: &Path, kind: &str, time: DateTime<Utc>) -> Result<JobRecord, Error> {
+    Ok(match kind {
+        "DELTA" => JobRecord::Delta(time, read_json(path)?),
+        "DISAPPEARED" => JobRecord::Disappeared(read_json(path)?),
+        "FINISHED" => JobRecord::Finished(read_json(path)?),
+        _ => JobRecord::Observed(time, read_json(path)?),
+    })
+}
+
+/// Sort records by time, initial rows before changes of the same snapshot
+pub(crate) fn sort_records(records: &mut [JobRecord]) {
+    records.sort_by_key(|r| (r.time(), !matches!(r, JobRecord::Observed(..))));
+}
+
+/// Format a time for use in file names (i.e., without `:`)
+pub(crate) fn cleaned_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339().replace(":", "_")
+}
+
+/// Parse a time formatted by [`cleaned_time`]
+pub(crate) fn parse_cleaned_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&s.replace("_", ":"))
+        .ok()
+        .map(|t| t.to_utc())
+}
+
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+fn write_json<T: Serialize>(save_path: &Path, value: &T) -> Result<(), Error> {
+    serde_json::to_writer(BufWriter::new(File::create(save_path)?), value)?;
+    Ok(())
+}
+
+/// Report a file which could not be written, without aborting the rest of the snapshot
+fn log_failure(name: &str, res: Result<(), Error>) {
+    if let Err(e) = res {
+        eprintln!("Failed to create file for {name}: {e:?}");
+    }
+}
+
+impl SnapshotStore for DirectoryStore {
+    fn save_diff(&mut self, diff: &SnapshotDiff<'_>) -> Result<(), Error> {
+        let cleaned_time = cleaned_time(&diff.time);
+        create_dir_all(&self.path)?;
+        if !self.index_path().exists() {
+            log_failure("snapshot index", self.rebuild_index().map(|_| ()));
+        }
+        log_failure(
+            "all jobs ids",
+            write_json(
+                &self.path.join(format!("{cleaned_time}.json")),
+                diff.job_ids,
+            ),
+        );
+        log_failure("snapshot index", self.append_index(diff.time));
+        for disappeared in diff.disappeared {
+            log_failure(
+                &disappeared.job_id,
+                self.save_job_file(
+                    &disappeared.job_id,
+                    &format!("DISAPPEARED-{cleaned_time}.json"),
+                    disappeared,
+                ),
+            );
+        }
+        diff.new_jobs.par_iter().for_each(|row| {
+            log_failure(
+                &row.job_id,
+                self.save_job_file(&row.job_id, &format!("{cleaned_time}.json"), row),
+            )
+        });
+        diff.deltas.par_iter().for_each(|(job_id, delta)| {
+            log_failure(
+                job_id,
+                self.save_job_file(job_id, &format!("DELTA-{cleaned_time}.json"), delta),
+            )
+        });
+        Ok(())
+    }
+
+    fn save_finished(&mut self, finished: &[JobFinished]) -> Result<(), Error> {
+        for record in finished {
+            self.save_job_file(
+                &record.job_id,
+                &format!("FINISHED-{}.json", cleaned_time(&record.time)),
+                record,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::data_extraction::{
+        get_squeue_res, squeue_diff, DirectoryStore, HistorySource, SqueueDiffState, SqueueMode,
+    };
+    use crate::executor::MockExecutor;
+
+    const ROW: &str = "acc|{id}|n001|1|2|1|N/A|(null)|(null)|{id}|grp|{id}|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|PENDING|None|N/A|2025-01-14T09:59:00|/home/user|./run.sh\n";
+
+    #[tokio::test]
+    async fn test_directory_store_partial_failure() {
+        let path = std::env::temp_dir().join("slurry_test_directory_store_partial_failure");
+        // Remove leftovers of previous (failed) runs
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        // The folder of job 1 cannot be created
+        std::fs::write(path.join("1"), "").unwrap();
+        let executor = MockExecutor::new().with_stdout(
+            "squeue",
+            format!("{}{}", ROW.replace("{id}", "1"), ROW.replace("{id}", "2")),
+        );
+        let mut store = DirectoryStore::new(&path);
+        let mut state = SqueueDiffState::default();
+        for _ in 0..2 {
+            squeue_diff(
+                || get_squeue_res(&SqueueMode::ALL, &executor),
+                &mut store,
+                &mut state,
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(state.known_jobs.len(), 2);
+        // Job 2 is only saved once, even though saving job 1 failed
+        assert_eq!(std::fs::read_dir(path.join("2")).unwrap().count(), 1);
+        assert_eq!(
+            store.snapshot_job_ids(state.last_time.unwrap()).unwrap(),
+            HashSet::from(["1".to_string(), "2".to_string()])
+        );
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+#[cfg(not(feature = "tokio"))]
+use std::{fs, process::Command};
+
+use anyhow::Error;
+#[cfg(feature = "tokio")]
+use tokio::{fs, process::Command};
+
+use super::{CommandExecutor, CommandOutput};
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Executes commands on the local machine using `sh -c`
+///
+/// Useful when running directly on a login node of the SLURM system.
+/// File transfers are plain copies on the local file system.
+/// With the `tokio` feature, commands and file operations do not block the async runtime;
+/// without it, they block the current thread.
+pub struct LocalExecutor;
+
+impl CommandExecutor for LocalExecutor {
+    async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        #[cfg(feature = "tokio")]
+        let out = cmd.output().await?;
+        #[cfg(not(feature = "tokio"))]
+        let out = cmd.output()?;
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+            exit_code: out.status.code().unwrap_or(-1),
+        })
+    }
+
+    async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        let copy = fs::copy(local_path, remote_path);
+        #[cfg(feature = "tokio")]
+        let copy = copy.await;
+        copy?;
+        Ok(())
+    }
+
+    async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let copy = fs::copy(remote_path, local_path);
+        #[cfg(feature = "tokio")]
+        let copy = copy.await;
+        copy?;
+        Ok(())
+    }
+
+    async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        let write = fs::write(remote_path, content);
+        #[cfg(feature = "tokio")]
+        let write = write.await;
+        write?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::{CommandExecutor, LocalExecutor};
+
+    #[tokio::test]
+    async fn test_local_executor() {
+        let dir = std::env::temp_dir().join("slurry_test_local_executor");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Invalid UTF-8 is replaced instead of failing the command
+        let out = LocalExecutor
+            .execute("printf 'a\\377b'; echo err >&2; exit 3")
+            .await
+            .unwrap();
+        assert_eq!(out.stdout, "a\u{FFFD}b");
+        assert_eq!(out.stderr, "err\n");
+        assert_eq!(out.exit_code, 3);
+
+        let path = dir.join("file.txt").to_string_lossy().to_string();
+        LocalExecutor.write_file(&path, b"content").await.unwrap();
+        let copy = dir.join("copy.txt");
+        LocalExecutor.download_file(&path, &copy).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "content");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
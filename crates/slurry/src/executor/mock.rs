@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Error;
+
+use super::{CommandExecutor, CommandOutput};
+
+// Command pattern and the outputs returned for it
+type Response = (String, VecDeque<CommandOutput>);
+
+#[derive(Debug, Clone, Default)]
+/// In-memory executor returning canned responses
+///
+/// Responses are registered for command patterns (see [`MockExecutor::with_response`]).
+/// An executed command is answered by the first registered response whose pattern is contained in the command.
+/// Commands without a matching response succeed with empty output.
+///
+/// All executed commands and transferred files are recorded and can be inspected afterwards.
+/// Clones share the same state.
+pub struct MockExecutor {
+    responses: Arc<Mutex<Vec<Response>>>,
+    executed: Arc<Mutex<Vec<String>>>,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl MockExecutor {
+    /// Create a new mock executor without any registered responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the output returned for commands containing `pattern`
+    pub fn with_response(self, pattern: impl Into<String>, output: CommandOutput) -> Self {
+        self.with_responses(pattern, [output])
+    }
+
+    /// Register outputs returned one after another for commands containing `pattern` (e.g., a job changing its state)
+    ///
+    /// Once all other outputs were returned, the last output is returned for all further commands.
+    pub fn with_responses(
+        self,
+        pattern: impl Into<String>,
+        outputs: impl IntoIterator<Item = CommandOutput>,
+    ) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .push((pattern.into(), outputs.into_iter().collect()));
+        self
+    }
+
+    /// Register a successful command output (with the given stdout) for commands containing `pattern`
+    pub fn with_stdout(self, pattern: impl Into<String>, stdout: impl Into<String>) -> Self {
+        self.with_response(pattern, CommandOutput::from_stdout(stdout))
+    }
+
+    /// Place a file with the given content on the mocked (remote) system
+    pub fn with_file(self, remote_path: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(remote_path.into(), content.into());
+        self
+    }
+
+    /// All commands executed so far (in order of execution)
+    pub fn executed_commands(&self) -> Vec<String> {
+        self.executed.lock().unwrap().clone()
+    }
+
+    /// Content of a file on the mocked (remote) system (e.g., a previously uploaded file)
+    pub fn file(&self, remote_path: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(remote_path).cloned()
+    }
+}
+
+impl CommandExecutor for MockExecutor {
+    async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        self.executed.lock().unwrap().push(command.to_string());
+        let output = self
+            .responses
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|(pattern, _)| command.contains(pattern.as_str()))
+            .and_then(|(_, outputs)| match outputs.len() {
+                0 | 1 => outputs.front().cloned(),
+                _ => outputs.pop_front(),
+            })
+            .unwrap_or_default();
+        Ok(output)
+    }
+
+    async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        let content = fs::read(local_path)?;
+        self.files
+            .lock()
+            .unwrap()
+            .insert(remote_path.to_string(), content);
+        Ok(())
+    }
+
+    async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let content = self
+            .file(remote_path)
+            .ok_or_else(|| Error::msg(format!("No such file: {remote_path}")))?;
+        fs::write(local_path, content)?;
+        Ok(())
+    }
+
+    async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(remote_path.to_string(), content.to_vec());
+        Ok(())
+    }
+}
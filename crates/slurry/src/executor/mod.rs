@@ -0,0 +1,167 @@
+use std::{future::Future, path::Path, sync::Arc};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+/// Execute commands directly on the local machine (e.g., on a login node)
+pub mod local;
+/// In-memory executor with canned responses (e.g., for testing)
+pub mod mock;
+#[cfg(feature = "ssh")]
+/// Execute commands on a remote machine over SSH (using `Client`, `ResilientSession` or `SessionPool`)
+pub mod ssh;
+
+pub use local::LocalExecutor;
+pub use mock::MockExecutor;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Output of an executed (shell) command
+pub struct CommandOutput {
+    /// Everything the command wrote to stdout
+    pub stdout: String,
+    /// Everything the command wrote to stderr
+    pub stderr: String,
+    /// Exit code of the command (`-1` if the command was terminated without an exit code, e.g., by a signal)
+    pub exit_code: i32,
+}
+
+impl CommandOutput {
+    /// Create a successful command output (exit code `0`) with the given stdout
+    pub fn from_stdout(stdout: impl Into<String>) -> Self {
+        Self {
+            stdout: stdout.into(),
+            stderr: String::new(),
+            exit_code: 0,
+        }
+    }
+
+    /// Whether the command exited with exit code `0`
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Backend for running shell commands and transferring files
+///
+/// All slurry APIs which need to interact with a SLURM system (e.g., [`crate::data_extraction::get_squeue_res`] or [`crate::job_management::submit_job`])
+/// are generic over this trait, so they work the same way locally ([`LocalExecutor`]), over SSH (`Client`), or against a [`MockExecutor`].
+pub trait CommandExecutor: Send + Sync {
+    /// Execute a shell command (interpreted by `sh`) and return its output
+    fn execute(&self, command: &str) -> impl Future<Output = Result<CommandOutput, Error>> + Send;
+
+    /// Upload a local file to the given path on the (remote) system
+    ///
+    /// The parent directory of `remote_path` has to exist.
+    fn upload_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Download a file from the given path on the (remote) system to a local file
+    fn download_file(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// Upload a local file to the given path on the (remote) system, skipping it if the remote file is unchanged
+    ///
+    /// The parent directory of `remote_path` has to exist.
+    /// Returns whether the file was transferred (the default implementation always uploads it).
+    fn sync_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send {
+        async move {
+            self.upload_file(local_path, remote_path).await?;
+            Ok(true)
+        }
+    }
+
+    /// Write the given content to a file on the (remote) system, replacing it if it already exists
+    ///
+    /// The parent directory of `remote_path` has to exist.
+    fn write_file(
+        &self,
+        remote_path: &str,
+        content: &[u8],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl<E: CommandExecutor> CommandExecutor for Arc<E> {
+    fn execute(&self, command: &str) -> impl Future<Output = Result<CommandOutput, Error>> + Send {
+        self.as_ref().execute(command)
+    }
+
+    fn upload_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        self.as_ref().upload_file(local_path, remote_path)
+    }
+
+    fn download_file(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        self.as_ref().download_file(remote_path, local_path)
+    }
+
+    fn sync_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send {
+        self.as_ref().sync_file(local_path, remote_path)
+    }
+
+    fn write_file(
+        &self,
+        remote_path: &str,
+        content: &[u8],
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        self.as_ref().write_file(remote_path, content)
+    }
+}
+
+impl<E: CommandExecutor> CommandExecutor for &E {
+    fn execute(&self, command: &str) -> impl Future<Output = Result<CommandOutput, Error>> + Send {
+        (*self).execute(command)
+    }
+
+    fn upload_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        (*self).upload_file(local_path, remote_path)
+    }
+
+    fn download_file(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        (*self).download_file(remote_path, local_path)
+    }
+
+    fn sync_file(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> impl Future<Output = Result<bool, Error>> + Send {
+        (*self).sync_file(local_path, remote_path)
+    }
+
+    fn write_file(
+        &self,
+        remote_path: &str,
+        content: &[u8],
+    ) -> impl Future<Output = Result<(), Error>> + Send {
+        (*self).write_file(remote_path, content)
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use anyhow::Error;
+
+use crate::{
+    ssh::{ResilientSession, SessionPool, SyncOptions},
+    Client,
+};
+
+use super::{CommandExecutor, CommandOutput};
+
+impl CommandExecutor for Client {
+    async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        Client::execute(self, command).await
+    }
+
+    async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        Client::upload_file(self, local_path, remote_path).await
+    }
+
+    async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        Client::download_file(self, remote_path, local_path).await
+    }
+
+    async fn sync_file(&self, local_path: &Path, remote_path: &str) -> Result<bool, Error> {
+        Client::sync_upload(self, local_path, remote_path, &SyncOptions::default())
+            .await
+            .map(|report| !report.transferred.is_empty())
+    }
+
+    async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        Client::write_file(self, remote_path, content).await
+    }
+}
+
+impl CommandExecutor for ResilientSession {
+    async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        let res = async { self.client().await?.execute(command).await }.await;
+        self.report(command, res)
+    }
+
+    async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        let res = async {
+            self.client()
+                .await?
+                .upload_file(local_path, remote_path)
+                .await
+        }
+        .await;
+        self.report(&format!("upload {remote_path}"), res)
+    }
+
+    async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let res = async {
+            self.client()
+                .await?
+                .download_file(remote_path, local_path)
+                .await
+        }
+        .await;
+        self.report(&format!("download {remote_path}"), res)
+    }
+
+    async fn sync_file(&self, local_path: &Path, remote_path: &str) -> Result<bool, Error> {
+        ResilientSession::sync_upload(self, local_path, remote_path, &SyncOptions::default())
+            .await
+            .map(|report| !report.transferred.is_empty())
+    }
+
+    async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        let res = async { self.client().await?.write_file(remote_path, content).await }.await;
+        self.report(&format!("write {remote_path}"), res)
+    }
+}
+
+impl CommandExecutor for SessionPool {
+    async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled.session.execute(command).await;
+        drop(pooled);
+        res
+    }
+
+    async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled.session.upload_file(local_path, remote_path).await;
+        drop(pooled);
+        res
+    }
+
+    async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled.session.download_file(remote_path, local_path).await;
+        drop(pooled);
+        res
+    }
+
+    async fn sync_file(&self, local_path: &Path, remote_path: &str) -> Result<bool, Error> {
+        SessionPool::sync_upload(self, local_path, remote_path, &SyncOptions::default())
+            .await
+            .map(|report| !report.transferred.is_empty())
+    }
+
+    async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled.session.write_file(remote_path, content).await;
+        drop(pooled);
+        res
+    }
+}
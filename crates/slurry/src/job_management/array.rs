@@ -0,0 +1,193 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+/// Placeholder in job commands which is replaced by the ID of the array task (i.e., `$SLURM_ARRAY_TASK_ID`)
+pub const ARRAY_TASK_ID_PLACEHOLDER: &str = "{task_id}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// A range of array task IDs (e.g., `1-9:2`)
+pub struct ArrayRange {
+    /// First task ID
+    pub start: u32,
+    /// Last task ID (inclusive)
+    pub end: u32,
+    /// Increment between task IDs
+    pub step: u32,
+}
+
+impl ArrayRange {
+    /// Create a range from `start` to `end` (inclusive) with step `1`
+    pub fn new(start: u32, end: u32) -> Self {
+        Self {
+            start,
+            end,
+            step: 1,
+        }
+    }
+
+    /// All task IDs contained in this range
+    pub fn task_ids(self) -> impl Iterator<Item = u32> {
+        (self.start..=self.end).step_by(self.step.max(1) as usize)
+    }
+}
+
+impl Display for ArrayRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else if self.step == 1 {
+            write!(f, "{}-{}", self.start, self.end)
+        } else {
+            write!(f, "{}-{}:{}", self.start, self.end, self.step)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Specification of the tasks of a job array (i.e., the value of `sbatch --array`)
+///
+/// Can be parsed from strings like `0-15`, `1,3,5-9:2%4` or `[3-10%1]` (as shown by `squeue` for pending array tasks).
+pub struct ArraySpec {
+    /// Ranges of task IDs
+    pub ranges: Vec<ArrayRange>,
+    /// Maximum number of simultaneously running tasks (`%` suffix)
+    pub max_concurrent: Option<u32>,
+}
+
+impl ArraySpec {
+    /// Create an array specification for the task IDs `start` to `end` (inclusive)
+    pub fn range(start: u32, end: u32) -> Self {
+        Self {
+            ranges: vec![ArrayRange::new(start, end)],
+            max_concurrent: None,
+        }
+    }
+
+    /// Use the passed increment between task IDs for all ranges
+    pub fn with_step(mut self, step: u32) -> Self {
+        for range in &mut self.ranges {
+            range.step = step;
+        }
+        self
+    }
+
+    /// Limit the number of simultaneously running tasks
+    pub fn with_max_concurrent(mut self, max_concurrent: u32) -> Self {
+        self.max_concurrent = Some(max_concurrent);
+        self
+    }
+
+    /// All task IDs of this array (in the specified order)
+    pub fn task_ids(&self) -> Vec<u32> {
+        self.ranges.iter().flat_map(|r| r.task_ids()).collect()
+    }
+}
+
+impl Display for ArraySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<_> = self.ranges.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", ranges.join(","))?;
+        if let Some(max_concurrent) = self.max_concurrent {
+            write!(f, "%{max_concurrent}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ArraySpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(s);
+        let (ranges_str, max_concurrent) = match s.split_once('%') {
+            Some((ranges, limit)) => (ranges, Some(limit.parse()?)),
+            None => (s, None),
+        };
+        let ranges = ranges_str
+            .split(',')
+            .map(|r| {
+                let (range, step) = match r.split_once(':') {
+                    Some((range, step)) => (range, step.parse()?),
+                    None => (r, 1),
+                };
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (start.parse()?, end.parse()?),
+                    None => {
+                        let id = range.parse()?;
+                        (id, id)
+                    }
+                };
+                if start > end || step == 0 {
+                    return Err(Error::msg(format!("Invalid array range {r}")));
+                }
+                Ok(ArrayRange { start, end, step })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            ranges,
+            max_concurrent,
+        })
+    }
+}
+
+/// Expand a (possibly bracketed) array job ID into the IDs of the individual tasks
+///
+/// For example, `49616001_[3-5%1]` is expanded to `49616001_3`, `49616001_4`, and `49616001_5`.
+/// Job IDs without array part are returned unchanged.
+pub fn expand_array_job_id(job_id: &str) -> Result<Vec<String>, Error> {
+    match job_id.split_once('_') {
+        Some((base, tasks)) => Ok(tasks
+            .parse::<ArraySpec>()?
+            .task_ids()
+            .into_iter()
+            .map(|task| format!("{base}_{task}"))
+            .collect()),
+        None => Ok(vec![job_id.to_string()]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::job_management::{expand_array_job_id, ArrayRange, ArraySpec};
+
+    #[test]
+    fn test_parse_array_spec() {
+        let spec: ArraySpec = "[3-10%1]".parse().unwrap();
+        assert_eq!(spec.ranges, vec![ArrayRange::new(3, 10)]);
+        assert_eq!(spec.max_concurrent, Some(1));
+        assert_eq!(spec.to_string(), "3-10%1");
+
+        let spec: ArraySpec = "1,3,5-9:2".parse().unwrap();
+        assert_eq!(spec.task_ids(), vec![1, 3, 5, 7, 9]);
+        assert_eq!(spec.to_string(), "1,3,5-9:2");
+
+        assert!("5-3".parse::<ArraySpec>().is_err());
+        assert!("1-4:0".parse::<ArraySpec>().is_err());
+        assert_eq!(
+            ArraySpec::range(0, 6)
+                .with_step(3)
+                .with_max_concurrent(2)
+                .to_string(),
+            "0-6:3%2"
+        );
+    }
+
+    #[test]
+    fn test_expand_array_job_id() {
+        assert_eq!(
+            expand_array_job_id("49616001_[3-5%1]").unwrap(),
+            vec!["49616001_3", "49616001_4", "49616001_5"]
+        );
+        assert_eq!(
+            expand_array_job_id("49869434_2").unwrap(),
+            vec!["49869434_2"]
+        );
+        assert_eq!(expand_array_job_id("49848561").unwrap(), vec!["49848561"]);
+    }
+}
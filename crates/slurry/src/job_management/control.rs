@@ -0,0 +1,273 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::executor::{CommandExecutor, CommandOutput};
+
+#[derive(Debug)]
+/// Error when controlling (e.g., cancelling or holding) SLURM jobs
+pub enum JobControlError {
+    /// At least one of the job IDs is unknown to SLURM (or malformed)
+    InvalidJobId(String),
+    /// The user is not allowed to perform this operation on (at least one of) the jobs
+    PermissionDenied(String),
+    /// The signal to send is malformed (e.g., contains characters other than letters and digits)
+    InvalidSignal(String),
+    /// The SLURM command failed for another reason
+    CommandFailed {
+        /// Exit code of the SLURM command
+        exit_code: i32,
+        /// Error message (stderr) of the SLURM command
+        message: String,
+    },
+    /// The command could not be executed (e.g., because the SSH connection failed)
+    Execution(anyhow::Error),
+}
+
+impl Display for JobControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobControlError::InvalidJobId(msg) => write!(f, "Invalid job ID: {msg}"),
+            JobControlError::PermissionDenied(msg) => write!(f, "Permission denied: {msg}"),
+            JobControlError::InvalidSignal(signal) => write!(f, "Invalid signal: {signal}"),
+            JobControlError::CommandFailed { exit_code, message } => {
+                write!(f, "Command failed with exit code {exit_code}: {message}")
+            }
+            JobControlError::Execution(e) => write!(f, "Could not execute command: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JobControlError {}
+
+impl From<anyhow::Error> for JobControlError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Execution(e)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+/// Options for cancelling (or signalling) SLURM jobs using `scancel`
+pub struct CancelOptions {
+    /// Signal to send instead of cancelling the job (`--signal`), e.g., `USR1` or `SIGTERM`
+    pub signal: Option<String>,
+    /// Only signal the batch step (i.e., the batch shell), but not its child processes (`--batch`)
+    pub batch: bool,
+    /// Signal all steps of the job, including the batch step (`--full`)
+    pub full: bool,
+}
+
+impl CancelOptions {
+    /// Send the passed signal instead of cancelling the job
+    pub fn with_signal(mut self, signal: impl Into<String>) -> Self {
+        self.signal = Some(signal.into());
+        self
+    }
+
+    /// Only signal the batch step
+    pub fn with_batch(mut self, batch: bool) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Signal all steps of the job, including the batch step
+    pub fn with_full(mut self, full: bool) -> Self {
+        self.full = full;
+        self
+    }
+}
+
+// Job IDs may refer to array tasks, e.g., 4242, 4242_7, or 4242_[1-5,8]
+fn quote_job_ids<S: AsRef<str>>(job_ids: &[S]) -> Result<Vec<String>, JobControlError> {
+    if job_ids.is_empty() {
+        return Err(JobControlError::InvalidJobId(String::from(
+            "No job IDs specified",
+        )));
+    }
+    job_ids
+        .iter()
+        .map(|id| {
+            let id = id.as_ref();
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "_[]-,%+".contains(c))
+            {
+                Err(JobControlError::InvalidJobId(id.to_string()))
+            } else {
+                Ok(format!("'{id}'"))
+            }
+        })
+        .collect()
+}
+
+fn check_output(out: CommandOutput) -> Result<(), JobControlError> {
+    let message = out.stderr.trim().to_string();
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("invalid job id") {
+        Err(JobControlError::InvalidJobId(message))
+    } else if lowercase.contains("permission denied") || lowercase.contains("not authorized") {
+        Err(JobControlError::PermissionDenied(message))
+    } else if !out.success() || lowercase.contains("error") {
+        Err(JobControlError::CommandFailed {
+            exit_code: out.exit_code,
+            message,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Cancel (or signal) the SLURM jobs with the given IDs using `scancel`
+///
+/// Job IDs can also refer to tasks of job arrays (e.g., `4242_7` or `4242_[1-5]`).
+pub async fn cancel_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+    options: &CancelOptions,
+) -> Result<(), JobControlError> {
+    let mut args = Vec::new();
+    if let Some(signal) = &options.signal {
+        if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(JobControlError::InvalidSignal(signal.clone()));
+        }
+        args.push(format!("--signal={signal}"));
+    }
+    if options.batch {
+        args.push(String::from("--batch"));
+    }
+    if options.full {
+        args.push(String::from("--full"));
+    }
+    args.extend(quote_job_ids(job_ids)?);
+    check_output(
+        executor
+            .execute(&format!("scancel {}", args.join(" ")))
+            .await?,
+    )
+}
+
+async fn scontrol_job_cmd<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    cmd: &str,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    let job_ids = quote_job_ids(job_ids)?;
+    check_output(
+        executor
+            .execute(&format!("scontrol {cmd} {}", job_ids.join(",")))
+            .await?,
+    )
+}
+
+/// Hold the pending SLURM jobs with the given IDs (i.e., prevent them from starting) using `scontrol hold`
+pub async fn hold_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    scontrol_job_cmd(executor, "hold", job_ids).await
+}
+
+/// Release the previously held SLURM jobs with the given IDs using `scontrol release`
+pub async fn release_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    scontrol_job_cmd(executor, "release", job_ids).await
+}
+
+/// Requeue the SLURM jobs with the given IDs using `scontrol requeue`
+pub async fn requeue_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    scontrol_job_cmd(executor, "requeue", job_ids).await
+}
+
+/// Suspend the running SLURM jobs with the given IDs using `scontrol suspend`
+///
+/// Usually, this requires administrator privileges.
+pub async fn suspend_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    scontrol_job_cmd(executor, "suspend", job_ids).await
+}
+
+/// Resume the previously suspended SLURM jobs with the given IDs using `scontrol resume`
+///
+/// Usually, this requires administrator privileges.
+pub async fn resume_job<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    job_ids: &[S],
+) -> Result<(), JobControlError> {
+    scontrol_job_cmd(executor, "resume", job_ids).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::{CommandOutput, MockExecutor},
+        job_management::{cancel_job, hold_job, CancelOptions, JobControlError},
+    };
+
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let executor = MockExecutor::new();
+        cancel_job(
+            &executor,
+            &["4242", "4243_[1-5]"],
+            &CancelOptions::default().with_signal("USR1").with_full(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            executor.executed_commands(),
+            vec!["scancel --signal=USR1 --full '4242' '4243_[1-5]'"]
+        );
+        assert!(matches!(
+            cancel_job(&executor, &["4242; rm -rf ~"], &CancelOptions::default()).await,
+            Err(JobControlError::InvalidJobId(_))
+        ));
+        assert!(matches!(
+            cancel_job(
+                &executor,
+                &["4242"],
+                &CancelOptions::default().with_signal("USR1; rm -rf ~")
+            )
+            .await,
+            Err(JobControlError::InvalidSignal(_))
+        ));
+        assert_eq!(executor.executed_commands().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_control_errors() {
+        let executor = MockExecutor::new()
+            .with_response(
+                "hold '1'",
+                CommandOutput {
+                    stdout: String::new(),
+                    stderr: String::from("Invalid job id specified for job 1"),
+                    exit_code: 1,
+                },
+            )
+            .with_response(
+                "hold '2'",
+                CommandOutput {
+                    stdout: String::new(),
+                    stderr: String::from("Access/permission denied for job 2"),
+                    exit_code: 1,
+                },
+            );
+        assert!(matches!(
+            hold_job(&executor, &["1"]).await,
+            Err(JobControlError::InvalidJobId(_))
+        ));
+        assert!(matches!(
+            hold_job(&executor, &["2"]).await,
+            Err(JobControlError::PermissionDenied(_))
+        ));
+        assert!(hold_job(&executor, &["3"]).await.is_ok());
+    }
+}
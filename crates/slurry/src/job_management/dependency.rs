@@ -0,0 +1,174 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Type of a SLURM job dependency
+pub enum DependencyType {
+    /// Start after the jobs started (or were cancelled)
+    AFTER,
+    /// Start after the jobs terminated (in any state)
+    AFTERANY,
+    /// Start after the jobs completed successfully (exit code `0`)
+    AFTEROK,
+    /// Start after the jobs failed (e.g., non-zero exit code or timeout)
+    AFTERNOTOK,
+    /// Start after the corresponding tasks of the job arrays completed successfully
+    AFTERCORR,
+    /// Start after all previously started jobs with the same name and user terminated
+    SINGLETON,
+}
+
+impl Display for DependencyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format!("{self:?}").to_lowercase())
+    }
+}
+
+impl FromStr for DependencyType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "after" => Self::AFTER,
+            "afterany" => Self::AFTERANY,
+            "afterok" => Self::AFTEROK,
+            "afternotok" => Self::AFTERNOTOK,
+            "aftercorr" => Self::AFTERCORR,
+            "singleton" => Self::SINGLETON,
+            _ => return Err(Error::msg(format!("Unknown dependency type {s}"))),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// A single dependency condition (e.g., `afterok:4242:4243`)
+pub struct DependencyCondition {
+    /// Type of the dependency
+    pub kind: DependencyType,
+    /// IDs of the jobs this condition refers to (empty for [`DependencyType::SINGLETON`])
+    pub job_ids: Vec<String>,
+    /// Status of the condition as reported by `squeue` (e.g., `unfulfilled` or `failed`)
+    pub status: Option<String>,
+}
+
+impl DependencyCondition {
+    /// Create a new dependency condition of the given type on the given jobs
+    pub fn new<S: Into<String>>(
+        kind: DependencyType,
+        job_ids: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self {
+            kind,
+            job_ids: job_ids.into_iter().map(Into::into).collect(),
+            status: None,
+        }
+    }
+}
+
+impl Display for DependencyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for job_id in &self.job_ids {
+            write!(f, ":{job_id}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Dependencies of a SLURM job (i.e., the value of `sbatch --dependency` or the `DEPENDENCY` column of `squeue`)
+pub struct JobDependency {
+    /// All dependency conditions
+    pub conditions: Vec<DependencyCondition>,
+    /// Whether satisfying any of the conditions is sufficient (separated by `?`), instead of all of them (separated by `,`)
+    pub any: bool,
+}
+
+impl JobDependency {
+    /// Dependency on all of the given conditions
+    pub fn all(conditions: Vec<DependencyCondition>) -> Self {
+        Self {
+            conditions,
+            any: false,
+        }
+    }
+
+    /// Dependency on any of the given conditions
+    pub fn any(conditions: Vec<DependencyCondition>) -> Self {
+        Self {
+            conditions,
+            any: true,
+        }
+    }
+
+    /// Only start the job after all previously started jobs with the same name and user terminated
+    pub fn singleton() -> Self {
+        Self::all(vec![DependencyCondition::new::<String>(
+            DependencyType::SINGLETON,
+            [],
+        )])
+    }
+}
+
+impl Display for JobDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let conditions: Vec<_> = self.conditions.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", conditions.join(if self.any { "?" } else { "," }))
+    }
+}
+
+impl FromStr for JobDependency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let any = s.contains('?');
+        let conditions = s
+            .split([',', '?'])
+            .filter(|c| !c.is_empty())
+            .map(|c| {
+                // squeue appends the status, e.g., afterok:4242(unfulfilled)
+                let (c, status) = match c.split_once('(') {
+                    Some((c, status)) => (c, Some(status.trim_end_matches(')').to_string())),
+                    None => (c, None),
+                };
+                let mut parts = c.split(':');
+                let kind: DependencyType = parts.next().unwrap_or_default().parse()?;
+                Ok(DependencyCondition {
+                    kind,
+                    job_ids: parts.map(String::from).collect(),
+                    status,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self { conditions, any })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::job_management::{DependencyCondition, DependencyType, JobDependency};
+
+    #[test]
+    fn test_parse_dependency() {
+        let dep: JobDependency = "afterok:4242_*(unfulfilled),singleton".parse().unwrap();
+        assert!(!dep.any);
+        assert_eq!(
+            dep.conditions[0],
+            DependencyCondition {
+                kind: DependencyType::AFTEROK,
+                job_ids: vec!["4242_*".to_string()],
+                status: Some("unfulfilled".to_string()),
+            }
+        );
+        assert_eq!(dep.conditions[1].kind, DependencyType::SINGLETON);
+        assert_eq!(dep.to_string(), "afterok:4242_*,singleton");
+
+        let dep: JobDependency = "afterany:1:2?afternotok:3".parse().unwrap();
+        assert!(dep.any);
+        assert_eq!(dep.conditions[0].job_ids, vec!["1", "2"]);
+        assert!("afterwards:1".parse::<JobDependency>().is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Error;
+
+use crate::{
+    data_extraction::{expand_hostlist, get_squeue_res, SqueueMode},
+    executor::CommandExecutor,
+    misc::port_forwarding::PortForward,
+    Client, JobState,
+};
+
+/// How often to check whether a job has started (see [`forward_to_job`])
+pub const JOB_START_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wait until the given job is running and return the (first) node it runs on
+///
+/// Fails if the job ended (or is not known to SLURM) before it was running.
+pub async fn wait_for_job_node<E: CommandExecutor>(
+    executor: &E,
+    job_id: &str,
+    poll_interval: Duration,
+) -> Result<String, Error> {
+    loop {
+        let (_time, rows) =
+            get_squeue_res(&SqueueMode::JOBIDS(vec![job_id.to_string()]), executor).await?;
+        let Some(job) = rows.first() else {
+            return Err(Error::msg(format!("Job {job_id} was not found.")));
+        };
+        match &job.state {
+            JobState::PENDING => {}
+            // Nodes of the job are booting
+            JobState::OTHER(state) if state == "CONFIGURING" => {}
+            JobState::RUNNING => {
+                let node = job.exec_host.clone().or_else(|| {
+                    job.node_list
+                        .as_deref()
+                        .and_then(|nodes| expand_hostlist(nodes).into_iter().next())
+                });
+                // The node might not be reported immediately after the job started
+                if let Some(node) = node {
+                    return Ok(node);
+                }
+            }
+            state => {
+                return Err(Error::msg(format!(
+                    "Job {job_id} is not running (state {state})."
+                )))
+            }
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Forward a local port to a port on the compute node of a job (e.g., of a Jupyter server running inside the job)
+///
+/// Waits until the job is running, then forwards connections to `127.0.0.1:{local_port}` to `{node}:{remote_port}` through the SSH server (i.e., the login node).
+/// The compute node only has to be reachable from the login node, the job itself does not need to open any tunnel.
+/// The forwarding stops once the returned [`PortForward`] is dropped.
+pub async fn forward_to_job(
+    client: &Client,
+    job_id: &str,
+    remote_port: u16,
+    local_port: u16,
+) -> Result<PortForward, Error> {
+    let node = wait_for_job_node(client, job_id, JOB_START_POLL_INTERVAL).await?;
+    PortForward::local(
+        client,
+        SocketAddr::from((Ipv4Addr::LOCALHOST, local_port)),
+        node,
+        remote_port,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{executor::MockExecutor, job_management::wait_for_job_node};
+
+    #[tokio::test]
+    async fn test_wait_for_job_node() {
+        let executor = MockExecutor::new().with_stdout(
+            "squeue",
+            "acc|123|n042|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|notebook|4G|0:10|0.5|gpu|RUNNING|None|2025-01-14T10:00:00|2025-01-14T09:59:00|/home/user|./jupyter.sh\n",
+        );
+        let node = wait_for_job_node(&executor, "123", Duration::from_millis(1))
+            .await
+            .unwrap();
+        assert_eq!(node, "n042");
+
+        let executor = MockExecutor::new().with_stdout(
+            "squeue",
+            "acc|123|n/a|1|2|1|N/A|(null)|(null)|123|grp|123|1-00:00:00|1-00:00:00|notebook|4G|0:00|0.5|gpu|CANCELLED|None|N/A|2025-01-14T09:59:00|/home/user|./jupyter.sh\n",
+        );
+        assert!(
+            wait_for_job_node(&executor, "123", Duration::from_millis(1))
+                .await
+                .is_err()
+        );
+    }
+}
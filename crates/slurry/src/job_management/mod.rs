@@ -1,35 +1,68 @@
-use std::{collections::HashSet, path::PathBuf, sync::Arc, time::SystemTime};
+use std::{path::PathBuf, time::SystemTime};
 
 use anyhow::{Error, Ok};
-use async_ssh2_tokio::Client;
 use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
-use tokio::task::JoinSet;
 
-use crate::JobState;
+use crate::{executor::CommandExecutor, JobState};
+
+/// Module for controlling submitted SLURM jobs (e.g., cancelling, holding or requeuing them)
+pub mod control;
+
+pub use control::{
+    cancel_job, hold_job, release_job, requeue_job, resume_job, suspend_job, CancelOptions,
+    JobControlError,
+};
+
+/// Module for configuring SLURM jobs (e.g., requested resources or mail notifications)
+pub mod options;
+
+pub use options::{JobMail, JobMemory, JobOptions, MailType};
+
+/// Module for rendering SLURM batch scripts
+pub mod script;
+
+pub use script::{shell_quote, JobScript};
+
+/// Module for job arrays (e.g., specifying and expanding array task IDs)
+pub mod array;
+
+pub use array::{expand_array_job_id, ArrayRange, ArraySpec, ARRAY_TASK_ID_PLACEHOLDER};
+
+/// Module for dependencies between SLURM jobs (e.g., `afterok`)
+pub mod dependency;
+
+pub use dependency::{DependencyCondition, DependencyType, JobDependency};
+
+/// Module for submitting workflows of dependent SLURM jobs
+pub mod workflow;
+
+pub use workflow::{SubmittedJob, Workflow, WorkflowJob, WorkflowSubmitError};
+
+/// Module for retrieving the outputs of SLURM jobs (e.g., result files or `stdout.txt`)
+pub mod outputs;
+
+pub use outputs::download_job_outputs;
+#[cfg(feature = "tokio")]
+pub use outputs::follow_job_output;
+
+#[cfg(feature = "ssh")]
+/// Module for accessing services inside running jobs (e.g., forwarding ports of their compute nodes)
+pub mod forwarding;
+
+#[cfg(feature = "ssh")]
+pub use forwarding::{forward_to_job, wait_for_job_node, JOB_START_POLL_INTERVAL};
 
 type JobID = String;
 type FolderID = String;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-/// Options for creating new SLURM jobs
-pub struct JobOptions {
-    /// The root directory (i.e., where the job should be started)
-    pub root_dir: String,
-    /// Files to upload before starting the job (e.g., the binary that should be started or required data files)
-    pub files_to_upload: HashSet<JobFilesToUpload>,
-    /// How many CPUs to request per task (`--cpus-per-task`)
-    pub num_cpus: usize,
-    /// How long the job should be executed (`--time`)
-    pub time: String,
-    /// The bash command to execute
-    pub command: String,
-    /// Port forwarding configuartion, if local port on HPC node executing the job should be forwarded
-    pub local_forwarding: Option<JobLocalForwarding>,
-}
-
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 /// Files to upload before starting a SLURM job
+///
+/// Files passed to [`JobOptions::with_file_to_upload`] are uploaded into the (new) job folder on every submission.
+/// Files passed to [`JobOptions::with_file_to_sync`] are synced into the root directory instead,
+/// skipping unchanged files (see [`CommandExecutor::sync_file`]).
 pub struct JobFilesToUpload {
     /// Local path to file
     pub local_path: PathBuf,
@@ -45,6 +78,9 @@ pub struct JobFilesToUpload {
 /// Can be used to forward a port of the executing HPC cluster node to the user's local machine.
 ///
 /// Forwarding is done over a relay node directly accessible over SSH (e.g., the login node of the SLURM system)
+///
+/// This requires the job to open a reverse SSH tunnel (i.e., passwordless SSH from the compute node to the relay).
+/// Forwarding directly to the compute node through the SSH connection (`forward_to_job` of the `ssh` feature) does not have this requirement.
 pub struct JobLocalForwarding {
     /// The port where the forwarding should be available locally
     pub local_port: u16,
@@ -53,106 +89,82 @@ pub struct JobLocalForwarding {
     /// The address of the relay (e.g., hostname)
     pub relay_addr: String,
 }
-/// Submit a job to SLURM over SSH
-pub async fn submit_job(
-    client: Arc<Client>,
+/// Submit a job to SLURM using the given [`CommandExecutor`] (e.g., over SSH or locally)
+pub async fn submit_job<E: CommandExecutor>(
+    executor: &E,
     job_options: JobOptions,
 ) -> Result<(FolderID, JobID), Error> {
+    job_options.validate()?;
     // Create job folder
     let folder_id = DateTime::<Utc>::from(SystemTime::now()).to_rfc3339();
-    client
-        .execute(&format!(
-            "mkdir -p '{}/{}'",
-            job_options.root_dir, folder_id
-        ))
+    let job_dir = format!("{}/{}", job_options.root_dir, folder_id);
+    executor
+        .execute(&format!("mkdir -p {}", shell_quote(&job_dir)))
         .await?;
 
-    let mut set = JoinSet::new();
-    let root_dir = job_options.root_dir.clone();
+    // Sync shared files (skipping unchanged ones)
+    try_join_all(job_options.files_to_sync.iter().map(|file_to_sync| async {
+        let remote_dir = format!("{}/{}", job_options.root_dir, file_to_sync.remote_subpath);
+        executor
+            .execute(&format!("mkdir -p {}", shell_quote(&remote_dir)))
+            .await?;
+        executor
+            .sync_file(
+                &file_to_sync.local_path,
+                &format!("{}/{}", remote_dir, file_to_sync.remote_file_name),
+            )
+            .await
+            .map_err(|e| e.context(format!("Could not sync {}", file_to_sync.remote_file_name)))
+    }))
+    .await?;
 
     // Upload all files
-    job_options
-        .files_to_upload
-        .into_iter()
-        .for_each(|file_to_upload| {
-            let root_dir = root_dir.clone();
-            let client_arc = Arc::clone(&client);
-            let file_to_upload = file_to_upload.clone();
-            let folder_id = folder_id.clone();
-            set.spawn(async move {
-                client_arc
-                    .execute(&format!(
-                        "mkdir -p '{}/{}/{}'",
-                        root_dir, folder_id, file_to_upload.remote_subpath
-                    ))
+    try_join_all(
+        job_options
+            .files_to_upload
+            .iter()
+            .map(|file_to_upload| async {
+                let remote_dir = format!("{}/{}", job_dir, file_to_upload.remote_subpath);
+                executor
+                    .execute(&format!("mkdir -p {}", shell_quote(&remote_dir)))
                     .await
-                    .unwrap_or_else(|_| {
-                        panic!(
+                    .map_err(|e| {
+                        e.context(format!(
                             "Could not create directory for file {}",
                             file_to_upload.remote_subpath
-                        )
-                    });
-                client_arc
+                        ))
+                    })?;
+                executor
                     .upload_file(
                         &file_to_upload.local_path,
-                        format!(
-                            "{}/{}/{}/{}",
-                            root_dir,
-                            folder_id,
-                            file_to_upload.remote_subpath,
-                            file_to_upload.remote_file_name
-                        ),
+                        &format!("{}/{}", remote_dir, file_to_upload.remote_file_name),
                     )
                     .await
-            });
-        });
-    set.join_all()
+            }),
+    )
+    .await?;
+
+    // Create job script locally and upload it
+    let script = JobScript::from_options(&job_options, &folder_id);
+    executor
+        .write_file(&format!("{job_dir}/start.sh"), script.render().as_bytes())
         .await
-        .into_iter()
-        .collect::<Result<(), _>>()?;
-
-    // Create Job Script
-
-    // Add local port forwarding (if necessary)
-    let forwaring_str = match job_options.local_forwarding {
-        Some(forwarding_options) => format!(
-            "ssh -N -f -R {}:localhost:{} {}",
-            forwarding_options.relay_port,
-            forwarding_options.local_port,
-            forwarding_options.relay_addr
-        ),
-        None => String::default(),
-    };
-    // Create script on system
-    client
-        .execute(&format!(
-            "cd {}/{} &&
-    echo '#!/usr/bin/zsh
-### Job Parameters
-#SBATCH --ntasks=1
-#SBATCH --cpus-per-task={}
-#SBATCH --time={}
-#SBATCH --job-name={}  # Sets the job name
-#SBATCH --output=stdout.txt     # redirects stdout and stderr to stdout.txt
-
-### Program Code
-{}
-{}' > start.sh && chmod +x start.sh",
-            root_dir,
-            folder_id,
-            job_options.num_cpus,
-            job_options.time,
-            folder_id,
-            forwaring_str,
-            job_options.command
-        ))
-        .await?;
+        .map_err(|e| e.context("Could not upload job script"))?;
 
     // Schedule job & get job id
-    let sbatch_out = client
-        .execute(&format!("cd {root_dir}/{folder_id} && sbatch start.sh"))
+    let sbatch_out = executor
+        .execute(&format!(
+            "cd {} && chmod +x start.sh && sbatch start.sh",
+            shell_quote(&job_dir)
+        ))
         .await?;
-    let job_id = sbatch_out.stdout.split(" ").last();
+    if !sbatch_out.success() {
+        return Err(Error::msg(format!(
+            "sbatch failed: {}",
+            sbatch_out.stderr.trim()
+        )));
+    }
+    let job_id = sbatch_out.stdout.split_whitespace().last();
     if let Some(job_id) = job_id {
         Ok((folder_id.clone(), job_id.to_string()))
     } else {
@@ -185,13 +197,21 @@ pub enum JobStatus {
     NotFound,
 }
 
-/// Get the status of a SLURM job, given its ID and a SSH client
-pub async fn get_job_status(client: &Client, job_id: &str) -> Result<JobStatus, Error> {
-    let (_time, res) = crate::data_extraction::get_squeue_res_ssh(
-        client,
+/// Get the status of a SLURM job, given its ID and a [`CommandExecutor`] (e.g., a SSH client)
+pub async fn get_job_status<E: CommandExecutor>(
+    executor: &E,
+    job_id: &str,
+) -> Result<JobStatus, Error> {
+    let res = crate::data_extraction::get_squeue_res(
         &crate::data_extraction::SqueueMode::JOBIDS(vec![job_id.to_string()]),
+        executor,
     )
-    .await?;
+    .await;
+    let res = match res {
+        // squeue fails for IDs of jobs which are no longer known to the controller
+        Err(e) if e.to_string().to_lowercase().contains("invalid job id") => Vec::new(),
+        res => res?.1,
+    };
     if res.is_empty() {
         return Ok(JobStatus::NotFound);
         // return Err(Error::msg("Could not find job."))
@@ -208,3 +228,84 @@ pub async fn get_job_status(client: &Client, job_id: &str) -> Result<JobStatus,
         c => JobStatus::ENDED { state: c.clone() },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::MockExecutor,
+        job_management::{submit_job, JobFilesToUpload, JobMemory, JobOptions, MailType},
+    };
+
+    #[tokio::test]
+    async fn test_submit_job_mock() {
+        let executor = MockExecutor::new().with_stdout("sbatch", "Submitted batch job 4242");
+        let (folder_id, job_id) = submit_job(
+            &executor,
+            JobOptions::new("experiments", "./run.sh")
+                .with_num_cpus(4)
+                .with_partition("c23ms")
+                .with_memory(JobMemory::PerCpu("2G".to_string()))
+                .with_mail("user@example.com", vec![MailType::END, MailType::FAIL])
+                .with_env("OMP_NUM_THREADS", "4"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(job_id, "4242");
+        let commands = executor.executed_commands();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].contains(&folder_id));
+        let script = String::from_utf8(
+            executor
+                .file(&format!("experiments/{folder_id}/start.sh"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(script.contains("#SBATCH --cpus-per-task=4"));
+        assert!(script.contains("#SBATCH --partition=c23ms"));
+        assert!(script.contains("#SBATCH --mem-per-cpu=2G"));
+        assert!(script.contains("#SBATCH --mail-type=END,FAIL"));
+        assert!(script.contains("#SBATCH --export=ALL,OMP_NUM_THREADS=4"));
+        assert!(script.ends_with("./run.sh\n"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_job_synced_files() {
+        let path = std::env::temp_dir().join("slurry_test_submit_job_synced_files");
+        std::fs::write(&path, "binary").unwrap();
+        let executor = MockExecutor::new().with_stdout("sbatch", "Submitted batch job 4242");
+        let (folder_id, _) = submit_job(
+            &executor,
+            JobOptions::new("experiments", "../bin/app").with_file_to_sync(JobFilesToUpload {
+                local_path: path.clone(),
+                remote_subpath: "bin".to_string(),
+                remote_file_name: "app".to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+        // Synced files are shared by all jobs, so they are not saved in the job folder
+        assert_eq!(executor.file("experiments/bin/app").unwrap(), b"binary");
+        assert!(executor
+            .file(&format!("experiments/{folder_id}/bin/app"))
+            .is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_job_options_validation() {
+        let options = JobOptions::new("experiments", "./run.sh");
+        assert!(options.validate().is_ok());
+        assert!(options.clone().with_time("1-12:00").validate().is_ok());
+        assert!(options.clone().with_time("ten minutes").validate().is_err());
+        assert!(options
+            .clone()
+            .with_memory(JobMemory::PerNode("lots".to_string()))
+            .validate()
+            .is_err());
+        assert!(options.clone().with_env("1VAR", "x").validate().is_err());
+        assert!(options
+            .with_partition("c23ms\n#SBATCH --exclusive")
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use std::{collections::HashSet, fmt::Display};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{parse_slurm_duration, parse_slurm_memory};
+
+use super::{ArraySpec, JobDependency, JobFilesToUpload, JobLocalForwarding};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Options for creating new SLURM jobs
+///
+/// Can be created using [`JobOptions::new`] and configured using the builder-style `with_*` methods.
+pub struct JobOptions {
+    /// The root directory (i.e., where the job should be started)
+    pub root_dir: String,
+    /// Files to upload before starting the job (e.g., the binary that should be started or required data files)
+    pub files_to_upload: HashSet<JobFilesToUpload>,
+    /// Files to sync into `root_dir` before starting the job (shared by all jobs, unchanged files are not uploaded again)
+    ///
+    /// Useful for large files needed by many jobs (e.g., binaries or datasets).
+    /// The `remote_subpath` is relative to `root_dir` instead of the job folder.
+    pub files_to_sync: HashSet<JobFilesToUpload>,
+    /// How many CPUs to request per task (`--cpus-per-task`)
+    pub num_cpus: usize,
+    /// How long the job should be executed (`--time`)
+    ///
+    /// Accepted formats are, e.g., `minutes`, `hours:minutes:seconds`, or `days-hours:minutes:seconds`
+    pub time: String,
+    /// The bash command to execute
+    pub command: String,
+    /// Port forwarding configuartion, if local port on HPC node executing the job should be forwarded
+    pub local_forwarding: Option<JobLocalForwarding>,
+    /// The shell used to execute the job script (e.g., `/bin/bash`)
+    pub shell: String,
+    /// The name of the job (`--job-name`), defaults to the ID of the job folder
+    pub job_name: Option<String>,
+    /// How many tasks to start (`--ntasks`)
+    pub ntasks: usize,
+    /// How many nodes to request (`--nodes`), e.g., `2` or `1-4`
+    pub nodes: Option<String>,
+    /// The partition to submit the job to (`--partition`)
+    pub partition: Option<String>,
+    /// The account to charge the job to (`--account`)
+    pub account: Option<String>,
+    /// The quality of service of the job (`--qos`)
+    pub qos: Option<String>,
+    /// How much memory to request (`--mem` or `--mem-per-cpu`)
+    pub memory: Option<JobMemory>,
+    /// GPUs to request (`--gpus`), e.g., `2` or `a100:2`
+    pub gpus: Option<String>,
+    /// Generic resources to request (`--gres`), e.g., `gpu:a100:2`
+    pub gres: Option<String>,
+    /// Node features required for the job (`--constraint`), e.g., `skylake&ib`
+    pub constraint: Option<String>,
+    /// Whether nodes should not be shared with other jobs (`--exclusive`)
+    pub exclusive: bool,
+    /// Mail notification settings (`--mail-user` and `--mail-type`)
+    pub mail: Option<JobMail>,
+    /// File to write stdout to (`--output`), relative to the job folder
+    ///
+    /// For job arrays, `%a` is replaced by the task ID (e.g., `stdout_%a.txt`).
+    pub output: String,
+    /// File to write stderr to (`--error`), relative to the job folder
+    ///
+    /// If not set, stderr is written to the same file as stdout.
+    pub error: Option<String>,
+    /// Environment variables to export to the job (`--export=ALL,...`)
+    pub env: Vec<(String, String)>,
+    /// Earliest time the job may start (`--begin`), e.g., `now+1hour` or `2025-01-14T16:00:00`
+    pub begin: Option<String>,
+    /// Additional `#SBATCH` directives (e.g., `--signal=USR1@60`)
+    pub extra_directives: Vec<String>,
+    /// Submit the job as job array with the specified tasks (`--array`)
+    ///
+    /// The placeholder [`super::ARRAY_TASK_ID_PLACEHOLDER`] in the command is replaced by the ID of the task.
+    pub array: Option<ArraySpec>,
+    /// Dependencies on other jobs (`--dependency`)
+    pub dependency: Option<JobDependency>,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        Self {
+            root_dir: String::new(),
+            files_to_upload: HashSet::new(),
+            files_to_sync: HashSet::new(),
+            num_cpus: 1,
+            time: String::from("0-00:10:00"),
+            command: String::new(),
+            local_forwarding: None,
+            shell: String::from("/usr/bin/zsh"),
+            job_name: None,
+            ntasks: 1,
+            nodes: None,
+            partition: None,
+            account: None,
+            qos: None,
+            memory: None,
+            gpus: None,
+            gres: None,
+            constraint: None,
+            exclusive: false,
+            mail: None,
+            output: String::from("stdout.txt"),
+            error: None,
+            env: Vec::new(),
+            begin: None,
+            extra_directives: Vec::new(),
+            array: None,
+            dependency: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Memory to request for a SLURM job (e.g., `4G` or `500M`)
+pub enum JobMemory {
+    /// Memory per node (`--mem`)
+    PerNode(String),
+    /// Memory per allocated CPU (`--mem-per-cpu`)
+    PerCpu(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Mail notification settings for a SLURM job
+pub struct JobMail {
+    /// Mail address to notify (`--mail-user`)
+    pub user: String,
+    /// Events to notify about (`--mail-type`)
+    pub types: Vec<MailType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Event types for mail notifications (`--mail-type`)
+pub enum MailType {
+    /// No notifications
+    NONE,
+    /// Job started
+    BEGIN,
+    /// Job ended
+    END,
+    /// Job failed
+    FAIL,
+    /// Job was requeued
+    REQUEUE,
+    /// Equivalent to `BEGIN`, `END`, `FAIL`, `INVALID_DEPEND`, `REQUEUE`, and `STAGE_OUT`
+    ALL,
+    /// Job reached its time limit
+    #[allow(non_camel_case_types)]
+    TIME_LIMIT,
+    /// Job reached 90 percent of its time limit
+    #[allow(non_camel_case_types)]
+    TIME_LIMIT_90,
+    /// Job reached 80 percent of its time limit
+    #[allow(non_camel_case_types)]
+    TIME_LIMIT_80,
+    /// Job reached 50 percent of its time limit
+    #[allow(non_camel_case_types)]
+    TIME_LIMIT_50,
+    /// Notifications for each array task (instead of the array as a whole)
+    #[allow(non_camel_case_types)]
+    ARRAY_TASKS,
+}
+
+impl Display for MailType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl JobOptions {
+    /// Create new job options executing `command` in a new job folder inside `root_dir`
+    pub fn new(root_dir: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            root_dir: root_dir.into(),
+            command: command.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Upload the passed file before starting the job
+    pub fn with_file_to_upload(mut self, file: JobFilesToUpload) -> Self {
+        self.files_to_upload.insert(file);
+        self
+    }
+
+    /// Sync the passed file into the root directory before starting the job, skipping it if unchanged
+    pub fn with_file_to_sync(mut self, file: JobFilesToUpload) -> Self {
+        self.files_to_sync.insert(file);
+        self
+    }
+
+    /// Request the passed number of CPUs per task
+    pub fn with_num_cpus(mut self, num_cpus: usize) -> Self {
+        self.num_cpus = num_cpus;
+        self
+    }
+
+    /// Set the time limit of the job (e.g., `0-01:00:00`)
+    pub fn with_time(mut self, time: impl Into<String>) -> Self {
+        self.time = time.into();
+        self
+    }
+
+    /// Forward a port of the node executing the job
+    pub fn with_local_forwarding(mut self, local_forwarding: JobLocalForwarding) -> Self {
+        self.local_forwarding = Some(local_forwarding);
+        self
+    }
+
+    /// Use the passed shell for executing the job script
+    pub fn with_shell(mut self, shell: impl Into<String>) -> Self {
+        self.shell = shell.into();
+        self
+    }
+
+    /// Set the name of the job
+    pub fn with_job_name(mut self, job_name: impl Into<String>) -> Self {
+        self.job_name = Some(job_name.into());
+        self
+    }
+
+    /// Request the passed number of tasks
+    pub fn with_ntasks(mut self, ntasks: usize) -> Self {
+        self.ntasks = ntasks;
+        self
+    }
+
+    /// Request the passed number of nodes (e.g., `2` or `1-4`)
+    pub fn with_nodes(mut self, nodes: impl Into<String>) -> Self {
+        self.nodes = Some(nodes.into());
+        self
+    }
+
+    /// Submit the job to the passed partition
+    pub fn with_partition(mut self, partition: impl Into<String>) -> Self {
+        self.partition = Some(partition.into());
+        self
+    }
+
+    /// Charge the job to the passed account
+    pub fn with_account(mut self, account: impl Into<String>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// Use the passed quality of service
+    pub fn with_qos(mut self, qos: impl Into<String>) -> Self {
+        self.qos = Some(qos.into());
+        self
+    }
+
+    /// Request the passed amount of memory
+    pub fn with_memory(mut self, memory: JobMemory) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Request the passed GPUs (e.g., `2` or `a100:2`)
+    pub fn with_gpus(mut self, gpus: impl Into<String>) -> Self {
+        self.gpus = Some(gpus.into());
+        self
+    }
+
+    /// Request the passed generic resources (e.g., `gpu:a100:2`)
+    pub fn with_gres(mut self, gres: impl Into<String>) -> Self {
+        self.gres = Some(gres.into());
+        self
+    }
+
+    /// Only run the job on nodes with the passed features (e.g., `skylake&ib`)
+    pub fn with_constraint(mut self, constraint: impl Into<String>) -> Self {
+        self.constraint = Some(constraint.into());
+        self
+    }
+
+    /// Do not share the allocated nodes with other jobs
+    pub fn with_exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = exclusive;
+        self
+    }
+
+    /// Send mail notifications to `user` for the passed event types
+    pub fn with_mail(mut self, user: impl Into<String>, types: Vec<MailType>) -> Self {
+        self.mail = Some(JobMail {
+            user: user.into(),
+            types,
+        });
+        self
+    }
+
+    /// Write stdout to the passed file
+    pub fn with_output(mut self, output: impl Into<String>) -> Self {
+        self.output = output.into();
+        self
+    }
+
+    /// Write stderr to the passed file (instead of the stdout file)
+    pub fn with_error(mut self, error: impl Into<String>) -> Self {
+        self.error = Some(error.into());
+        self
+    }
+
+    /// Export the passed environment variable to the job
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Do not start the job before the passed time (e.g., `now+1hour`)
+    pub fn with_begin(mut self, begin: impl Into<String>) -> Self {
+        self.begin = Some(begin.into());
+        self
+    }
+
+    /// Add an additional `#SBATCH` directive (e.g., `--signal=USR1@60`)
+    pub fn with_directive(mut self, directive: impl Into<String>) -> Self {
+        self.extra_directives.push(directive.into());
+        self
+    }
+
+    /// Submit the job as job array with the specified tasks
+    ///
+    /// If stdout is written to the default file, it is changed to a separate file per task (`stdout_%a.txt`).
+    pub fn with_array(mut self, array: ArraySpec) -> Self {
+        if self.output == Self::default().output {
+            self.output = String::from("stdout_%a.txt");
+        }
+        self.array = Some(array);
+        self
+    }
+
+    /// Only start the job once the passed dependencies are satisfied
+    pub fn with_dependency(mut self, dependency: JobDependency) -> Self {
+        self.dependency = Some(dependency);
+        self
+    }
+
+    /// Check that the options are valid (e.g., that the time limit can be parsed)
+    pub fn validate(&self) -> Result<(), Error> {
+        parse_slurm_duration(&self.time)
+            .map_err(|e| e.context(format!("Invalid time limit {}", self.time)))?;
+        if let Some(JobMemory::PerNode(mem) | JobMemory::PerCpu(mem)) = &self.memory {
+            parse_slurm_memory(mem).map_err(|e| e.context(format!("Invalid memory {mem}")))?;
+        }
+        if self.num_cpus == 0 || self.ntasks == 0 {
+            return Err(Error::msg(
+                "At least one CPU and task have to be requested.",
+            ));
+        }
+        for (key, value) in &self.env {
+            if key.is_empty()
+                || key.starts_with(|c: char| c.is_ascii_digit())
+                || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(Error::msg(format!("Invalid environment variable {key}")));
+            }
+            if value.contains(',') {
+                return Err(Error::msg(format!(
+                    "Value of environment variable {key} must not contain commas"
+                )));
+            }
+        }
+        if self.sbatch_directives("").iter().any(|d| d.contains('\n')) {
+            return Err(Error::msg("Options must not contain line breaks."));
+        }
+        Ok(())
+    }
+
+    /// Get the `#SBATCH` directives (without the `#SBATCH` prefix) for these options
+    ///
+    /// `default_job_name` is used if no explicit job name is set.
+    pub fn sbatch_directives(&self, default_job_name: &str) -> Vec<String> {
+        let mut directives = vec![
+            format!("--ntasks={}", self.ntasks),
+            format!("--cpus-per-task={}", self.num_cpus),
+            format!("--time={}", self.time),
+            format!(
+                "--job-name={}",
+                self.job_name.as_deref().unwrap_or(default_job_name)
+            ),
+            format!("--output={}", self.output),
+        ];
+        if let Some(error) = &self.error {
+            directives.push(format!("--error={error}"));
+        }
+        if let Some(array) = &self.array {
+            directives.push(format!("--array={array}"));
+        }
+        if let Some(dependency) = &self.dependency {
+            directives.push(format!("--dependency={dependency}"));
+        }
+        let optional = [
+            ("nodes", &self.nodes),
+            ("partition", &self.partition),
+            ("account", &self.account),
+            ("qos", &self.qos),
+            ("gpus", &self.gpus),
+            ("gres", &self.gres),
+            ("constraint", &self.constraint),
+            ("begin", &self.begin),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                directives.push(format!("--{name}={value}"));
+            }
+        }
+        match &self.memory {
+            Some(JobMemory::PerNode(mem)) => directives.push(format!("--mem={mem}")),
+            Some(JobMemory::PerCpu(mem)) => directives.push(format!("--mem-per-cpu={mem}")),
+            None => {}
+        }
+        if self.exclusive {
+            directives.push(String::from("--exclusive"));
+        }
+        if let Some(mail) = &self.mail {
+            directives.push(format!("--mail-user={}", mail.user));
+            if !mail.types.is_empty() {
+                let types: Vec<_> = mail.types.iter().map(|t| t.to_string()).collect();
+                directives.push(format!("--mail-type={}", types.join(",")));
+            }
+        }
+        if !self.env.is_empty() {
+            let env: Vec<_> = self.env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            directives.push(format!("--export=ALL,{}", env.join(",")));
+        }
+        directives.extend(self.extra_directives.iter().cloned());
+        directives
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use glob::{MatchOptions, Pattern};
+
+use crate::executor::CommandExecutor;
+
+use super::shell_quote;
+
+/// Download the files of a job folder matching any of the given glob patterns (e.g., `*.csv` or `results/**/*.json`)
+///
+/// Patterns are matched against the path relative to the job folder, where `*` does not match `/`
+/// (i.e., `*.csv` only matches files directly in the job folder, `**/*.csv` also those in subfolders).
+/// Files are saved in `local_dir`, keeping their path relative to the job folder (i.e., `{root_dir}/{folder_id}`).
+/// Returns the local paths of all downloaded files.
+pub async fn download_job_outputs<E: CommandExecutor, S: AsRef<str>>(
+    executor: &E,
+    root_dir: &str,
+    folder_id: &str,
+    globs: &[S],
+    local_dir: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let patterns = globs
+        .iter()
+        .map(|g| Pattern::new(g.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let job_dir = format!("{root_dir}/{folder_id}");
+    let out = executor
+        .execute(&format!("cd {} && find . -type f", shell_quote(&job_dir)))
+        .await?;
+    if !out.success() {
+        return Err(Error::msg(format!(
+            "Could not list files of job folder {job_dir}: {}",
+            out.stderr.trim()
+        )));
+    }
+    let mut downloaded = Vec::new();
+    for file in out.stdout.lines() {
+        let file = file.strip_prefix("./").unwrap_or(file);
+        if file.is_empty() || !patterns.iter().any(|p| p.matches_with(file, options)) {
+            continue;
+        }
+        let local_path = local_dir.join(file);
+        if let Some(parent) = local_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        executor
+            .download_file(&format!("{job_dir}/{file}"), &local_path)
+            .await
+            .map_err(|e| e.context(format!("Could not download {file}")))?;
+        downloaded.push(local_path);
+    }
+    Ok(downloaded)
+}
+
+#[cfg(feature = "tokio")]
+/// Follow an output file of a job (e.g., `stdout.txt`) while the job is running, like `tail -f`
+///
+/// The returned stream yields all (complete) lines of the file, polling for new lines every `poll_interval`.
+/// It ends once the job is no longer pending or running and all lines were yielded.
+pub fn follow_job_output<'a, E: CommandExecutor>(
+    executor: &'a E,
+    root_dir: &str,
+    folder_id: &str,
+    job_id: &str,
+    file: &str,
+    poll_interval: std::time::Duration,
+) -> impl futures::Stream<Item = Result<String, Error>> + use<'a, E> {
+    use futures::StreamExt;
+
+    use super::{get_job_status, JobStatus};
+
+    let path = shell_quote(&format!("{root_dir}/{folder_id}/{file}"));
+    let job_id = job_id.to_string();
+    // (next line to read, whether the stream is done)
+    futures::stream::unfold((1usize, false), move |(next_line, done)| {
+        let path = path.clone();
+        let job_id = job_id.clone();
+        async move {
+            if done {
+                return None;
+            }
+            loop {
+                let ended = match get_job_status(executor, &job_id).await {
+                    Ok(JobStatus::PENDING { .. } | JobStatus::RUNNING { .. }) => false,
+                    Ok(_) => true,
+                    Err(e) => return Some((vec![Err(e)], (next_line, true))),
+                };
+                let out = match executor
+                    .execute(&format!("tail -n +{next_line} {path} 2>/dev/null"))
+                    .await
+                {
+                    Ok(out) => out.stdout,
+                    Err(e) => return Some((vec![Err(e)], (next_line, true))),
+                };
+                // Only yield complete lines, unless the job has ended
+                let complete = match out.rfind('\n') {
+                    _ if ended => out.as_str(),
+                    Some(i) => &out[..=i],
+                    None => "",
+                };
+                let lines: Vec<_> = complete.lines().map(|l| Ok(l.to_string())).collect();
+                if !lines.is_empty() {
+                    let next_line = next_line + lines.len();
+                    return Some((lines, (next_line, false)));
+                }
+                if ended {
+                    return None;
+                }
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    })
+    .flat_map(futures::stream::iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{executor::MockExecutor, job_management::download_job_outputs};
+
+    #[tokio::test]
+    async fn test_download_job_outputs() {
+        let executor = MockExecutor::new()
+            .with_stdout(
+                "find . -type f",
+                "./start.sh\n./stdout.txt\n./results/a.csv\n./results/b.json\n",
+            )
+            .with_file("exp/1/stdout.txt", "Done!")
+            .with_file("exp/1/results/a.csv", "a,b\n1,2\n");
+        let local_dir = std::env::temp_dir().join("slurry_test_download_job_outputs");
+        let downloaded = download_job_outputs(
+            &executor,
+            "exp",
+            "1",
+            &["stdout.txt", "**/*.csv", "*.json"],
+            &local_dir,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            downloaded,
+            vec![
+                local_dir.join("stdout.txt"),
+                local_dir.join("results/a.csv")
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(local_dir.join("results/a.csv")).unwrap(),
+            "a,b\n1,2\n"
+        );
+        std::fs::remove_dir_all(local_dir).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_follow_job_output() {
+        use futures::StreamExt;
+
+        // The job is no longer in the queue, so the output is read until no new lines are available
+        let executor = MockExecutor::new()
+            .with_stdout("tail -n +1 ", "Starting\nEpoch 1\n")
+            .with_stdout("tail -n +3 ", "Epoch 2\nDone");
+        let lines: Vec<_> = crate::job_management::follow_job_output(
+            &executor,
+            "exp",
+            "1",
+            "4242",
+            "stdout.txt",
+            std::time::Duration::from_millis(10),
+        )
+        .map(Result::unwrap)
+        .collect()
+        .await;
+        assert_eq!(lines, vec!["Starting", "Epoch 1", "Epoch 2", "Done"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_follow_running_job_output() {
+        use futures::StreamExt;
+
+        use crate::executor::CommandOutput;
+
+        const RUNNING: &str = "acc|4242|n001|1|2|1|N/A|(null)|(null)|4242|grp|4242|1-00:00:00|1-00:00:00|myjob|4G|0:10|0.5|part|RUNNING|None|2025-01-14T10:00:00|2025-01-14T09:59:00|/home/user|./run.sh\n";
+        // The job runs for three polls, the second one only finds an incomplete line
+        let executor = MockExecutor::new()
+            .with_responses(
+                "squeue",
+                [RUNNING, RUNNING, RUNNING, ""].map(CommandOutput::from_stdout),
+            )
+            .with_stdout("tail -n +1 ", "Starting\nEpo")
+            .with_responses(
+                "tail -n +2 ",
+                ["Epo", "Epoch 1\n"].map(CommandOutput::from_stdout),
+            )
+            .with_stdout("tail -n +3 ", "Done");
+        let lines: Vec<_> = crate::job_management::follow_job_output(
+            &executor,
+            "exp",
+            "1",
+            "4242",
+            "stdout.txt",
+            std::time::Duration::from_millis(10),
+        )
+        .map(Result::unwrap)
+        .collect()
+        .await;
+        assert_eq!(lines, vec!["Starting", "Epoch 1", "Done"]);
+        let tails = executor
+            .executed_commands()
+            .iter()
+            .filter(|c| c.starts_with("tail"))
+            .count();
+        assert_eq!(tails, 5);
+    }
+}
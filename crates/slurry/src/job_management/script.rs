@@ -0,0 +1,156 @@
+use super::{JobOptions, ARRAY_TASK_ID_PLACEHOLDER};
+
+/// Quote a string for safe use as a single word in POSIX shell commands
+///
+/// Strings only consisting of safe characters (e.g., `experiments/run_1.sh`) are returned unchanged,
+/// all others are wrapped in single quotes (escaping contained single quotes).
+pub fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:+,@%".contains(c))
+    {
+        return s.to_string();
+    }
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A SLURM batch script (i.e., shebang, `#SBATCH` directives and the commands to execute)
+///
+/// The script is rendered locally (see [`JobScript::render`]) and then uploaded as a file,
+/// so commands are never interpolated into other shell commands.
+pub struct JobScript {
+    /// The shell used to execute the script (e.g., `/bin/bash`)
+    pub shell: String,
+    /// `#SBATCH` directives (without the `#SBATCH` prefix), e.g., `--ntasks=1`
+    pub directives: Vec<String>,
+    /// Lines of shell code to execute (in order)
+    pub commands: Vec<String>,
+}
+
+impl JobScript {
+    /// Create a new, empty job script executed by the given shell
+    pub fn new(shell: impl Into<String>) -> Self {
+        Self {
+            shell: shell.into(),
+            directives: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Create the job script for the given [`JobOptions`]
+    ///
+    /// `default_job_name` is used if the options do not specify a job name.
+    /// For job arrays, [`ARRAY_TASK_ID_PLACEHOLDER`] in the command is replaced by `${SLURM_ARRAY_TASK_ID}`.
+    pub fn from_options(options: &JobOptions, default_job_name: &str) -> Self {
+        let mut script = Self::new(&options.shell);
+        script.directives = options.sbatch_directives(default_job_name);
+        if let Some(forwarding) = &options.local_forwarding {
+            script.commands.push(format!(
+                "ssh -N -f -R {}:localhost:{} {}",
+                forwarding.relay_port,
+                forwarding.local_port,
+                shell_quote(&forwarding.relay_addr)
+            ));
+        }
+        if options.array.is_some() {
+            script.commands.push(
+                options
+                    .command
+                    .replace(ARRAY_TASK_ID_PLACEHOLDER, "${SLURM_ARRAY_TASK_ID}"),
+            );
+        } else {
+            script.commands.push(options.command.clone());
+        }
+        script
+    }
+
+    /// Add an `#SBATCH` directive (e.g., `--signal=USR1@60`)
+    pub fn with_directive(mut self, directive: impl Into<String>) -> Self {
+        self.directives.push(directive.into());
+        self
+    }
+
+    /// Add a line of shell code to execute
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.commands.push(command.into());
+        self
+    }
+
+    /// Render the script
+    ///
+    /// Directive values containing whitespace are wrapped in double quotes, which is understood by `sbatch`.
+    pub fn render(&self) -> String {
+        let mut s = format!("#!{}\n### Job Parameters\n", self.shell);
+        for directive in &self.directives {
+            let directive = match directive.split_once('=') {
+                Some((name, value)) if value.contains(char::is_whitespace) => {
+                    format!("{name}=\"{}\"", value.replace('"', "\\\""))
+                }
+                _ => directive.clone(),
+            };
+            s.push_str(&format!("#SBATCH {directive}\n"));
+        }
+        s.push_str("\n### Program Code\n");
+        for command in &self.commands {
+            s.push_str(command);
+            s.push('\n');
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::job_management::{
+        shell_quote, ArraySpec, JobLocalForwarding, JobOptions, JobScript,
+    };
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("experiments/run_1.sh"), "experiments/run_1.sh");
+        assert_eq!(shell_quote("my experiments"), "'my experiments'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_render_job_script() {
+        let options = JobOptions::new("experiments", "echo 'Hello, World!'")
+            .with_shell("/bin/bash")
+            .with_num_cpus(2)
+            .with_time("0-01:00:00")
+            .with_job_name("my job")
+            .with_local_forwarding(JobLocalForwarding {
+                local_port: 3000,
+                relay_port: 3001,
+                relay_addr: "login23-1".to_string(),
+            });
+        assert_eq!(
+            JobScript::from_options(&options, "unused").render(),
+            "#!/bin/bash
+### Job Parameters
+#SBATCH --ntasks=1
+#SBATCH --cpus-per-task=2
+#SBATCH --time=0-01:00:00
+#SBATCH --job-name=\"my job\"
+#SBATCH --output=stdout.txt
+
+### Program Code
+ssh -N -f -R 3001:localhost:3000 login23-1
+echo 'Hello, World!'
+"
+        );
+    }
+
+    #[test]
+    fn test_render_array_job_script() {
+        let options = JobOptions::new("experiments", "./sweep --seed {task_id}")
+            .with_array(ArraySpec::range(1, 10).with_max_concurrent(2));
+        let script = JobScript::from_options(&options, "sweep").render();
+        assert!(script.contains("#SBATCH --array=1-10%2\n"));
+        assert!(script.contains("#SBATCH --output=stdout_%a.txt\n"));
+        assert!(script.ends_with("./sweep --seed ${SLURM_ARRAY_TASK_ID}\n"));
+    }
+}
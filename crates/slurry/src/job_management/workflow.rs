@@ -0,0 +1,365 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Error;
+
+use crate::executor::CommandExecutor;
+
+use super::{
+    submit_job, DependencyCondition, DependencyType, FolderID, JobDependency, JobID, JobOptions,
+};
+
+// Distinguishes the jobs of different workflows
+static NEXT_WORKFLOW_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Handle of a job added to a [`Workflow`]
+///
+/// Handles are only valid for the workflow (or its clones) they were returned by.
+pub struct WorkflowJob {
+    workflow: usize,
+    index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A submitted job of a [`Workflow`]
+pub struct SubmittedJob {
+    /// Name of the job in the workflow
+    pub name: String,
+    /// ID of the job folder
+    pub folder_id: FolderID,
+    /// SLURM job ID
+    pub job_id: JobID,
+}
+
+#[derive(Debug)]
+/// Error when a job of a [`Workflow`] could not be submitted (see [`Workflow::submit`])
+pub struct WorkflowSubmitError {
+    /// Name of the job which could not be submitted
+    pub job: String,
+    /// Jobs which were already submitted (e.g., to cancel them), in the order in which they were added to the workflow
+    pub submitted: Vec<SubmittedJob>,
+    /// Reason why the job could not be submitted
+    pub source: Error,
+}
+
+impl Display for WorkflowSubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let job_ids: Vec<_> = self.submitted.iter().map(|j| j.job_id.as_str()).collect();
+        write!(
+            f,
+            "Could not submit job {} (already submitted: [{}]): {}",
+            self.job,
+            job_ids.join(", "),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for WorkflowSubmitError {}
+
+#[derive(Debug, Clone)]
+/// A workflow of SLURM jobs with dependencies between them (i.e., a DAG)
+///
+/// Jobs are submitted in topological order, passing the real SLURM job IDs of submitted jobs into `--dependency`.
+/// All dependencies of a job (i.e., its own conditions and the workflow dependencies) must be satisfied,
+/// so jobs of a workflow cannot use [`JobDependency::any`] together with workflow dependencies.
+pub struct Workflow {
+    id: usize,
+    jobs: Vec<(String, JobOptions)>,
+    // (job, dependency type, job it depends on)
+    edges: Vec<(WorkflowJob, DependencyType, WorkflowJob)>,
+    singletons: Vec<WorkflowJob>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self {
+            id: NEXT_WORKFLOW_ID.fetch_add(1, Ordering::Relaxed),
+            jobs: Vec::new(),
+            edges: Vec::new(),
+            singletons: Vec::new(),
+        }
+    }
+}
+
+impl Workflow {
+    /// Create a new, empty workflow
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a job to the workflow
+    ///
+    /// If the options do not specify a job name, `name` is used as job name.
+    pub fn add_job(&mut self, name: impl Into<String>, mut options: JobOptions) -> WorkflowJob {
+        let name = name.into();
+        if options.job_name.is_none() {
+            options.job_name = Some(name.clone());
+        }
+        self.jobs.push((name, options));
+        WorkflowJob {
+            workflow: self.id,
+            index: self.jobs.len() - 1,
+        }
+    }
+
+    /// Index of a job of this workflow
+    fn index(&self, job: WorkflowJob) -> Result<usize, Error> {
+        match job.workflow == self.id && job.index < self.jobs.len() {
+            true => Ok(job.index),
+            false => Err(Error::msg("Job does not belong to this workflow.")),
+        }
+    }
+
+    /// Dependencies between the jobs as indices (job, dependency type, job it depends on)
+    fn edge_indices(&self) -> Result<Vec<(usize, DependencyType, usize)>, Error> {
+        self.edges
+            .iter()
+            .map(|(job, kind, on)| Ok((self.index(*job)?, *kind, self.index(*on)?)))
+            .collect()
+    }
+
+    /// Let `job` depend on `on` with the given dependency type (e.g., [`DependencyType::AFTEROK`])
+    ///
+    /// For [`DependencyType::SINGLETON`], use [`Workflow::singleton`] instead.
+    pub fn add_dependency(
+        &mut self,
+        job: WorkflowJob,
+        kind: DependencyType,
+        on: WorkflowJob,
+    ) -> &mut Self {
+        self.edges.push((job, kind, on));
+        self
+    }
+
+    /// Only start `job` after all previously started jobs with the same name and user terminated
+    pub fn singleton(&mut self, job: WorkflowJob) -> &mut Self {
+        self.singletons.push(job);
+        self
+    }
+
+    /// Order in which the jobs can be submitted (i.e., every job comes after all jobs it depends on)
+    ///
+    /// Fails if the dependencies contain a cycle or a job of another workflow.
+    pub fn topological_order(&self) -> Result<Vec<WorkflowJob>, Error> {
+        let edges = self.edge_indices()?;
+        let mut in_degree = vec![0; self.jobs.len()];
+        for (job, _, _) in &edges {
+            in_degree[*job] += 1;
+        }
+        let mut queue: VecDeque<usize> = (0..self.jobs.len())
+            .filter(|i| in_degree[*i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.jobs.len());
+        while let Some(i) = queue.pop_front() {
+            order.push(WorkflowJob {
+                workflow: self.id,
+                index: i,
+            });
+            for (job, _, on) in &edges {
+                if *on == i {
+                    in_degree[*job] -= 1;
+                    if in_degree[*job] == 0 {
+                        queue.push_back(*job);
+                    }
+                }
+            }
+        }
+        if order.len() != self.jobs.len() {
+            return Err(Error::msg("Workflow contains a dependency cycle."));
+        }
+        Ok(order)
+    }
+
+    /// Submit all jobs of the workflow using the given [`CommandExecutor`]
+    ///
+    /// Returns the submitted jobs in the order in which they were added to the workflow.
+    /// The workflow (i.e., its dependencies and the options of all jobs) is validated before any job is submitted.
+    /// If a job cannot be submitted, the returned error is a [`WorkflowSubmitError`] containing the already submitted jobs.
+    pub async fn submit<E: CommandExecutor>(
+        &self,
+        executor: &E,
+    ) -> Result<Vec<SubmittedJob>, Error> {
+        let order = self.topological_order()?;
+        let edges = self.edge_indices()?;
+        let mut singletons = vec![false; self.jobs.len()];
+        for job in &self.singletons {
+            singletons[self.index(*job)?] = true;
+        }
+        for (i, (name, options)) in self.jobs.iter().enumerate() {
+            options
+                .validate()
+                .map_err(|e| e.context(format!("Invalid options of job {name}")))?;
+            let any = options.dependency.as_ref().is_some_and(|d| d.any);
+            if any && (singletons[i] || edges.iter().any(|(job, _, _)| *job == i)) {
+                return Err(Error::msg(format!(
+                    "Job {name} only requires any of its dependencies, which cannot be combined with workflow dependencies."
+                )));
+            }
+        }
+
+        let mut job_ids: Vec<Option<SubmittedJob>> = vec![None; self.jobs.len()];
+        for WorkflowJob { index: i, .. } in order {
+            let (name, options) = &self.jobs[i];
+            let mut options = options.clone();
+            let dependency = options
+                .dependency
+                .get_or_insert_with(JobDependency::default);
+            if singletons[i] {
+                dependency
+                    .conditions
+                    .push(DependencyCondition::new::<String>(
+                        DependencyType::SINGLETON,
+                        [],
+                    ));
+            }
+            for (_, kind, on) in edges.iter().filter(|(job, _, _)| *job == i) {
+                let job_id = job_ids[*on]
+                    .as_ref()
+                    .map(|j| j.job_id.clone())
+                    .ok_or_else(|| Error::msg("Dependency was not submitted."))?;
+                match dependency
+                    .conditions
+                    .iter_mut()
+                    .find(|c| c.kind == *kind && c.status.is_none())
+                {
+                    Some(condition) => condition.job_ids.push(job_id),
+                    None => dependency
+                        .conditions
+                        .push(DependencyCondition::new(*kind, [job_id])),
+                }
+            }
+            if dependency.conditions.is_empty() {
+                options.dependency = None;
+            }
+            let (folder_id, job_id) = match submit_job(executor, options).await {
+                Ok(res) => res,
+                Err(source) => {
+                    return Err(WorkflowSubmitError {
+                        job: name.clone(),
+                        submitted: job_ids.into_iter().flatten().collect(),
+                        source,
+                    }
+                    .into())
+                }
+            };
+            job_ids[i] = Some(SubmittedJob {
+                name: name.clone(),
+                folder_id,
+                job_id,
+            });
+        }
+        Ok(job_ids.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::MockExecutor,
+        job_management::{
+            DependencyCondition, DependencyType, JobDependency, JobOptions, Workflow,
+            WorkflowSubmitError,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_submit_workflow() {
+        // Separate root directories, so that the sbatch commands can be distinguished
+        let executor = MockExecutor::new()
+            .with_stdout("wf/preprocess", "Submitted batch job 1")
+            .with_stdout("wf/train", "Submitted batch job 2")
+            .with_stdout("wf/evaluate", "Submitted batch job 3");
+        let mut workflow = Workflow::new();
+        let evaluate = workflow.add_job("evaluate", JobOptions::new("wf/evaluate", "./eval"));
+        let train = workflow.add_job("train", JobOptions::new("wf/train", "./train"));
+        let preprocess = workflow.add_job("preprocess", JobOptions::new("wf/preprocess", "./pre"));
+        workflow
+            .add_dependency(train, DependencyType::AFTEROK, preprocess)
+            .add_dependency(evaluate, DependencyType::AFTEROK, train)
+            .add_dependency(evaluate, DependencyType::AFTEROK, preprocess)
+            .singleton(train);
+        assert_eq!(
+            workflow.topological_order().unwrap(),
+            vec![preprocess, train, evaluate]
+        );
+
+        let submitted = workflow.submit(&executor).await.unwrap();
+        let ids: Vec<_> = submitted.iter().map(|j| j.job_id.as_str()).collect();
+        assert_eq!(ids, vec!["3", "2", "1"]);
+        let script = |root: &str, folder_id: &str| {
+            String::from_utf8(
+                executor
+                    .file(&format!("{root}/{folder_id}/start.sh"))
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        assert!(script("wf/evaluate", &submitted[0].folder_id)
+            .contains("#SBATCH --dependency=afterok:2:1\n"));
+        assert!(script("wf/train", &submitted[1].folder_id)
+            .contains("#SBATCH --dependency=singleton,afterok:1\n"));
+        assert!(!script("wf/preprocess", &submitted[2].folder_id).contains("--dependency"));
+
+        workflow.add_dependency(preprocess, DependencyType::AFTERANY, evaluate);
+        assert!(workflow.topological_order().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_submit_workflow_errors() {
+        // No response for the second job, so that sbatch returns no job ID
+        let executor = MockExecutor::new().with_stdout("wf/first", "Submitted batch job 1");
+        let mut workflow = Workflow::new();
+        let first = workflow.add_job("first", JobOptions::new("wf/first", "./first"));
+        let second = workflow.add_job("second", JobOptions::new("wf/second", "./second"));
+        workflow.add_dependency(second, DependencyType::AFTEROK, first);
+        let err = workflow.submit(&executor).await.unwrap_err();
+        let err = err.downcast_ref::<WorkflowSubmitError>().unwrap();
+        assert_eq!(err.job, "second");
+        assert_eq!(err.submitted.len(), 1);
+        assert_eq!(err.submitted[0].job_id, "1");
+
+        // Jobs of other workflows are rejected instead of panicking
+        let mut other = Workflow::new();
+        let foreign = other.add_job("foreign", JobOptions::new("wf/foreign", "./foreign"));
+        workflow.singleton(foreign);
+        assert!(workflow.submit(&executor).await.is_err());
+        let mut workflow = Workflow::new();
+        let job = workflow.add_job("job", JobOptions::new("wf/job", "./job"));
+        workflow.add_dependency(job, DependencyType::AFTEROK, foreign);
+        assert!(workflow.topological_order().is_err());
+
+        // Invalid options of a later job are detected before submitting the first one
+        let mut workflow = Workflow::new();
+        let first = workflow.add_job("first", JobOptions::new("wf/first", "./first"));
+        let second = workflow.add_job("second", JobOptions::new("wf/second", "./second"));
+        let third = workflow.add_job(
+            "third",
+            JobOptions::new("wf/third", "./third").with_time("two hours"),
+        );
+        workflow
+            .add_dependency(second, DependencyType::AFTEROK, first)
+            .add_dependency(third, DependencyType::AFTEROK, second);
+        let commands = executor.executed_commands().len();
+        assert!(workflow.submit(&executor).await.is_err());
+        assert_eq!(executor.executed_commands().len(), commands);
+
+        // Dependencies of the workflow would be ORed with the own ones
+        let mut workflow = Workflow::new();
+        let first = workflow.add_job("first", JobOptions::new("wf/first", "./first"));
+        let second = workflow.add_job(
+            "second",
+            JobOptions::new("wf/second", "./second").with_dependency(JobDependency::any(vec![
+                DependencyCondition::new(DependencyType::AFTER, ["42"]),
+            ])),
+        );
+        workflow.add_dependency(second, DependencyType::AFTEROK, first);
+        let commands = executor.executed_commands().len();
+        assert!(workflow.submit(&executor).await.is_err());
+        assert_eq!(executor.executed_commands().len(), commands);
+    }
+}
@@ -13,20 +13,30 @@ use anyhow::Error;
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "ssh")]
-use async_ssh2_tokio::client::{AuthKeyboardInteractive, AuthMethod, ServerCheckMethod};
+pub use ssh::Client;
 #[cfg(feature = "ssh")]
-const SERVER_CHECK_METHOD: ServerCheckMethod = ServerCheckMethod::NoCheck;
-#[cfg(feature = "ssh")]
-pub use async_ssh2_tokio::Client;
+use ssh::{
+    AuthKeyboardInteractive, AuthMethod, HostKeyCallback, HostKeyCheck,
+    KeyboardInteractiveCallback, PromptResponse, SshHostConfig,
+};
 
-#[cfg(feature = "ssh")]
 /// Module for managing (e.g., creating or cancelling) SLURM jobs
 pub mod job_management;
 
+/// Module for executing commands and transferring files
+/// e.g., locally or over SSH
+pub mod executor;
+
 /// Module for extracting data from SLURM systems
 /// e.g., about currently running jobs
 pub mod data_extraction;
 
+#[cfg(feature = "ssh")]
+/// Module for SSH connections
+///
+/// e.g., verifying host keys
+pub mod ssh;
+
 /// Module for miscellaneous features
 ///
 /// e.g., SSH port forwarding
@@ -34,12 +44,14 @@ pub mod misc;
 
 #[cfg(feature = "ssh")]
 #[doc(inline)]
-pub use misc::port_forwarding::ssh_port_forwarding;
+pub use misc::port_forwarding::{ssh_port_forwarding, PortForward};
 
-#[cfg(feature = "ssh")]
 #[doc(inline)]
 pub use job_management::submit_job;
 
+#[doc(inline)]
+pub use executor::{CommandExecutor, CommandOutput};
+
 #[doc(inline)]
 pub use data_extraction::get_squeue_res_locally;
 
@@ -50,6 +62,13 @@ pub use data_extraction::get_squeue_res_ssh;
 #[doc(inline)]
 pub use data_extraction::squeue_diff;
 
+#[doc(inline)]
+pub use data_extraction::get_sacct_res_locally;
+
+#[cfg(feature = "ssh")]
+#[doc(inline)]
+pub use data_extraction::get_sacct_res_ssh;
+
 // days-hours:minutes:seconds
 fn parse_slurm_duration(s: &str) -> Result<Duration, Error> {
     let mut dur = Duration::default();
@@ -68,8 +87,13 @@ fn parse_slurm_duration(s: &str) -> Result<Duration, Error> {
     if hms.len() == 3 {
         let hours: u64 = hms[0].parse()?;
         let mins: u64 = hms[1].parse()?;
-        let secs: u64 = hms[1].parse()?;
+        let secs: u64 = hms[2].parse()?;
         dur += Duration::from_secs(secs + 60 * mins + 60 * 60 * hours);
+    } else if hms.len() == 2 && has_days_part {
+        // days-hours:minutes
+        let hours: u64 = hms[0].parse()?;
+        let mins: u64 = hms[1].parse()?;
+        dur += Duration::from_secs(60 * mins + 60 * 60 * hours);
     } else if hms.len() == 2 {
         let mins: u64 = hms[0].parse()?;
         let secs: u64 = hms[1].parse()?;
@@ -92,6 +116,25 @@ fn parse_slurm_duration(s: &str) -> Result<Duration, Error> {
     Ok(dur)
 }
 
+// e.g., 1024K, 4G, or 512 (bytes)
+fn parse_slurm_memory(s: &str) -> Result<u64, Error> {
+    let (num, unit) = match s.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((i, _)) => s.split_at(i),
+        None => (s, ""),
+    };
+    let factor: u64 = match unit.chars().next() {
+        None => 1,
+        Some('K') => 1 << 10,
+        Some('M') => 1 << 20,
+        Some('G') => 1 << 30,
+        Some('T') => 1 << 40,
+        Some('P') => 1 << 50,
+        Some(u) => return Err(Error::msg(format!("Invalid memory unit {u}."))),
+    };
+    let num: f64 = num.parse()?;
+    Ok((num * factor as f64) as u64)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 /// State of a SLURM job (according to `squeue`)
 ///
@@ -120,6 +163,29 @@ pub enum JobState {
     /// Other Job state, specifying the concrete job state as a [`String`]
     OTHER(String),
 }
+impl JobState {
+    /// Whether the job terminated (i.e., its state can no longer change)
+    pub fn is_finished(&self) -> bool {
+        match self {
+            JobState::RUNNING | JobState::PENDING | JobState::COMPLETING => false,
+            JobState::COMPLETED
+            | JobState::CANCELLED
+            | JobState::FAILED
+            | JobState::TIMEOUT
+            | JobState::OUT_OF_MEMORY
+            | JobState::NODE_FAIL => true,
+            JobState::OTHER(s) => matches!(s.as_str(), "BOOT_FAIL" | "DEADLINE" | "PREEMPTED"),
+        }
+    }
+}
+impl std::fmt::Display for JobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobState::OTHER(s) => write!(f, "{s}"),
+            s => write!(f, "{s:?}"),
+        }
+    }
+}
 impl FromStr for JobState {
     type Err = Error;
 
@@ -152,6 +218,21 @@ pub struct ConnectionConfig {
     pub username: String,
     /// The authentication configuration
     pub auth: ConnectionAuth,
+    #[serde(default, rename = "hostKeyCheck")]
+    /// How to verify the host key of the server
+    pub host_key_check: HostKeyCheck,
+    #[serde(skip)]
+    /// Callback for deciding whether to trust unknown host keys (see [`HostKeyCheck::TrustOnFirstUse`])
+    pub host_key_callback: Option<HostKeyCallback>,
+    #[serde(skip)]
+    /// Callback asked for answers to keyboard-interactive prompts without configured response (e.g., MFA codes)
+    pub keyboard_interactive_callback: Option<KeyboardInteractiveCallback>,
+    #[serde(default, rename = "jumpHosts")]
+    /// Jump hosts (e.g., a gateway or bastion host) to connect through, in order
+    ///
+    /// Each jump host uses its own authentication and host key verification settings.
+    /// If a jump host has no [`ConnectionConfig::host_key_callback`], the callback of this config is used.
+    pub jump_hosts: Vec<ConnectionConfig>,
 }
 
 #[cfg(feature = "ssh")]
@@ -164,6 +245,10 @@ impl Default for ConnectionConfig {
                 password: String::new(),
                 mfa_code: String::new(),
             },
+            host_key_check: HostKeyCheck::default(),
+            host_key_callback: None,
+            keyboard_interactive_callback: None,
+            jump_hosts: Vec::new(),
         }
     }
 }
@@ -175,8 +260,31 @@ impl ConnectionConfig {
             host,
             username,
             auth,
+            ..Default::default()
         }
     }
+    /// Create a connection configuration for a host alias of the OpenSSH config (`~/.ssh/config` and `/etc/ssh/ssh_config`)
+    ///
+    /// Resolves `HostName`, `Port`, `User`, `IdentityFile`, `IdentitiesOnly` and `ProxyJump` (into [`ConnectionConfig::jump_hosts`]).
+    /// The first existing `IdentityFile` is used for authentication.
+    /// Without one, the SSH agent (if `SSH_AUTH_SOCK` is set and `IdentitiesOnly` is not) is tried before the first existing default key (e.g., `~/.ssh/id_ed25519`).
+    /// Certificates next to the key (i.e., `{key}-cert.pub`) are used automatically.
+    pub fn from_ssh_config(alias: &str) -> Result<Self, Error> {
+        ssh::connection_config_from_ssh(alias, &SshHostConfig::resolve)
+    }
+
+    /// Create a connection configuration for a host alias of the given OpenSSH config files
+    ///
+    /// See [`ConnectionConfig::from_ssh_config`].
+    pub fn from_ssh_config_files<P: AsRef<std::path::Path>>(
+        alias: &str,
+        paths: &[P],
+    ) -> Result<Self, Error> {
+        ssh::connection_config_from_ssh(alias, &|alias| {
+            SshHostConfig::resolve_from_files(alias, paths)
+        })
+    }
+
     /// Assign the passed authentication settings to the connection config
     pub fn with_auth(mut self, auth: ConnectionAuth) -> Self {
         self.auth = auth;
@@ -194,6 +302,29 @@ impl ConnectionConfig {
         self.host = host;
         self
     }
+    /// Assign the passed callback for answering keyboard-interactive prompts to the connection config
+    pub fn with_keyboard_interactive_callback(
+        mut self,
+        callback: KeyboardInteractiveCallback,
+    ) -> Self {
+        self.keyboard_interactive_callback = Some(callback);
+        self
+    }
+    /// Add a jump host to connect through (after all previously added jump hosts)
+    pub fn with_jump_host(mut self, jump_host: ConnectionConfig) -> Self {
+        self.jump_hosts.push(jump_host);
+        self
+    }
+    /// Assign the passed host key verification settings to the connection config
+    pub fn with_host_key_check(mut self, host_key_check: HostKeyCheck) -> Self {
+        self.host_key_check = host_key_check;
+        self
+    }
+    /// Assign the passed callback for deciding whether to trust unknown host keys to the connection config
+    pub fn with_host_key_callback(mut self, callback: HostKeyCallback) -> Self {
+        self.host_key_callback = Some(callback);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -218,57 +349,124 @@ pub enum ConnectionAuth {
         /// Optional passphrase for the SSH key
         passphrase: Option<String>,
     },
+    #[serde(rename = "ssh-key-data")]
+    /// Login via in-memory SSH key material (e.g., retrieved from a secrets store)
+    SSHKeyData {
+        /// Content of the private SSH key (e.g., in OpenSSH format)
+        key: String,
+        /// Optional passphrase for the SSH key
+        passphrase: Option<String>,
+        /// Optional content of an OpenSSH user certificate for the key
+        certificate: Option<String>,
+    },
+    #[serde(rename = "ssh-certificate")]
+    /// Login via an SSH key and an OpenSSH user certificate
+    SSHCertificate {
+        /// Path to where the SSH key is stored
+        path: String,
+        /// Optional passphrase for the SSH key
+        passphrase: Option<String>,
+        #[serde(rename = "certificatePath")]
+        /// Path to where the certificate is stored (defaults to `{path}-cert.pub`)
+        certificate_path: Option<String>,
+    },
+    #[serde(rename = "agent")]
+    /// Login via the keys of the running SSH agent (using `SSH_AUTH_SOCK`)
+    Agent,
+    #[serde(rename = "keyboard-interactive")]
+    /// Login via keyboard-interactive authentication, answering prompts matching a regular expression
+    ///
+    /// Prompts without a matching response are passed to [`ConnectionConfig::keyboard_interactive_callback`].
+    KeyboardInteractive {
+        /// Responses to the prompts (e.g., `(?i)verification code` with the current MFA code)
+        #[serde(default)]
+        responses: Vec<PromptResponse>,
+    },
+    #[serde(rename = "fallback")]
+    /// Login via the first of the given methods which succeeds (e.g., the SSH agent, then a key file)
+    Fallback {
+        /// Methods to try (in order)
+        methods: Vec<ConnectionAuth>,
+    },
 }
 
 #[cfg(feature = "ssh")]
-impl From<ConnectionAuth> for AuthMethod {
-    fn from(val: ConnectionAuth) -> Self {
-        match val {
-            ConnectionAuth::PasswordMFA { password, mfa_code } => {
-                AuthMethod::with_keyboard_interactive(
-                    AuthKeyboardInteractive::new()
-                        .with_response("Password", password)
-                        .with_response("Two-factor code", mfa_code),
-                )
-            }
-            ConnectionAuth::SSHKey { path, passphrase } => {
-                AuthMethod::with_key_file(path, passphrase.as_deref())
-            }
-        }
+// Common prompts for MFA codes (e.g., `Two-factor code: `, `Verification code: ` or `One-time password: `)
+const MFA_PROMPT_REGEX: &str = r"(?i)(two-factor|verification|one-time|otp|token|\bcode\b)";
+
+#[cfg(feature = "ssh")]
+impl TryFrom<ConnectionAuth> for AuthMethod {
+    type Error = Error;
+
+    fn try_from(val: ConnectionAuth) -> Result<Self, Self::Error> {
+        (&val).try_into()
     }
 }
 
 #[cfg(feature = "ssh")]
-impl From<&ConnectionAuth> for AuthMethod {
-    fn from(val: &ConnectionAuth) -> Self {
-        match val {
+/// Fails if a prompt pattern of keyboard-interactive authentication is not a valid regular expression
+impl TryFrom<&ConnectionAuth> for AuthMethod {
+    type Error = Error;
+
+    fn try_from(val: &ConnectionAuth) -> Result<Self, Self::Error> {
+        Ok(match val {
             ConnectionAuth::PasswordMFA { password, mfa_code } => {
                 AuthMethod::with_keyboard_interactive(
                     AuthKeyboardInteractive::new()
-                        .with_response("Password", password.clone())
-                        .with_response("Two-factor code", mfa_code.clone()),
+                        .with_response(MFA_PROMPT_REGEX, mfa_code.clone())?
+                        .with_response("(?i)password", password.clone())?,
                 )
             }
             ConnectionAuth::SSHKey { path, passphrase } => {
                 AuthMethod::with_key_file(path, passphrase.as_deref())
             }
-        }
+            ConnectionAuth::SSHKeyData {
+                key,
+                passphrase,
+                certificate: Some(certificate),
+            } => AuthMethod::with_certificate(key, passphrase.as_deref(), certificate),
+            ConnectionAuth::SSHKeyData {
+                key,
+                passphrase,
+                certificate: None,
+            } => AuthMethod::with_key(key, passphrase.as_deref()),
+            ConnectionAuth::SSHCertificate {
+                path,
+                passphrase,
+                certificate_path,
+            } => AuthMethod::with_certificate_file(
+                path,
+                passphrase.as_deref(),
+                certificate_path
+                    .clone()
+                    .unwrap_or_else(|| format!("{path}-cert.pub")),
+            ),
+            ConnectionAuth::Agent => AuthMethod::with_agent(),
+            ConnectionAuth::KeyboardInteractive { responses } => {
+                AuthMethod::with_keyboard_interactive(
+                    responses
+                        .iter()
+                        .try_fold(AuthKeyboardInteractive::new(), |auth, r| {
+                            auth.with_response(&r.prompt, r.response.clone())
+                        })?,
+                )
+            }
+            ConnectionAuth::Fallback { methods } => AuthMethod::with_fallback(
+                methods
+                    .iter()
+                    .map(AuthMethod::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
     }
 }
 
 #[cfg(feature = "ssh")]
 /// Login via SSH using the specified configuration
+///
+/// The host key of the server is verified according to [`ConnectionConfig::host_key_check`].
+/// If [`ConnectionConfig::jump_hosts`] are configured, the connection is tunnelled through them.
 pub async fn login_with_cfg(cfg: &ConnectionConfig) -> Result<Client, Error> {
-    let auth_method = (&cfg.auth).into();
-    let client = Client::connect_with_config(
-        cfg.host.clone(),
-        &cfg.username,
-        auth_method,
-        SERVER_CHECK_METHOD,
-        async_ssh2_tokio::Config {
-            ..Default::default()
-        },
-    )
-    .await?;
+    let client = Client::connect(cfg).await?;
     Ok(client)
 }
@@ -1,11 +1,11 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "ssh"))]
 use crate::ConnectionConfig;
 
 #[cfg(feature = "ssh")]
-/// SSH Port Forwarding
+/// SSH Port Forwarding (local, remote and dynamic)
 pub mod port_forwarding;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "ssh"))]
 pub(crate) fn get_config_from_env() -> ConnectionConfig {
     use std::env;
 
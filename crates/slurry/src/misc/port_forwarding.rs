@@ -1,66 +1,478 @@
 use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-    sync::Arc,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
 };
 
 use anyhow::Error;
+use russh::{client::Msg, Channel};
+use serde::{Deserialize, Serialize};
 use tokio::{
-    net::TcpListener,
-    task::{self, JoinHandle},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    task::{JoinHandle, JoinSet},
 };
 
-/// Perform port forwarding over SSH
+use crate::Client;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+/// Event of a [`PortForward`] (e.g., to log forwarded connections)
+pub enum ForwardEvent {
+    /// A new connection is forwarded
+    Connected {
+        /// Address of the connecting peer
+        peer: String,
+        /// Where the connection is forwarded to (`host:port`)
+        target: String,
+    },
+    /// A forwarded connection was closed
+    Closed {
+        /// Address of the connecting peer
+        peer: String,
+        /// Bytes sent from the peer to the target
+        bytes_sent: u64,
+        /// Bytes received from the target
+        bytes_received: u64,
+    },
+    /// A connection could not be accepted or forwarded
+    Error {
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+type ForwardEventHandlerFn = dyn Fn(ForwardEvent) + Send + Sync;
+
+#[derive(Clone)]
+/// Handler for [`ForwardEvent`]s
+pub struct ForwardEventHandler(Arc<ForwardEventHandlerFn>);
+
+impl ForwardEventHandler {
+    /// Create a new event handler
+    pub fn new<F: Fn(ForwardEvent) + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for ForwardEventHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ForwardEventHandler").finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Connection statistics of a [`PortForward`]
+pub struct ForwardStats {
+    /// Currently open connections
+    pub active_connections: usize,
+    /// All connections forwarded so far
+    pub total_connections: usize,
+    /// Connections which could not be accepted or forwarded
+    pub failed_connections: usize,
+    /// Bytes sent to the target (of closed connections)
+    pub bytes_sent: u64,
+    /// Bytes received from the target (of closed connections)
+    pub bytes_received: u64,
+}
+
+/// State shared between a [`PortForward`] and its background task
+#[derive(Debug, Default)]
+struct Shared {
+    event_handler: RwLock<Option<ForwardEventHandler>>,
+    active_connections: AtomicUsize,
+    total_connections: AtomicUsize,
+    failed_connections: AtomicUsize,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Shared {
+    fn emit(&self, event: ForwardEvent) {
+        if let Some(handler) = self.event_handler.read().unwrap().as_ref() {
+            (handler.0)(event);
+        }
+    }
+
+    fn fail(&self, error: Error) {
+        self.failed_connections.fetch_add(1, Ordering::Relaxed);
+        self.emit(ForwardEvent::Error {
+            error: format!("{error:#}"),
+        });
+    }
+
+    /// Forward traffic between `peer_stream` and the channel opened by `open`, until either side closes the connection
+    async fn forward<S, F>(&self, mut peer_stream: S, peer: String, target: String, open: F)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: std::future::Future<Output = Result<Channel<Msg>, Error>>,
+    {
+        let channel = match open.await {
+            Ok(channel) => channel,
+            Err(e) => {
+                self.fail(e.context(format!("Could not forward {peer} to {target}")));
+                return;
+            }
+        };
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        self.emit(ForwardEvent::Connected {
+            peer: peer.clone(),
+            target,
+        });
+        let mut channel_stream = channel.into_stream();
+        let res = tokio::io::copy_bidirectional(&mut peer_stream, &mut channel_stream).await;
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+        match res {
+            Ok((bytes_sent, bytes_received)) => {
+                self.bytes_sent.fetch_add(bytes_sent, Ordering::Relaxed);
+                self.bytes_received
+                    .fetch_add(bytes_received, Ordering::Relaxed);
+                self.emit(ForwardEvent::Closed {
+                    peer,
+                    bytes_sent,
+                    bytes_received,
+                });
+            }
+            Err(e) => self.fail(Error::from(e).context(format!("Forwarding {peer} failed"))),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "the forwarding stops when the handle is dropped"]
+/// A running SSH port forwarding (local, remote or dynamic)
 ///
-/// Using the given client, the local port on the SSH machine will be forwarded to the remote port (e.g., the user's machine)
-pub async fn ssh_port_forwarding<S: AsRef<str>>(
-    client: Arc<async_ssh2_tokio::Client>,
-    local_addr: S,
-    remote_addr: S,
-) -> Result<JoinHandle<()>, Error> {
-    println!("Got client!");
-    let l_addr: SocketAddr = local_addr.as_ref().parse().unwrap();
-    let local_listener = TcpListener::bind(l_addr)
-        .await
-        .expect("Cannot bind local port");
-    let arc = std::sync::Arc::new(client);
-    let r_addr: SocketAddr = remote_addr.as_ref().parse().unwrap();
-    let f = task::spawn(async move {
-        loop {
-            let (mut socket, _) = local_listener
-                .accept()
-                .await
-                .expect("Cannot process local client");
-
-            println!("Client connected");
-            let a = arc.clone();
-            tokio::spawn(async move {
-                let c = a
-                    .open_direct_tcpip_channel(
-                        r_addr,
-                        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0),
-                    )
+/// Connections are forwarded in the background until [`PortForward::shutdown`] is called or the handle is dropped.
+/// Failing connections do not stop the forwarding, they are reported as [`ForwardEvent::Error`] instead.
+pub struct PortForward {
+    shared: Arc<Shared>,
+    shutdown: watch::Sender<bool>,
+    task: Option<JoinHandle<()>>,
+    local_addr: Option<SocketAddr>,
+    remote: Option<(Client, String, u16)>,
+}
+
+impl PortForward {
+    /// Forward connections to a local address through the SSH server to `host:port` (like `ssh -L`)
+    ///
+    /// `host` is resolved by the SSH server (e.g., the name of a compute node).
+    /// Use port `0` in `local_addr` to choose a free port (see [`PortForward::local_addr`]).
+    pub async fn local(
+        client: &Client,
+        local_addr: SocketAddr,
+        host: impl Into<String>,
+        port: u16,
+    ) -> Result<Self, Error> {
+        let listener = TcpListener::bind(local_addr).await.map_err(|e| {
+            Error::from(e).context(format!("Cannot bind local address {local_addr}"))
+        })?;
+        let host = host.into();
+        let client = client.clone();
+        Ok(Self::listen(listener, move |shared, socket, peer| {
+            let client = client.clone();
+            let host = host.clone();
+            async move {
+                let target = format!("{host}:{port}");
+                let open = client.open_direct_tcpip_channel(host, port, Some(peer));
+                shared.forward(socket, peer.to_string(), target, open).await;
+            }
+        }))
+    }
+
+    /// Forward connections to a local address as SOCKS5 proxy through the SSH server (like `ssh -D`)
+    ///
+    /// Only the `CONNECT` command without authentication is supported.
+    /// Target hostnames are resolved by the SSH server.
+    pub async fn dynamic(client: &Client, local_addr: SocketAddr) -> Result<Self, Error> {
+        let listener = TcpListener::bind(local_addr).await.map_err(|e| {
+            Error::from(e).context(format!("Cannot bind local address {local_addr}"))
+        })?;
+        let client = client.clone();
+        Ok(Self::listen(listener, move |shared, mut socket, peer| {
+            let client = client.clone();
+            async move {
+                let (host, port) = match socks5_handshake(&mut socket).await {
+                    Ok(target) => target,
+                    Err(e) => {
+                        shared.fail(e.context(format!("Invalid SOCKS5 request from {peer}")));
+                        return;
+                    }
+                };
+                let target = format!("{host}:{port}");
+                let res = client
+                    .open_direct_tcpip_channel(host, port, Some(peer))
                     .await;
-                match c {
-                    Ok(channel) => {
-                        let mut ssh_stream = channel.into_stream();
-
-                        let copy_bidirectional =
-                            tokio::io::copy_bidirectional(&mut socket, &mut ssh_stream).await;
-                        match copy_bidirectional {
-                            Ok((bytes_to_remote, bytes_to_local)) => {
-                                println!(
-                            "Connection closed. Sent {bytes_to_remote} bytes to remote, received {bytes_to_local} bytes from remote"
-                        );
-                            }
-                            Err(e) => eprintln!("Error forwarding traffic: {e:?}"),
+                // Report the result to the SOCKS5 client (with an unspecified bound address)
+                let reply = if res.is_ok() {
+                    SOCKS5_SUCCEEDED
+                } else {
+                    SOCKS5_FAILURE
+                };
+                if let Err(e) = socket
+                    .write_all(&[SOCKS5_VERSION, reply, 0, 1, 0, 0, 0, 0, 0, 0])
+                    .await
+                {
+                    shared.fail(Error::from(e).context(format!("Could not reply to {peer}")));
+                    return;
+                }
+                shared
+                    .forward(socket, peer.to_string(), target, async { res })
+                    .await;
+            }
+        }))
+    }
+
+    /// Let the SSH server listen on `remote_addr:remote_port` and forward connections to `host:port` on the local machine (like `ssh -R`)
+    ///
+    /// Use remote port `0` to let the server choose a free port (see [`PortForward::remote_port`]).
+    /// Binding to other addresses than `localhost` on the server might require `GatewayPorts` to be enabled.
+    pub async fn remote(
+        client: &Client,
+        remote_addr: impl Into<String>,
+        remote_port: u16,
+        host: impl Into<String>,
+        port: u16,
+    ) -> Result<Self, Error> {
+        let remote_addr = remote_addr.into();
+        let host = host.into();
+        let (remote_port, mut channels) = client
+            .request_remote_forward(&remote_addr, remote_port)
+            .await?;
+        let shared = Arc::new(Shared::default());
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+        let task_shared = Arc::clone(&shared);
+        let task = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            loop {
+                let channel = tokio::select! {
+                    _ = shutdown_rx.changed() => break,
+                    channel = channels.recv() => channel,
+                    // Clean up finished connections
+                    Some(_) = connections.join_next() => continue,
+                };
+                let Some((channel, peer)) = channel else {
+                    task_shared.fail(Error::msg("Remote forwarding was cancelled"));
+                    break;
+                };
+                let shared = Arc::clone(&task_shared);
+                let host = host.clone();
+                connections.spawn(async move {
+                    let target = format!("{host}:{port}");
+                    match TcpStream::connect((host.as_str(), port)).await {
+                        Ok(socket) => {
+                            shared
+                                .forward(socket, peer, target, async { Ok(channel) })
+                                .await;
+                        }
+                        Err(e) => {
+                            shared.fail(
+                                Error::from(e).context(format!("Could not connect to {target}")),
+                            );
+                            let _ = channel.close().await;
                         }
                     }
-                    Err(e) => eprintln!("Could not open channel: {e:?}"),
+                });
+            }
+        });
+        Ok(Self {
+            shared,
+            shutdown,
+            task: Some(task),
+            local_addr: None,
+            remote: Some((client.clone(), remote_addr, remote_port)),
+        })
+    }
+
+    /// Accept connections of `listener` in a background task, handling each using `handle`
+    fn listen<F, Fut>(listener: TcpListener, handle: F) -> Self
+    where
+        F: Fn(Arc<Shared>, TcpStream, SocketAddr) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let local_addr = listener.local_addr().ok();
+        let shared = Arc::new(Shared::default());
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+        let task_shared = Arc::clone(&shared);
+        let task = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            loop {
+                let accepted = tokio::select! {
+                    _ = shutdown_rx.changed() => break,
+                    accepted = listener.accept() => accepted,
+                    // Clean up finished connections
+                    Some(_) = connections.join_next() => continue,
+                };
+                match accepted {
+                    Ok((socket, peer)) => {
+                        connections.spawn(handle(Arc::clone(&task_shared), socket, peer));
+                    }
+                    Err(e) => {
+                        task_shared.fail(Error::from(e).context("Cannot accept connection"));
+                        // Do not spin if accepting keeps failing (e.g., because of too many open files)
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
                 }
-            });
+            }
+        });
+        Self {
+            shared,
+            shutdown,
+            task: Some(task),
+            local_addr,
+            remote: None,
+        }
+    }
+
+    /// Pass all [`ForwardEvent`]s to the given handler
+    pub fn with_event_handler(self, handler: ForwardEventHandler) -> Self {
+        *self.shared.event_handler.write().unwrap() = Some(handler);
+        self
+    }
+
+    /// The local address connections are accepted on (for local and dynamic forwardings)
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// The port the SSH server listens on (for remote forwardings)
+    pub fn remote_port(&self) -> Option<u16> {
+        self.remote.as_ref().map(|(_, _, port)| *port)
+    }
+
+    /// Current connection statistics
+    pub fn stats(&self) -> ForwardStats {
+        ForwardStats {
+            active_connections: self.shared.active_connections.load(Ordering::Relaxed),
+            total_connections: self.shared.total_connections.load(Ordering::Relaxed),
+            failed_connections: self.shared.failed_connections.load(Ordering::Relaxed),
+            bytes_sent: self.shared.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.shared.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether connections are still forwarded
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    /// Stop accepting new connections, close all open connections and wait until the forwarding has stopped
+    ///
+    /// For remote forwardings, the server is asked to stop listening.
+    pub async fn shutdown(mut self) -> Result<(), Error> {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.task.take() {
+            task.await?;
+        }
+        if let Some((client, remote_addr, remote_port)) = self.remote.take() {
+            client
+                .cancel_remote_forward(&remote_addr, remote_port)
+                .await?;
         }
-    });
-    Ok(f)
+        Ok(())
+    }
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some((client, remote_addr, remote_port)) = self.remote.take() {
+            // Ask the server to stop listening (otherwise, it listens until the connection is closed)
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(async move {
+                    let _ = client
+                        .cancel_remote_forward(&remote_addr, remote_port)
+                        .await;
+                });
+            }
+        }
+    }
+}
+
+const SOCKS5_VERSION: u8 = 5;
+const SOCKS5_SUCCEEDED: u8 = 0;
+const SOCKS5_FAILURE: u8 = 1;
+
+/// Perform the SOCKS5 handshake (without authentication) and read the requested target of a `CONNECT` command
+///
+/// The final reply (after connecting to the target) is left to the caller.
+async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> Result<(String, u16), Error> {
+    let [version, num_methods] = read_array(stream).await?;
+    if version != SOCKS5_VERSION {
+        return Err(Error::msg(format!("Unsupported SOCKS version {version}")));
+    }
+    let mut methods = vec![0; num_methods.into()];
+    stream.read_exact(&mut methods).await?;
+    // Only "no authentication" (0x00) is supported
+    if !methods.contains(&0) {
+        stream.write_all(&[SOCKS5_VERSION, 0xFF]).await?;
+        return Err(Error::msg("No supported authentication method"));
+    }
+    stream.write_all(&[SOCKS5_VERSION, 0]).await?;
+
+    let [_version, command, _reserved, address_type] = read_array(stream).await?;
+    let host = match address_type {
+        1 => Ipv4Addr::from(read_array::<_, 4>(stream).await?).to_string(),
+        3 => {
+            let [len] = read_array(stream).await?;
+            let mut name = vec![0; len.into()];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name)?
+        }
+        4 => Ipv6Addr::from(read_array::<_, 16>(stream).await?).to_string(),
+        _ => {
+            // Address type not supported
+            stream
+                .write_all(&[SOCKS5_VERSION, 8, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await?;
+            return Err(Error::msg(format!(
+                "Unsupported address type {address_type}"
+            )));
+        }
+    };
+    let port = u16::from_be_bytes(read_array(stream).await?);
+    if command != 1 {
+        // Command not supported
+        stream
+            .write_all(&[SOCKS5_VERSION, 7, 0, 1, 0, 0, 0, 0, 0, 0])
+            .await?;
+        return Err(Error::msg(format!("Unsupported command {command}")));
+    }
+    Ok((host, port))
+}
+
+async fn read_array<S: AsyncRead + Unpin, const N: usize>(
+    stream: &mut S,
+) -> Result<[u8; N], Error> {
+    let mut buf = [0; N];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// Perform port forwarding over SSH
+///
+/// Using the given client, connections to the local address are forwarded to the remote address (resolved by the SSH server).
+/// The forwarding stops once the returned [`PortForward`] is dropped (see [`PortForward::local`]).
+pub async fn ssh_port_forwarding<S: AsRef<str>>(
+    client: Arc<Client>,
+    local_addr: S,
+    remote_addr: S,
+) -> Result<PortForward, Error> {
+    let local_addr: SocketAddr = local_addr.as_ref().parse()?;
+    let remote_addr: SocketAddr = remote_addr.as_ref().parse()?;
+    PortForward::local(
+        &client,
+        local_addr,
+        remote_addr.ip().to_string(),
+        remote_addr.port(),
+    )
+    .await
 }
 
 #[cfg(test)]
@@ -69,6 +481,9 @@ mod test {
 
     use crate::misc::port_forwarding::ssh_port_forwarding;
 
+    use super::socks5_handshake;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
     #[tokio::test]
     async fn test_port_forwarding() {
         use crate::login_with_cfg;
@@ -76,8 +491,34 @@ mod test {
         let login_cfg = crate::misc::get_config_from_env();
         let client = login_with_cfg(&login_cfg).await.unwrap();
         let arc = Arc::new(client);
-        ssh_port_forwarding(arc, "127.0.0.1:3000", "127.0.0.1:3000")
+        let forward = ssh_port_forwarding(arc, "127.0.0.1:3000", "127.0.0.1:3000")
             .await
             .unwrap();
+        assert!(forward.is_running());
+        forward.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks5_handshake() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let handshake = tokio::spawn(async move { socks5_handshake(&mut server).await });
+        client.write_all(&[5, 1, 0]).await.unwrap();
+        let mut reply = [0; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, 0]);
+        // CONNECT to the domain name "node042" on port 8787
+        client.write_all(&[5, 1, 0, 3, 7]).await.unwrap();
+        client.write_all(b"node042").await.unwrap();
+        client.write_all(&8787u16.to_be_bytes()).await.unwrap();
+        let target = handshake.await.unwrap().unwrap();
+        assert_eq!(target, (String::from("node042"), 8787));
+
+        // Username/password authentication is not supported
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let handshake = tokio::spawn(async move { socks5_handshake(&mut server).await });
+        client.write_all(&[5, 1, 2]).await.unwrap();
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, 0xFF]);
+        assert!(handshake.await.unwrap().is_err());
     }
 }
@@ -0,0 +1,496 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+
+use crate::{ConnectionAuth, ConnectionConfig};
+
+// Maximum depth of nested `Include` directives (same as OpenSSH)
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Maximum depth of nested `ProxyJump` hosts (i.e., jump hosts of jump hosts)
+const MAX_PROXY_JUMP_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Settings of a host alias, resolved from OpenSSH config files (see `man ssh_config`)
+pub struct SshHostConfig {
+    /// The real hostname to connect to (`HostName`, defaults to the alias)
+    pub host_name: String,
+    /// The port to connect to (`Port`, defaults to `22`)
+    pub port: u16,
+    /// The username to log in as (`User`)
+    pub user: Option<String>,
+    /// Private key files to authenticate with (`IdentityFile`), with `~` and `%` tokens expanded
+    pub identity_files: Vec<PathBuf>,
+    /// Whether only the identity files (and not the keys of the SSH agent) should be used (`IdentitiesOnly`)
+    pub identities_only: bool,
+    /// Jump hosts to connect through (`ProxyJump`), e.g., `user@gateway:2222`
+    pub proxy_jump: Vec<String>,
+}
+
+impl SshHostConfig {
+    /// Resolve the settings of `alias` from the user config (`~/.ssh/config`) and the system config (`/etc/ssh/ssh_config`)
+    ///
+    /// Missing config files are ignored.
+    pub fn resolve(alias: &str) -> Result<Self, Error> {
+        let mut paths = Vec::new();
+        if let Some(home) = home_dir() {
+            paths.push(home.join(".ssh").join("config"));
+        }
+        paths.push(PathBuf::from("/etc/ssh/ssh_config"));
+        Self::resolve_from_files(alias, &paths)
+    }
+
+    /// Resolve the settings of `alias` from the given config files
+    ///
+    /// As in OpenSSH, the first value obtained for each setting is used.
+    /// Missing config files are ignored.
+    pub fn resolve_from_files<P: AsRef<Path>>(alias: &str, paths: &[P]) -> Result<Self, Error> {
+        let mut values = RawValues::default();
+        for path in paths {
+            let path = path.as_ref();
+            if !path.exists() {
+                continue;
+            }
+            let content = std::fs::read_to_string(path)?;
+            let base_dir = path.parent().unwrap_or(Path::new("."));
+            apply_config(alias, &content, base_dir, &mut values, 0)?;
+        }
+        Ok(values.finish(alias))
+    }
+
+    /// Resolve the settings of `alias` from the content of a config file
+    ///
+    /// Relative paths of `Include` directives are resolved against `base_dir`.
+    pub fn parse(alias: &str, content: &str, base_dir: &Path) -> Result<Self, Error> {
+        let mut values = RawValues::default();
+        apply_config(alias, content, base_dir, &mut values, 0)?;
+        Ok(values.finish(alias))
+    }
+}
+
+/// Build a [`ConnectionConfig`] for `alias`, resolving the settings of the alias (and of all jump hosts) using `resolve`
+pub(crate) fn connection_config_from_ssh(
+    alias: &str,
+    resolve: &dyn Fn(&str) -> Result<SshHostConfig, Error>,
+) -> Result<ConnectionConfig, Error> {
+    resolve_connection_config(alias, resolve, 0)
+}
+
+fn resolve_connection_config(
+    alias: &str,
+    resolve: &dyn Fn(&str) -> Result<SshHostConfig, Error>,
+    depth: usize,
+) -> Result<ConnectionConfig, Error> {
+    if depth > MAX_PROXY_JUMP_DEPTH {
+        return Err(Error::msg("Too many nested ProxyJump hosts."));
+    }
+    let host = resolve(alias)?;
+    let username = host.user.clone().unwrap_or_else(local_user);
+    let agent = std::env::var_os("SSH_AUTH_SOCK").is_some();
+    let auth = resolve_auth(
+        alias,
+        &host.identity_files,
+        &default_keys(),
+        host.identities_only,
+        agent,
+    )?;
+    let mut cfg = ConnectionConfig::new((host.host_name, host.port), username, auth);
+    for jump in &host.proxy_jump {
+        // [ssh://][user@]host[:port]
+        let jump = jump.strip_prefix("ssh://").unwrap_or(jump);
+        let (user, jump) = match jump.rsplit_once('@') {
+            Some((user, jump)) => (Some(user), jump),
+            None => (None, jump),
+        };
+        let (jump, port) = match jump.rsplit_once(':') {
+            Some((jump, port)) => (jump, Some(port.parse::<u16>()?)),
+            None => (jump, None),
+        };
+        let mut jump_cfg = resolve_connection_config(jump, resolve, depth + 1)?;
+        if let Some(user) = user {
+            jump_cfg.username = user.to_string();
+        }
+        if let Some(port) = port {
+            jump_cfg.host.1 = port;
+        }
+        // Jump hosts of the jump host are connected to first
+        cfg.jump_hosts.append(&mut jump_cfg.jump_hosts);
+        cfg.jump_hosts.push(jump_cfg);
+    }
+    Ok(cfg)
+}
+
+/// Authentication for `alias`, similar to OpenSSH
+///
+/// The first existing identity file is used if there is one (or if `identities_only` is set, the first existing default key).
+/// Otherwise, the keys of the SSH agent (if it is running) are tried first, falling back to the first existing default key.
+/// Keys with a passphrase are not usable this way, as the passphrase is not known.
+fn resolve_auth(
+    alias: &str,
+    identity_files: &[PathBuf],
+    default_keys: &[PathBuf],
+    identities_only: bool,
+    agent: bool,
+) -> Result<ConnectionAuth, Error> {
+    // Missing identity files are skipped (as OpenSSH does)
+    if let Some(identity_file) = identity_files.iter().find(|f| f.exists()) {
+        return Ok(key_auth(identity_file));
+    }
+    let default_key = default_keys.iter().find(|f| f.exists());
+    match (default_key, agent && !identities_only) {
+        (Some(key), true) => Ok(ConnectionAuth::Fallback {
+            methods: vec![ConnectionAuth::Agent, key_auth(key)],
+        }),
+        (None, true) => Ok(ConnectionAuth::Agent),
+        (Some(key), false) => Ok(key_auth(key)),
+        (None, false) => Err(Error::msg(format!(
+            "No existing IdentityFile or running SSH agent found for host {alias}."
+        ))),
+    }
+}
+
+/// Default private key files of the user (`~/.ssh/id_*`), in the order OpenSSH tries them
+fn default_keys() -> Vec<PathBuf> {
+    let Some(home) = home_dir() else {
+        return Vec::new();
+    };
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|name| home.join(".ssh").join(name))
+        .collect()
+}
+
+fn key_auth(key_file: &Path) -> ConnectionAuth {
+    let path = key_file.to_string_lossy().to_string();
+    // Use the certificate of the key, if there is one (as OpenSSH does)
+    if Path::new(&format!("{path}-cert.pub")).exists() {
+        ConnectionAuth::SSHCertificate {
+            path,
+            passphrase: None,
+            certificate_path: None,
+        }
+    } else {
+        ConnectionAuth::SSHKey {
+            path,
+            passphrase: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RawValues {
+    host_name: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    identity_files: Vec<String>,
+    identities_only: Option<bool>,
+    proxy_jump: Option<String>,
+}
+
+impl RawValues {
+    fn finish(self, alias: &str) -> SshHostConfig {
+        let host_name = self
+            .host_name
+            .map(|h| expand_tokens(&h, alias, alias, None))
+            .unwrap_or_else(|| alias.to_string());
+        let user = self.user;
+        let identity_files = self
+            .identity_files
+            .iter()
+            .map(|f| PathBuf::from(expand_tokens(f, alias, &host_name, user.as_deref())))
+            .collect();
+        let proxy_jump = match self.proxy_jump.as_deref() {
+            None | Some("none") => Vec::new(),
+            Some(jumps) => jumps.split(',').map(|j| j.trim().to_string()).collect(),
+        };
+        SshHostConfig {
+            host_name,
+            port: self.port.unwrap_or(22),
+            user,
+            identity_files,
+            identities_only: self.identities_only.unwrap_or_default(),
+            proxy_jump,
+        }
+    }
+}
+
+fn apply_config(
+    alias: &str,
+    content: &str,
+    base_dir: &Path,
+    values: &mut RawValues,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::msg("Too many nested Include directives."));
+    }
+    // Settings before the first Host block apply to all hosts
+    let mut active = true;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, args) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((keyword, args)) => (keyword, args.trim_start_matches([' ', '\t', '='])),
+            None => (line, ""),
+        };
+        let args = split_args(args);
+        match keyword.to_lowercase().as_str() {
+            "host" => active = host_matches(alias, &args),
+            // Match criteria are not supported, except for `Match all`
+            "match" => active = args.iter().all(|a| a.eq_ignore_ascii_case("all")),
+            _ if !active => {}
+            "include" => {
+                for pattern in &args {
+                    let pattern = expand_home(pattern);
+                    let pattern = if Path::new(&pattern).is_absolute() {
+                        pattern
+                    } else {
+                        base_dir.join(pattern).to_string_lossy().to_string()
+                    };
+                    let mut paths: Vec<_> = glob::glob(&pattern)?.filter_map(Result::ok).collect();
+                    paths.sort();
+                    for path in paths {
+                        let content = std::fs::read_to_string(&path)?;
+                        apply_config(alias, &content, base_dir, values, depth + 1)?;
+                    }
+                }
+            }
+            "hostname" => {
+                values.host_name = values.host_name.take().or(args.first().cloned());
+            }
+            "port" if values.port.is_none() => {
+                if let Some(port) = args.first() {
+                    values.port = Some(port.parse()?);
+                }
+            }
+            "user" => values.user = values.user.take().or(args.first().cloned()),
+            "identityfile" => values.identity_files.extend(args.first().cloned()),
+            "identitiesonly" if values.identities_only.is_none() => {
+                values.identities_only = args.first().map(|a| a.eq_ignore_ascii_case("yes"));
+            }
+            "proxyjump" => {
+                values.proxy_jump = values.proxy_jump.take().or(args.first().cloned());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Split arguments at whitespace, respecting double quotes
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in s.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+// A host matches if any pattern matches and no negated pattern (e.g., `!login*`) matches
+fn host_matches(alias: &str, patterns: &[String]) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(pattern) if wildcard_match(pattern, alias) => return false,
+            Some(_) => {}
+            None => matched |= wildcard_match(pattern, alias),
+        }
+    }
+    matched
+}
+
+// Match with `*` (any sequence) and `?` (any single character) wildcards
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let s: Vec<char> = s.to_lowercase().chars().collect();
+    let (mut p, mut i) = (0, 0);
+    // Position of the last `*` in the pattern and the matching position in `s`
+    let mut backtrack: Option<(usize, usize)> = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, i));
+            p += 1;
+        } else if let Some((star, pos)) = backtrack {
+            p = star + 1;
+            i = pos + 1;
+            backtrack = Some((star, pos + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn local_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+// Expand `~` and the `%d`, `%h`, `%n`, `%r`, `%u` and `%%` tokens
+fn expand_tokens(s: &str, alias: &str, host_name: &str, user: Option<&str>) -> String {
+    let local_user = local_user();
+    let home = home_dir()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let s = expand_home(s);
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => res.push('%'),
+            Some('d') => res.push_str(&home),
+            Some('h') => res.push_str(host_name),
+            Some('n') => res.push_str(alias),
+            Some('r') => res.push_str(user.unwrap_or(&local_user)),
+            Some('u') => res.push_str(&local_user),
+            Some(c) => {
+                res.push('%');
+                res.push(c);
+            }
+            None => res.push('%'),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{ssh::SshHostConfig, ConnectionAuth, ConnectionConfig};
+
+    use super::resolve_auth;
+
+    #[test]
+    fn test_parse_ssh_config() {
+        let dir = std::env::temp_dir().join("slurry_test_ssh_config");
+        std::fs::create_dir_all(dir.join("config.d")).unwrap();
+        std::fs::write(
+            dir.join("config.d").join("cluster"),
+            "Host hpc login*\n  HostName %h.cluster.example.org\n  User ab123456\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("hpc key"), "").unwrap();
+        std::fs::write(dir.join("default"), "").unwrap();
+        let config = r#"
+Include config.d/*
+
+Host gateway
+    HostName gw.example.org
+    Port 2222
+
+Host hpc !login2
+    Port=2200
+    IdentitiesOnly yes
+    IdentityFile "{dir}/hpc key"
+    ProxyJump gateway,jump@bastion:22
+
+Host *
+    User fallback
+    IdentityFile {dir}/default
+"#
+        .replace("{dir}", dir.to_str().unwrap());
+        let config = config.as_str();
+        let hpc = SshHostConfig::parse("hpc", config, &dir).unwrap();
+        assert_eq!(
+            hpc,
+            SshHostConfig {
+                host_name: String::from("hpc.cluster.example.org"),
+                port: 2200,
+                user: Some(String::from("ab123456")),
+                identity_files: vec![dir.join("hpc key"), dir.join("default")],
+                identities_only: true,
+                proxy_jump: vec![String::from("gateway"), String::from("jump@bastion:22")],
+            }
+        );
+
+        let login2 = SshHostConfig::parse("login2", config, &dir).unwrap();
+        assert_eq!(login2.host_name, "login2.cluster.example.org");
+        assert_eq!(login2.port, 22);
+        assert!(login2.proxy_jump.is_empty());
+
+        let gateway = SshHostConfig::parse("gateway", config, &dir).unwrap();
+        assert_eq!(gateway.host_name, "gw.example.org");
+        assert_eq!(gateway.port, 2222);
+        assert_eq!(gateway.user.as_deref(), Some("fallback"));
+
+        let config_path = dir.join("config");
+        std::fs::write(&config_path, config).unwrap();
+        let cfg = ConnectionConfig::from_ssh_config_files("hpc", &[&config_path]).unwrap();
+        assert_eq!(cfg.host, (String::from("hpc.cluster.example.org"), 2200));
+        let jumps: Vec<_> = cfg
+            .jump_hosts
+            .iter()
+            .map(|j| (j.host.clone(), j.username.as_str()))
+            .collect();
+        assert_eq!(
+            jumps,
+            vec![
+                ((String::from("gw.example.org"), 2222), "fallback"),
+                ((String::from("bastion"), 22), "jump")
+            ]
+        );
+
+        assert!(!gateway.identities_only);
+
+        // Existing identity files are used even if the agent is running, missing ones are skipped
+        let identity_files = [PathBuf::from("/missing/key"), dir.join("hpc key")];
+        let key_path = dir.join("hpc key").to_string_lossy().to_string();
+        for agent in [false, true] {
+            let auth = resolve_auth("hpc", &identity_files, &[], false, agent).unwrap();
+            assert!(matches!(auth, ConnectionAuth::SSHKey { ref path, .. } if *path == key_path));
+        }
+        // Otherwise, the agent is tried before the default keys
+        let default_keys = [dir.join("default")];
+        let auth = resolve_auth("hpc", &[], &default_keys, false, true).unwrap();
+        assert!(matches!(
+            auth,
+            ConnectionAuth::Fallback { ref methods } if matches!(
+                methods.as_slice(),
+                [ConnectionAuth::Agent, ConnectionAuth::SSHKey { .. }]
+            )
+        ));
+        let auth = resolve_auth("hpc", &[], &[], false, true).unwrap();
+        assert!(matches!(auth, ConnectionAuth::Agent));
+        let auth = resolve_auth("hpc", &[], &default_keys, true, true).unwrap();
+        assert!(matches!(auth, ConnectionAuth::SSHKey { .. }));
+        assert!(resolve_auth("hpc", &[], &[], true, true).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fmt::Display, future::Future, pin::Pin, sync::Arc};
+
+use russh_keys::{key::PublicKey, PublicKeyBase64};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode")]
+/// How to verify the host key of an SSH server
+pub enum HostKeyCheck {
+    #[serde(rename = "no-check")]
+    /// Accept any host key
+    ///
+    /// This is insecure, as it allows man-in-the-middle attacks!
+    NoCheck,
+    #[serde(rename = "known-hosts")]
+    /// Only accept host keys listed in a `known_hosts` file
+    KnownHosts {
+        /// Path to the `known_hosts` file (defaults to `~/.ssh/known_hosts`)
+        path: Option<String>,
+    },
+    #[serde(rename = "trust-on-first-use")]
+    /// Accept host keys listed in a `known_hosts` file, and ask whether to trust the host key of unknown hosts
+    ///
+    /// Unknown host keys are passed to the [`HostKeyCallback`] of the connection.
+    /// Without a callback, unknown host keys are rejected (as with [`HostKeyCheck::KnownHosts`]).
+    /// Accepted host keys are added to the `known_hosts` file.
+    /// Changed host keys are always rejected.
+    TrustOnFirstUse {
+        /// Path to the `known_hosts` file (defaults to `~/.ssh/known_hosts`)
+        path: Option<String>,
+    },
+    #[serde(rename = "public-key")]
+    /// Only accept the given public key
+    PublicKey {
+        /// The public key in OpenSSH format (e.g., `ssh-ed25519 AAAA...`) or only its base64 part
+        key: String,
+    },
+    #[serde(rename = "fingerprint")]
+    /// Only accept a public key with the given SHA256 fingerprint (e.g., `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`)
+    Fingerprint {
+        /// The SHA256 fingerprint, as shown by `ssh-keygen -l`
+        fingerprint: String,
+    },
+}
+
+impl Default for HostKeyCheck {
+    fn default() -> Self {
+        Self::TrustOnFirstUse { path: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Information about the host key of an SSH server
+pub struct HostKeyInfo {
+    /// The host (as passed in the connection config)
+    pub host: String,
+    /// The port of the SSH server
+    pub port: u16,
+    /// The key algorithm (e.g., `ssh-ed25519`)
+    pub algorithm: String,
+    /// The SHA256 fingerprint of the key (e.g., `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`)
+    pub fingerprint: String,
+    /// The base64-encoded public key
+    pub key: String,
+}
+
+impl HostKeyInfo {
+    fn new(host: &str, port: u16, key: &PublicKey) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            algorithm: key.name().to_string(),
+            fingerprint: format!("SHA256:{}", key.fingerprint()),
+            key: key.public_key_base64(),
+        }
+    }
+}
+
+type HostKeyCallbackFn =
+    dyn Fn(HostKeyInfo) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync;
+
+#[derive(Clone)]
+/// Callback deciding whether to trust the host key of an unknown host (e.g., by asking the user)
+///
+/// Used for [`HostKeyCheck::TrustOnFirstUse`].
+pub struct HostKeyCallback(Arc<HostKeyCallbackFn>);
+
+impl HostKeyCallback {
+    /// Create a new callback, which resolves to `true` if the host key should be trusted
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn(HostKeyInfo) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        Self(Arc::new(move |info| Box::pin(f(info))))
+    }
+}
+
+impl std::fmt::Debug for HostKeyCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HostKeyCallback").finish()
+    }
+}
+
+#[derive(Debug)]
+/// Error when verifying the host key of an SSH server
+pub enum HostKeyError {
+    /// The host is not listed in the `known_hosts` file
+    Unknown(HostKeyInfo),
+    /// The host is listed in the `known_hosts` file with a different key
+    ///
+    /// This might indicate a man-in-the-middle attack!
+    Changed {
+        /// The host key sent by the server
+        info: HostKeyInfo,
+        /// Line of the `known_hosts` file containing the previous key
+        line: usize,
+    },
+    /// The host key does not match the pinned public key or fingerprint
+    Mismatch(HostKeyInfo),
+    /// The host key was rejected by the [`HostKeyCallback`]
+    Rejected(HostKeyInfo),
+    /// The `known_hosts` file or pinned key could not be read
+    Invalid(String),
+}
+
+impl Display for HostKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostKeyError::Unknown(info) => write!(
+                f,
+                "Unknown host key for {} ({})",
+                info.host, info.fingerprint
+            ),
+            HostKeyError::Changed { info, line } => write!(
+                f,
+                "Host key for {} changed to {} (known_hosts line {line}). This might be a man-in-the-middle attack!",
+                info.host, info.fingerprint
+            ),
+            HostKeyError::Mismatch(info) => write!(
+                f,
+                "Host key {} of {} does not match the expected key",
+                info.fingerprint, info.host
+            ),
+            HostKeyError::Rejected(info) => write!(
+                f,
+                "Host key {} of {} was rejected",
+                info.fingerprint, info.host
+            ),
+            HostKeyError::Invalid(msg) => write!(f, "Could not verify host key: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for HostKeyError {}
+
+/// Verify the host key of an SSH server according to the passed [`HostKeyCheck`]
+pub(crate) async fn verify_host_key(
+    host: &str,
+    port: u16,
+    key: &PublicKey,
+    check: &HostKeyCheck,
+    callback: Option<&HostKeyCallback>,
+) -> Result<(), HostKeyError> {
+    let info = HostKeyInfo::new(host, port, key);
+    match check {
+        HostKeyCheck::NoCheck => Ok(()),
+        HostKeyCheck::PublicKey { key: expected } => {
+            // Allow both `ssh-ed25519 AAAA... comment` and only `AAAA...`
+            let mut parts = expected.split_whitespace();
+            let base64 = match (parts.next(), parts.next()) {
+                (Some(_), Some(base64)) => base64,
+                (Some(base64), None) => base64,
+                _ => return Err(HostKeyError::Invalid(String::from("Empty public key"))),
+            };
+            let expected = russh_keys::parse_public_key_base64(base64)
+                .map_err(|e| HostKeyError::Invalid(e.to_string()))?;
+            if expected == *key {
+                Ok(())
+            } else {
+                Err(HostKeyError::Mismatch(info))
+            }
+        }
+        HostKeyCheck::Fingerprint { fingerprint } => {
+            let expected = fingerprint.trim();
+            let expected = expected.strip_prefix("SHA256:").unwrap_or(expected);
+            if expected.trim_end_matches('=') == key.fingerprint() {
+                Ok(())
+            } else {
+                Err(HostKeyError::Mismatch(info))
+            }
+        }
+        HostKeyCheck::KnownHosts { path } | HostKeyCheck::TrustOnFirstUse { path } => {
+            let known = match path {
+                Some(path) => russh_keys::check_known_hosts_path(host, port, key, path),
+                None => russh_keys::check_known_hosts(host, port, key),
+            };
+            match known {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(russh_keys::Error::KeyChanged { line }) => {
+                    return Err(HostKeyError::Changed { info, line })
+                }
+                Err(e) => return Err(HostKeyError::Invalid(e.to_string())),
+            }
+            let callback = match (check, callback) {
+                (HostKeyCheck::TrustOnFirstUse { .. }, Some(callback)) => callback,
+                _ => return Err(HostKeyError::Unknown(info)),
+            };
+            if !(callback.0)(info.clone()).await {
+                return Err(HostKeyError::Rejected(info));
+            }
+            match path {
+                Some(path) => russh_keys::learn_known_hosts_path(host, port, key, path),
+                None => russh_keys::learn_known_hosts(host, port, key),
+            }
+            .map_err(|e| HostKeyError::Invalid(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use russh_keys::key::PublicKey;
+
+    use crate::ssh::{verify_host_key, HostKeyCallback, HostKeyCheck, HostKeyError};
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAINs8VGpzg37Eib95Vxiz+dSGvDlXHVq+ipnfBq1u8FYB";
+    const OTHER_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIFz8cJOt6r1lCfYbclOjlosONIMiHr3xfziFQewGbCUl";
+
+    fn key(s: &str) -> PublicKey {
+        russh_keys::parse_public_key_base64(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pinned_host_key() {
+        let check = HostKeyCheck::PublicKey {
+            key: format!("ssh-ed25519 {KEY} cluster"),
+        };
+        assert!(verify_host_key("hpc", 22, &key(KEY), &check, None)
+            .await
+            .is_ok());
+        assert!(matches!(
+            verify_host_key("hpc", 22, &key(OTHER_KEY), &check, None).await,
+            Err(HostKeyError::Mismatch(_))
+        ));
+
+        let check = HostKeyCheck::Fingerprint {
+            fingerprint: format!("SHA256:{}", key(KEY).fingerprint()),
+        };
+        assert!(verify_host_key("hpc", 22, &key(KEY), &check, None)
+            .await
+            .is_ok());
+        assert!(verify_host_key("hpc", 22, &key(OTHER_KEY), &check, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trust_on_first_use() {
+        let path = std::env::temp_dir().join("slurry_test_known_hosts");
+        let _ = std::fs::remove_file(&path);
+        let path_str = path.to_string_lossy().to_string();
+
+        let strict = HostKeyCheck::KnownHosts {
+            path: Some(path_str.clone()),
+        };
+        assert!(matches!(
+            verify_host_key("hpc", 2222, &key(KEY), &strict, None).await,
+            Err(HostKeyError::Unknown(_))
+        ));
+
+        let tofu = HostKeyCheck::TrustOnFirstUse {
+            path: Some(path_str),
+        };
+        // Without a callback, unknown keys are not trusted
+        assert!(matches!(
+            verify_host_key("hpc", 2222, &key(KEY), &tofu, None).await,
+            Err(HostKeyError::Unknown(_))
+        ));
+        let reject = HostKeyCallback::new(|_| async { false });
+        assert!(matches!(
+            verify_host_key("hpc", 2222, &key(KEY), &tofu, Some(&reject)).await,
+            Err(HostKeyError::Rejected(_))
+        ));
+        let accept = HostKeyCallback::new(|info| async move { info.host == "hpc" });
+        verify_host_key("hpc", 2222, &key(KEY), &tofu, Some(&accept))
+            .await
+            .unwrap();
+
+        // Now the key is known, but a changed key is rejected
+        assert!(verify_host_key("hpc", 2222, &key(KEY), &strict, None)
+            .await
+            .is_ok());
+        assert!(matches!(
+            verify_host_key("hpc", 2222, &key(OTHER_KEY), &tofu, Some(&accept)).await,
+            Err(HostKeyError::Changed { .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use anyhow::Error;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Answer to keyboard-interactive prompts matching a regular expression
+pub struct PromptResponse {
+    /// Regular expression matched against the prompt (e.g., `(?i)verification code`)
+    pub prompt: String,
+    /// The answer to send
+    pub response: String,
+}
+
+impl PromptResponse {
+    /// Answer prompts matching the regular expression `prompt` with `response`
+    pub fn new(prompt: impl Into<String>, response: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            response: response.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Responses for keyboard-interactive authentication
+///
+/// Each response is used at most once, for the first prompt matching its regular expression.
+/// Prompts without a matching response are passed to the [`KeyboardInteractiveCallback`] of the connection (if set).
+pub struct AuthKeyboardInteractive {
+    responses: Vec<(Regex, String)>,
+}
+
+impl PartialEq for AuthKeyboardInteractive {
+    fn eq(&self, other: &Self) -> bool {
+        // Regular expressions are equal if their patterns are
+        self.responses.len() == other.responses.len()
+            && self
+                .responses
+                .iter()
+                .zip(&other.responses)
+                .all(|((a, a_resp), (b, b_resp))| a.as_str() == b.as_str() && a_resp == b_resp)
+    }
+}
+
+impl Eq for AuthKeyboardInteractive {}
+
+impl AuthKeyboardInteractive {
+    /// Create new keyboard-interactive authentication settings without any responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the first prompt matching the regular expression `prompt` with `response`
+    ///
+    /// Fails if `prompt` is not a valid regular expression.
+    pub fn with_response(
+        mut self,
+        prompt: impl AsRef<str>,
+        response: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let prompt = prompt.as_ref();
+        let regex = Regex::new(prompt)
+            .map_err(|e| Error::from(e).context(format!("Invalid prompt pattern {prompt}")))?;
+        self.responses.push((regex, response.into()));
+        Ok(self)
+    }
+
+    /// Answer the prompts of one info request (in order)
+    pub(crate) async fn answer(
+        &mut self,
+        request: &KeyboardInteractiveRequest,
+        callback: Option<&KeyboardInteractiveCallback>,
+    ) -> Result<Vec<String>, Error> {
+        let mut answers = Vec::with_capacity(request.prompts.len());
+        for (prompt, echo) in &request.prompts {
+            let matched = self
+                .responses
+                .iter()
+                .position(|(regex, _)| regex.is_match(prompt));
+            let answer = match (matched, callback) {
+                (Some(i), _) => self.responses.remove(i).1,
+                (None, Some(callback)) => (callback.0)(KeyboardInteractivePrompt {
+                    host: request.host.clone(),
+                    name: request.name.clone(),
+                    instructions: request.instructions.clone(),
+                    prompt: prompt.clone(),
+                    echo: *echo,
+                })
+                .await
+                .ok_or_else(|| Error::msg(format!("Login aborted at prompt {prompt}")))?,
+                (None, None) => return Err(Error::msg(format!("No response for prompt {prompt}"))),
+            };
+            answers.push(answer);
+        }
+        Ok(answers)
+    }
+}
+
+/// Info request of the server (i.e., prompts with whether the answer may be echoed)
+#[derive(Debug)]
+pub(crate) struct KeyboardInteractiveRequest {
+    pub(crate) host: String,
+    pub(crate) name: String,
+    pub(crate) instructions: String,
+    pub(crate) prompts: Vec<(String, bool)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A keyboard-interactive prompt of the server (e.g., `Verification code: `)
+pub struct KeyboardInteractivePrompt {
+    /// The host asking (as passed in the connection config, e.g., a jump host)
+    pub host: String,
+    /// Name of the info request (might be empty)
+    pub name: String,
+    /// Instructions of the info request (might be empty)
+    pub instructions: String,
+    /// The prompt itself
+    pub prompt: String,
+    /// Whether the answer may be displayed while typing (i.e., it is not secret)
+    pub echo: bool,
+}
+
+type KeyboardInteractiveCallbackFn = dyn Fn(KeyboardInteractivePrompt) -> Pin<Box<dyn Future<Output = Option<String>> + Send>>
+    + Send
+    + Sync;
+
+#[derive(Clone)]
+/// Callback asked for the answer to a keyboard-interactive prompt without configured response (e.g., by asking the user)
+pub struct KeyboardInteractiveCallback(Arc<KeyboardInteractiveCallbackFn>);
+
+impl KeyboardInteractiveCallback {
+    /// Create a new callback, which resolves to the answer, or `None` to abort the login
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn(KeyboardInteractivePrompt) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        Self(Arc::new(move |prompt| Box::pin(f(prompt))))
+    }
+}
+
+impl std::fmt::Debug for KeyboardInteractiveCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("KeyboardInteractiveCallback").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ssh::{
+        keyboard_interactive::KeyboardInteractiveRequest, AuthKeyboardInteractive,
+        KeyboardInteractiveCallback,
+    };
+
+    fn request(prompts: &[&str]) -> KeyboardInteractiveRequest {
+        KeyboardInteractiveRequest {
+            host: String::from("hpc"),
+            name: String::new(),
+            instructions: String::new(),
+            prompts: prompts.iter().map(|p| (p.to_string(), false)).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_prompts() {
+        let mut auth = AuthKeyboardInteractive::new()
+            .with_response("(?i)password", "hunter2")
+            .and_then(|auth| auth.with_response("^Verification code:", "123456"))
+            .unwrap();
+        let answers = auth
+            .answer(&request(&["Password: ", "Verification code: "]), None)
+            .await
+            .unwrap();
+        assert_eq!(answers, vec!["hunter2", "123456"]);
+        // Responses are only used once
+        assert!(auth.answer(&request(&["Password: "]), None).await.is_err());
+
+        let callback = KeyboardInteractiveCallback::new(|prompt| async move {
+            prompt
+                .prompt
+                .starts_with("PIN")
+                .then(|| String::from("0000"))
+        });
+        let answers = auth
+            .answer(&request(&["PIN: "]), Some(&callback))
+            .await
+            .unwrap();
+        assert_eq!(answers, vec!["0000"]);
+        assert!(auth
+            .answer(&request(&["Favorite color? "]), Some(&callback))
+            .await
+            .is_err());
+
+        // Invalid patterns are rejected when building the responses
+        assert!(AuthKeyboardInteractive::new()
+            .with_response("(unclosed", "secret")
+            .is_err());
+    }
+}
@@ -0,0 +1,626 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::Path,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Error;
+use russh::{
+    client::{Config, Handle, Handler, KeyboardInteractiveAuthResponse, Msg},
+    Channel, ChannelMsg,
+};
+use russh_sftp::client::SftpSession;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, RwLock},
+};
+
+use crate::{executor::CommandOutput, ConnectionConfig};
+
+/// Verification of SSH host keys (e.g., using `known_hosts` files)
+pub mod host_keys;
+
+/// Parsing of OpenSSH config files (i.e., `~/.ssh/config`)
+pub mod config;
+
+/// Keyboard-interactive authentication (e.g., password and MFA prompts)
+pub mod keyboard_interactive;
+
+/// SSH sessions with keepalives and automatic reconnection
+pub mod session;
+
+/// Sharing SSH connections between concurrent operations
+pub mod pool;
+
+/// Syncing files and directories over SFTP (e.g., skipping unchanged files, resuming interrupted transfers)
+pub mod transfer;
+
+pub(crate) use config::connection_config_from_ssh;
+pub use config::SshHostConfig;
+pub(crate) use host_keys::verify_host_key;
+pub use host_keys::{HostKeyCallback, HostKeyCheck, HostKeyError, HostKeyInfo};
+use keyboard_interactive::KeyboardInteractiveRequest;
+pub use keyboard_interactive::{
+    AuthKeyboardInteractive, KeyboardInteractiveCallback, KeyboardInteractivePrompt, PromptResponse,
+};
+pub use pool::{SessionPool, DEFAULT_MAX_SESSIONS};
+pub use session::{ReconnectOptions, ResilientSession, SessionEvent, SessionEventHandler};
+pub use transfer::{
+    SyncCompare, SyncOptions, SyncReport, TransferProgress, TransferProgressCallback,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Method for authenticating an SSH connection
+pub enum AuthMethod {
+    /// Password authentication
+    Password(String),
+    /// Public key authentication using a private key
+    PrivateKey {
+        /// Content of the private key (e.g., in OpenSSH format)
+        key_data: String,
+        /// Optional passphrase of the private key
+        key_pass: Option<String>,
+    },
+    /// Public key authentication using a private key file
+    PrivateKeyFile {
+        /// Path to the private key file
+        key_file_path: PathBuf,
+        /// Optional passphrase of the private key
+        key_pass: Option<String>,
+    },
+    /// Public key authentication using an OpenSSH user certificate and its private key
+    Certificate {
+        /// Content of the private key (e.g., in OpenSSH format)
+        key_data: String,
+        /// Optional passphrase of the private key
+        key_pass: Option<String>,
+        /// Content of the certificate (e.g., `ssh-ed25519-cert-v01@openssh.com AAAA...`)
+        cert_data: String,
+    },
+    /// Public key authentication using an OpenSSH user certificate file and its private key file
+    CertificateFile {
+        /// Path to the private key file
+        key_file_path: PathBuf,
+        /// Optional passphrase of the private key
+        key_pass: Option<String>,
+        /// Path to the certificate file (e.g., `~/.ssh/id_ed25519-cert.pub`)
+        cert_file_path: PathBuf,
+    },
+    /// Public key authentication using the keys of the SSH agent (see `SSH_AUTH_SOCK`)
+    Agent,
+    /// Keyboard-interactive authentication (e.g., password and MFA code)
+    KeyboardInteractive(AuthKeyboardInteractive),
+    /// Try the given methods in order until one of them succeeds
+    Fallback(Vec<AuthMethod>),
+}
+
+impl AuthMethod {
+    /// Authenticate using the given password
+    pub fn with_password(password: &str) -> Self {
+        Self::Password(password.to_string())
+    }
+
+    /// Authenticate using the given private key (and optional passphrase)
+    pub fn with_key(key: &str, passphrase: Option<&str>) -> Self {
+        Self::PrivateKey {
+            key_data: key.to_string(),
+            key_pass: passphrase.map(str::to_string),
+        }
+    }
+
+    /// Authenticate using the private key stored at the given path (and optional passphrase)
+    pub fn with_key_file<T: AsRef<Path>>(key_file_path: T, passphrase: Option<&str>) -> Self {
+        Self::PrivateKeyFile {
+            key_file_path: key_file_path.as_ref().to_path_buf(),
+            key_pass: passphrase.map(str::to_string),
+        }
+    }
+
+    /// Authenticate using the given OpenSSH user certificate and its private key (and optional passphrase)
+    pub fn with_certificate(key: &str, passphrase: Option<&str>, certificate: &str) -> Self {
+        Self::Certificate {
+            key_data: key.to_string(),
+            key_pass: passphrase.map(str::to_string),
+            cert_data: certificate.to_string(),
+        }
+    }
+
+    /// Authenticate using the OpenSSH user certificate and private key stored at the given paths (and optional passphrase)
+    pub fn with_certificate_file<T: AsRef<Path>, C: AsRef<Path>>(
+        key_file_path: T,
+        passphrase: Option<&str>,
+        cert_file_path: C,
+    ) -> Self {
+        Self::CertificateFile {
+            key_file_path: key_file_path.as_ref().to_path_buf(),
+            key_pass: passphrase.map(str::to_string),
+            cert_file_path: cert_file_path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Authenticate using the keys of the running SSH agent
+    pub fn with_agent() -> Self {
+        Self::Agent
+    }
+
+    /// Authenticate using keyboard-interactive authentication
+    pub fn with_keyboard_interactive(auth: AuthKeyboardInteractive) -> Self {
+        Self::KeyboardInteractive(auth)
+    }
+
+    /// Authenticate using the first of the given methods which succeeds (e.g., the SSH agent, then a key file)
+    pub fn with_fallback(methods: Vec<AuthMethod>) -> Self {
+        Self::Fallback(methods)
+    }
+}
+
+#[derive(Clone)]
+/// An authenticated SSH connection
+///
+/// Cloning the client is cheap, all clones share the same connection.
+pub struct Client {
+    // Requesting remote port forwardings requires exclusive access
+    handle: Arc<RwLock<Handle<ClientHandler>>>,
+    remote_forwards: RemoteForwards,
+    username: String,
+    host: (String, u16),
+    // Connections to the jump hosts, which have to be kept alive
+    jump_hosts: Arc<Vec<Client>>,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("username", &self.username)
+            .field("host", &self.host)
+            .field("jump_hosts", &self.jump_hosts)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Channel of a connection to a remote port forwarding, with the address of the connecting peer
+pub type ForwardedChannel = (Channel<Msg>, String);
+
+/// Receivers of the channels of remote port forwardings (by the port the server listens on)
+type RemoteForwards = Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<ForwardedChannel>>>>;
+
+struct ClientHandler {
+    host: (String, u16),
+    host_key_check: HostKeyCheck,
+    host_key_callback: Option<HostKeyCallback>,
+    remote_forwards: RemoteForwards,
+}
+
+#[async_trait::async_trait]
+impl Handler for ClientHandler {
+    type Error = Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &russh_keys::key::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        verify_host_key(
+            &self.host.0,
+            self.host.1,
+            server_public_key,
+            &self.host_key_check,
+            self.host_key_callback.as_ref(),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        originator_address: &str,
+        originator_port: u32,
+        _session: &mut russh::client::Session,
+    ) -> Result<(), Self::Error> {
+        let sender = self
+            .remote_forwards
+            .lock()
+            .unwrap()
+            .get(&connected_port)
+            .cloned();
+        let channel = match sender {
+            Some(sender) => {
+                let peer = format!("{originator_address}:{originator_port}");
+                match sender.send((channel, peer)) {
+                    Ok(()) => return Ok(()),
+                    Err(e) => e.0 .0,
+                }
+            }
+            None => channel,
+        };
+        // Nobody is interested in this connection (anymore)
+        channel.close().await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Connect and authenticate using the given configuration
+    ///
+    /// If the configuration contains jump hosts, they are connected to in order,
+    /// each tunnelling the next connection through a `direct-tcpip` channel.
+    pub async fn connect(cfg: &ConnectionConfig) -> Result<Self, Error> {
+        Self::connect_with_config(cfg, Config::default()).await
+    }
+
+    /// Connect and authenticate using the given configuration and SSH protocol settings (e.g., timeouts)
+    pub async fn connect_with_config(
+        cfg: &ConnectionConfig,
+        ssh_config: Config,
+    ) -> Result<Self, Error> {
+        let ssh_config = Arc::new(ssh_config);
+        // Jump hosts of jump hosts are not considered, all hops have to be listed in cfg.jump_hosts
+        let all_hops: Vec<&ConnectionConfig> =
+            cfg.jump_hosts.iter().chain(std::iter::once(cfg)).collect();
+        // Reject invalid authentication settings (e.g., prompt patterns) before connecting anywhere
+        let auths = all_hops
+            .iter()
+            .map(|hop| {
+                AuthMethod::try_from(&hop.auth)
+                    .map_err(|e| e.context(format!("Invalid authentication for {}", hop.host.0)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut hops: Vec<Client> = Vec::with_capacity(all_hops.len());
+        for (hop, auth) in all_hops.into_iter().zip(auths) {
+            let handler = ClientHandler {
+                host: hop.host.clone(),
+                host_key_check: hop.host_key_check.clone(),
+                host_key_callback: hop
+                    .host_key_callback
+                    .clone()
+                    .or_else(|| cfg.host_key_callback.clone()),
+                remote_forwards: RemoteForwards::default(),
+            };
+            let remote_forwards = Arc::clone(&handler.remote_forwards);
+            let mut handle = match hops.last() {
+                None => {
+                    russh::client::connect(
+                        ssh_config.clone(),
+                        (hop.host.0.as_str(), hop.host.1),
+                        handler,
+                    )
+                    .await?
+                }
+                Some(prev) => {
+                    let channel = prev
+                        .open_direct_tcpip_channel(hop.host.0.clone(), hop.host.1, None)
+                        .await
+                        .map_err(|e| {
+                            e.context(format!(
+                                "Could not reach {} via {}",
+                                hop.host.0, prev.host.0
+                            ))
+                        })?;
+                    russh::client::connect_stream(
+                        ssh_config.clone(),
+                        channel.into_stream(),
+                        handler,
+                    )
+                    .await?
+                }
+            };
+            let kbd_callback = hop
+                .keyboard_interactive_callback
+                .as_ref()
+                .or(cfg.keyboard_interactive_callback.as_ref());
+            authenticate(&mut handle, &hop.host.0, &hop.username, auth, kbd_callback)
+                .await
+                .map_err(|e| e.context(format!("Could not log in to {}", hop.host.0)))?;
+            hops.push(Self {
+                handle: Arc::new(RwLock::new(handle)),
+                remote_forwards,
+                username: hop.username.clone(),
+                host: hop.host.clone(),
+                jump_hosts: Arc::new(Vec::new()),
+            });
+        }
+        let mut client = hops.pop().expect("Target host is always connected");
+        client.jump_hosts = Arc::new(hops);
+        Ok(client)
+    }
+
+    /// The username used for logging in
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// The host (hostname and port) this client is connected to
+    pub fn host(&self) -> &(String, u16) {
+        &self.host
+    }
+
+    /// Open a new session channel
+    pub async fn get_channel(&self) -> Result<Channel<Msg>, Error> {
+        let channel = self.handle.read().await.channel_open_session().await?;
+        Ok(channel)
+    }
+
+    /// Open a `direct-tcpip` channel to the given host and port (resolved by the SSH server)
+    pub async fn open_direct_tcpip_channel(
+        &self,
+        host: impl Into<String>,
+        port: u16,
+        src: Option<SocketAddr>,
+    ) -> Result<Channel<Msg>, Error> {
+        let (src_addr, src_port) = src
+            .map(|src| (src.ip().to_string(), src.port()))
+            .unwrap_or_else(|| (String::from("127.0.0.1"), 22));
+        let channel = self
+            .handle
+            .read()
+            .await
+            .channel_open_direct_tcpip(host, port.into(), src_addr, src_port.into())
+            .await?;
+        Ok(channel)
+    }
+
+    /// Ask the server to listen on the given address and port (`0` to let the server choose a port),
+    /// forwarding incoming connections to this client (like `ssh -R`)
+    ///
+    /// Returns the port the server listens on and a receiver for the channels of incoming connections (with the address of the connecting peer).
+    /// The forwarding stops once [`Client::cancel_remote_forward`] is called.
+    pub async fn request_remote_forward(
+        &self,
+        address: &str,
+        port: u16,
+    ) -> Result<(u16, mpsc::UnboundedReceiver<ForwardedChannel>), Error> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // Register known ports before requesting the forwarding, so no early connection is missed
+        if port != 0 {
+            self.remote_forwards
+                .lock()
+                .unwrap()
+                .insert(port.into(), sender.clone());
+        }
+        let res = self
+            .handle
+            .write()
+            .await
+            .tcpip_forward(address, port.into())
+            .await;
+        let port = match res {
+            Ok(_) if port != 0 => port,
+            Ok(assigned) => u16::try_from(assigned)?,
+            Err(e) => {
+                self.remote_forwards.lock().unwrap().remove(&port.into());
+                return Err(
+                    Error::from(e).context(format!("Server refused to listen on {address}:{port}"))
+                );
+            }
+        };
+        self.remote_forwards
+            .lock()
+            .unwrap()
+            .insert(port.into(), sender);
+        Ok((port, receiver))
+    }
+
+    /// Stop a remote port forwarding requested using [`Client::request_remote_forward`]
+    pub async fn cancel_remote_forward(&self, address: &str, port: u16) -> Result<(), Error> {
+        self.remote_forwards.lock().unwrap().remove(&port.into());
+        self.handle
+            .read()
+            .await
+            .cancel_tcpip_forward(address, port.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Open a new SFTP session
+    pub async fn sftp(&self) -> Result<SftpSession, Error> {
+        let channel = self.get_channel().await?;
+        channel.request_subsystem(true, "sftp").await?;
+        Ok(SftpSession::new(channel.into_stream()).await?)
+    }
+
+    /// Execute a command (in a new shell context) and return its output
+    pub async fn execute(&self, command: &str) -> Result<CommandOutput, Error> {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut channel = self.get_channel().await?;
+        channel.exec(true, command).await?;
+        let mut exit_code = None;
+        // Do not stop at EOF or the exit status, as data might still follow
+        loop {
+            let Some(msg) = channel.wait().await else {
+                break;
+            };
+            match msg {
+                ChannelMsg::Data { ref data } => stdout.extend_from_slice(data),
+                ChannelMsg::ExtendedData { ref data, ext: 1 } => stderr.extend_from_slice(data),
+                ChannelMsg::ExitStatus { exit_status } => exit_code = Some(exit_status as i32),
+                ChannelMsg::ExitSignal { .. } => exit_code = exit_code.or(Some(-1)),
+                _ => {}
+            }
+        }
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            exit_code: exit_code.ok_or_else(|| Error::msg("Command did not exit."))?,
+        })
+    }
+
+    /// Upload a local file to the given remote path using SFTP
+    ///
+    /// The file is streamed, so it is not loaded into memory at once.
+    /// See [`Client::sync_upload`] for skipping unchanged files or resuming interrupted uploads.
+    pub async fn upload_file(&self, local_path: &Path, remote_path: &str) -> Result<(), Error> {
+        let sftp = self.sftp().await?;
+        let mut reader = tokio::fs::File::open(local_path).await?;
+        let mut writer = sftp.create(remote_path).await?;
+        tokio::io::copy(&mut reader, &mut writer).await?;
+        writer.shutdown().await?;
+        sftp.close().await?;
+        Ok(())
+    }
+
+    /// Write the given content to a remote file using SFTP
+    pub async fn write_file(&self, remote_path: &str, content: &[u8]) -> Result<(), Error> {
+        let sftp = self.sftp().await?;
+        let mut file = sftp.create(remote_path).await?;
+        file.write_all(content).await?;
+        file.shutdown().await?;
+        sftp.close().await?;
+        Ok(())
+    }
+
+    /// Download a remote file to the given local path using SFTP
+    ///
+    /// The file is streamed, so it is not loaded into memory at once.
+    /// See [`Client::sync_download`] for skipping unchanged files or resuming interrupted downloads.
+    pub async fn download_file(&self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let sftp = self.sftp().await?;
+        let mut reader = sftp.open(remote_path).await?;
+        let mut writer = tokio::fs::File::create(local_path).await?;
+        tokio::io::copy(&mut reader, &mut writer).await?;
+        writer.flush().await?;
+        sftp.close().await?;
+        Ok(())
+    }
+
+    /// The jump hosts this connection is tunnelled through (in order)
+    pub fn jump_hosts(&self) -> &[Client] {
+        &self.jump_hosts
+    }
+
+    /// Close the connection (including the connections to all jump hosts)
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.handle
+            .read()
+            .await
+            .disconnect(russh::Disconnect::ByApplication, "", "")
+            .await?;
+        for jump_host in self.jump_hosts.iter().rev() {
+            Box::pin(jump_host.disconnect()).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether the connection (or the connection to any jump host) is closed
+    pub fn is_closed(&self) -> bool {
+        // The handle is only locked exclusively while requesting a remote port forwarding
+        let closed = self
+            .handle
+            .try_read()
+            .is_ok_and(|handle| handle.is_closed());
+        closed || self.jump_hosts.iter().any(Client::is_closed)
+    }
+}
+
+async fn authenticate(
+    handle: &mut Handle<ClientHandler>,
+    host: &str,
+    username: &str,
+    auth: AuthMethod,
+    kbd_callback: Option<&KeyboardInteractiveCallback>,
+) -> Result<(), Error> {
+    let authenticated = match auth {
+        AuthMethod::Password(password) => handle.authenticate_password(username, password).await?,
+        AuthMethod::PrivateKey { key_data, key_pass } => {
+            let key = russh_keys::decode_secret_key(&key_data, key_pass.as_deref())?;
+            handle
+                .authenticate_publickey(username, Arc::new(key))
+                .await?
+        }
+        AuthMethod::PrivateKeyFile {
+            key_file_path,
+            key_pass,
+        } => {
+            let key = russh_keys::load_secret_key(key_file_path, key_pass.as_deref())?;
+            handle
+                .authenticate_publickey(username, Arc::new(key))
+                .await?
+        }
+        AuthMethod::Certificate {
+            key_data,
+            key_pass,
+            cert_data,
+        } => {
+            let key = russh_keys::decode_secret_key(&key_data, key_pass.as_deref())?;
+            let cert = ssh_key::Certificate::from_openssh(cert_data.trim())?;
+            handle
+                .authenticate_openssh_cert(username, Arc::new(key), cert)
+                .await?
+        }
+        AuthMethod::CertificateFile {
+            key_file_path,
+            key_pass,
+            cert_file_path,
+        } => {
+            let key = russh_keys::load_secret_key(key_file_path, key_pass.as_deref())?;
+            let cert = russh_keys::load_openssh_certificate(cert_file_path)?;
+            handle
+                .authenticate_openssh_cert(username, Arc::new(key), cert)
+                .await?
+        }
+        AuthMethod::Agent => {
+            let mut agent = russh_keys::agent::client::AgentClient::connect_env()
+                .await
+                .map_err(|e| Error::from(e).context("Could not connect to SSH agent"))?;
+            let keys = agent.request_identities().await?;
+            let mut authenticated = false;
+            // Try all keys of the agent until one is accepted
+            for key in keys {
+                let (returned_agent, res) = handle.authenticate_future(username, key, agent).await;
+                agent = returned_agent;
+                if res? {
+                    authenticated = true;
+                    break;
+                }
+            }
+            authenticated
+        }
+        AuthMethod::KeyboardInteractive(mut kbd) => {
+            let mut res = handle
+                .authenticate_keyboard_interactive_start(username, None)
+                .await?;
+            loop {
+                let request = match res {
+                    KeyboardInteractiveAuthResponse::Success => break true,
+                    KeyboardInteractiveAuthResponse::Failure => break false,
+                    KeyboardInteractiveAuthResponse::InfoRequest {
+                        name,
+                        instructions,
+                        prompts,
+                    } => KeyboardInteractiveRequest {
+                        host: host.to_string(),
+                        name,
+                        instructions,
+                        prompts: prompts.into_iter().map(|p| (p.prompt, p.echo)).collect(),
+                    },
+                };
+                let responses = kbd.answer(&request, kbd_callback).await?;
+                res = handle
+                    .authenticate_keyboard_interactive_respond(responses)
+                    .await?;
+            }
+        }
+        AuthMethod::Fallback(methods) => {
+            let mut last_error = Error::msg("No authentication method to try.");
+            for method in methods {
+                // Failures (e.g., an agent without a matching key) only move on to the next method
+                let res =
+                    Box::pin(authenticate(handle, host, username, method, kbd_callback)).await;
+                match res {
+                    Ok(()) => return Ok(()),
+                    Err(e) => last_error = e,
+                }
+            }
+            return Err(last_error);
+        }
+    };
+    if authenticated {
+        Ok(())
+    } else {
+        Err(Error::msg("Authentication failed."))
+    }
+}
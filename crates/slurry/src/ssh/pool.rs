@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::ConnectionConfig;
+
+use super::{ReconnectOptions, ResilientSession, SessionEventHandler};
+
+/// Default limit of concurrent channels per connection (the default `MaxSessions` of OpenSSH)
+pub const DEFAULT_MAX_SESSIONS: usize = 10;
+
+#[derive(Debug, Clone)]
+/// Cheap-to-clone handle to one or more SSH connections, which can be shared between tasks (e.g., a polling loop and job submissions)
+///
+/// Every command or file transfer uses its own channel of a connection.
+/// The number of concurrently open channels per connection is limited (see [`SessionPool::with_max_sessions`]),
+/// further operations wait until a channel is free.
+/// Additional connections (see [`SessionPool::with_connections`]) are only opened once the first connection is fully used.
+pub struct SessionPool {
+    // Connections with their own limit of concurrent channels
+    sessions: Arc<[(ResilientSession, Arc<Semaphore>)]>,
+    // Limit of concurrent channels of all connections
+    permits: Arc<Semaphore>,
+    max_sessions: usize,
+}
+
+/// A reserved channel of a [`SessionPool`], released on drop
+#[derive(Debug)]
+pub(crate) struct PooledSession {
+    pub(crate) session: ResilientSession,
+    _permits: (OwnedSemaphorePermit, OwnedSemaphorePermit),
+}
+
+impl SessionPool {
+    /// Create a new pool with a single connection using the given configuration
+    pub fn new(cfg: ConnectionConfig) -> Self {
+        Self::from_session(ResilientSession::new(cfg))
+    }
+
+    /// Create a new pool with a single connection using the given session
+    pub fn from_session(session: ResilientSession) -> Self {
+        Self {
+            sessions: Arc::new([(session, Arc::new(Semaphore::new(DEFAULT_MAX_SESSIONS)))]),
+            permits: Arc::new(Semaphore::new(DEFAULT_MAX_SESSIONS)),
+            max_sessions: DEFAULT_MAX_SESSIONS,
+        }
+    }
+
+    /// Limit the number of concurrent channels per connection (i.e., the `MaxSessions` setting of the server)
+    pub fn with_max_sessions(self, max_sessions: usize) -> Self {
+        let connections = self.sessions.len();
+        self.rebuild(connections, max_sessions.max(1))
+    }
+
+    /// Use up to the given number of connections (opened only when needed)
+    pub fn with_connections(self, connections: usize) -> Self {
+        let max_sessions = self.max_sessions;
+        self.rebuild(connections.max(1), max_sessions)
+    }
+
+    /// Use the passed keepalive and reconnection settings for all connections
+    pub fn with_options(self, options: ReconnectOptions) -> Self {
+        self.map_sessions(|s| s.with_options(options))
+    }
+
+    /// Pass the [`super::SessionEvent`]s of all connections to the given handler
+    pub fn with_event_handler(self, handler: SessionEventHandler) -> Self {
+        self.map_sessions(|s| s.with_event_handler(handler.clone()))
+    }
+
+    /// The first connection of the pool (e.g., for port forwarding)
+    pub fn session(&self) -> &ResilientSession {
+        &self.sessions[0].0
+    }
+
+    /// Connect the first connection (without retrying), e.g., to check the login credentials
+    pub async fn connect(&self) -> Result<(), Error> {
+        self.session().connect().await
+    }
+
+    /// Close all connections
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        for (session, _) in self.sessions.iter() {
+            session.disconnect().await?;
+        }
+        Ok(())
+    }
+
+    /// Reserve a channel, waiting until one is free
+    pub(crate) async fn acquire(&self) -> Result<PooledSession, Error> {
+        let total = Arc::clone(&self.permits).acquire_owned().await?;
+        // Prefer earlier connections, so that additional connections are only opened when needed
+        for (session, semaphore) in self.sessions.iter() {
+            if let Ok(permit) = Arc::clone(semaphore).try_acquire_owned() {
+                return Ok(PooledSession {
+                    session: session.clone(),
+                    _permits: (total, permit),
+                });
+            }
+        }
+        Err(Error::msg("No free channel available."))
+    }
+
+    fn rebuild(self, connections: usize, max_sessions: usize) -> Self {
+        let first = self.sessions[0].0.clone();
+        let sessions: Vec<_> = (0..connections)
+            .map(|i| {
+                let session = match self.sessions.get(i) {
+                    Some((session, _)) => session.clone(),
+                    None => first.fork(),
+                };
+                (session, Arc::new(Semaphore::new(max_sessions)))
+            })
+            .collect();
+        Self {
+            sessions: sessions.into(),
+            permits: Arc::new(Semaphore::new(connections * max_sessions)),
+            max_sessions,
+        }
+    }
+
+    fn map_sessions(self, f: impl Fn(ResilientSession) -> ResilientSession) -> Self {
+        let sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|(session, semaphore)| (f(session.clone()), Arc::clone(semaphore)))
+            .collect();
+        Self {
+            sessions: sessions.into(),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{ssh::SessionPool, ConnectionConfig};
+
+    #[tokio::test]
+    async fn test_pool_limits_channels() {
+        let pool = SessionPool::new(ConnectionConfig::default())
+            .with_max_sessions(2)
+            .with_connections(2);
+        let available = |pool: &SessionPool| -> Vec<usize> {
+            pool.sessions
+                .iter()
+                .map(|(_, s)| s.available_permits())
+                .collect()
+        };
+        let _first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+        let _third = pool.acquire().await.unwrap();
+        // The second connection is only used once the first one is fully used
+        assert_eq!(available(&pool), vec![0, 1]);
+        let _fourth = pool.acquire().await.unwrap();
+        // All channels are in use, until one is released
+        let shared = pool.clone();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), shared.acquire())
+                .await
+                .is_err()
+        );
+        drop(second);
+        let _fifth = pool.acquire().await.unwrap();
+        assert_eq!(available(&pool), vec![0, 0]);
+    }
+}
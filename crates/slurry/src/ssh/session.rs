@@ -0,0 +1,376 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Error;
+use russh::client::Config;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Mutex};
+
+use crate::{Client, ConnectionConfig};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+/// Event of a [`ResilientSession`] (e.g., to show the connection status to the user)
+pub enum SessionEvent {
+    /// Trying to (re)connect, after waiting for `delay_ms` milliseconds
+    Connecting {
+        /// Number of the connection attempt (starting at `1`)
+        attempt: usize,
+        /// Time waited before this attempt (in milliseconds)
+        delay_ms: u64,
+    },
+    /// A connection attempt failed
+    ConnectFailed {
+        /// Number of the failed connection attempt
+        attempt: usize,
+        /// Reason of the failure
+        error: String,
+    },
+    /// The connection was (re-)established
+    Connected {
+        /// Number of attempts it took
+        attempts: usize,
+    },
+    /// The connection was lost (e.g., because keepalives were not answered)
+    Disconnected,
+    /// A command or file transfer failed
+    CommandFailed {
+        /// The failed command (or file transfer)
+        command: String,
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Keepalive and reconnection settings of a [`ResilientSession`]
+pub struct ReconnectOptions {
+    /// Send a keepalive if nothing was received from the server for this long
+    pub keepalive_interval: Duration,
+    /// Consider the connection dead after this many unanswered keepalives
+    pub keepalive_max: usize,
+    /// Delay before the second connection attempt, doubled for every further attempt
+    pub initial_backoff: Duration,
+    /// Maximum delay between connection attempts
+    pub max_backoff: Duration,
+    /// Give up after this many failed connection attempts (`None` to retry forever)
+    pub max_attempts: Option<usize>,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        Self {
+            keepalive_interval: Duration::from_secs(30),
+            keepalive_max: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectOptions {
+    /// Delay before the given connection attempt (starting at `1`)
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        if attempt <= 1 {
+            return Duration::ZERO;
+        }
+        let factor = 2u32.saturating_pow((attempt - 2).min(31) as u32);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+type SessionEventHandlerFn = dyn Fn(SessionEvent) + Send + Sync;
+
+#[derive(Clone)]
+/// Handler for [`SessionEvent`]s
+pub struct SessionEventHandler(Arc<SessionEventHandlerFn>);
+
+impl SessionEventHandler {
+    /// Create a new event handler
+    pub fn new<F: Fn(SessionEvent) + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for SessionEventHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SessionEventHandler").finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// SSH session which sends keepalives and transparently reconnects (with exponential backoff) once the connection is lost
+///
+/// Failures are reported as [`SessionEvent`]s instead of ending the session, so long-running loops (e.g., collecting `squeue` data) can continue.
+/// Cloning the session is cheap, all clones share the same connection.
+pub struct ResilientSession {
+    cfg: Arc<ConnectionConfig>,
+    options: ReconnectOptions,
+    event_handler: Option<SessionEventHandler>,
+    client: Arc<Mutex<Option<Client>>>,
+    // Held while reconnecting, so only one task reconnects at a time (without blocking access to `client`)
+    reconnecting: Arc<Mutex<()>>,
+    // Incremented by `disconnect`, cancelling running reconnection attempts
+    disconnects: Arc<watch::Sender<u64>>,
+}
+
+impl ResilientSession {
+    /// Create a new session for the given configuration
+    ///
+    /// The connection is established on first use, or by calling [`ResilientSession::connect`].
+    pub fn new(cfg: ConnectionConfig) -> Self {
+        Self {
+            cfg: Arc::new(cfg),
+            options: ReconnectOptions::default(),
+            event_handler: None,
+            client: Arc::new(Mutex::new(None)),
+            reconnecting: Arc::new(Mutex::new(())),
+            disconnects: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    /// Use the passed keepalive and reconnection settings
+    pub fn with_options(mut self, options: ReconnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Pass all [`SessionEvent`]s to the given handler
+    pub fn with_event_handler(mut self, handler: SessionEventHandler) -> Self {
+        self.event_handler = Some(handler);
+        self
+    }
+
+    /// Create a new (not yet connected) session with the same configuration, settings and event handler
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            cfg: Arc::clone(&self.cfg),
+            options: self.options,
+            event_handler: self.event_handler.clone(),
+            client: Arc::new(Mutex::new(None)),
+            reconnecting: Arc::new(Mutex::new(())),
+            disconnects: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    /// The configuration used for (re)connecting
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.cfg
+    }
+
+    /// Connect once (without retrying), e.g., to check the login credentials
+    pub async fn connect(&self) -> Result<(), Error> {
+        let client = Client::connect_with_config(&self.cfg, self.ssh_config()).await?;
+        *self.client.lock().await = Some(client);
+        self.emit(SessionEvent::Connected { attempts: 1 });
+        Ok(())
+    }
+
+    /// Get a connected client, reconnecting if the connection was lost
+    ///
+    /// Fails if [`ReconnectOptions::max_attempts`] connection attempts failed,
+    /// or if [`ResilientSession::disconnect`] was called while reconnecting.
+    pub async fn client(&self) -> Result<Client, Error> {
+        if let Some(client) = self.current_client().await {
+            return Ok(client);
+        }
+        let _reconnecting = self.reconnecting.lock().await;
+        // Another task might have reconnected in the meantime
+        if let Some(client) = self.current_client().await {
+            return Ok(client);
+        }
+        let mut disconnects = self.disconnects.subscribe();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let delay = self.options.backoff(attempt);
+            self.emit(SessionEvent::Connecting {
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            });
+            let res = tokio::select! {
+                _ = disconnects.changed() => {
+                    return Err(Error::msg("The session was disconnected while reconnecting."));
+                }
+                res = async {
+                    tokio::time::sleep(delay).await;
+                    Client::connect_with_config(&self.cfg, self.ssh_config()).await
+                } => res,
+            };
+            match res {
+                Ok(c) => {
+                    let mut client = self.client.lock().await;
+                    if disconnects.has_changed().unwrap_or(true) {
+                        drop(client);
+                        c.disconnect().await?;
+                        return Err(Error::msg(
+                            "The session was disconnected while reconnecting.",
+                        ));
+                    }
+                    *client = Some(c.clone());
+                    drop(client);
+                    self.emit(SessionEvent::Connected { attempts: attempt });
+                    return Ok(c);
+                }
+                Err(e) => {
+                    self.emit(SessionEvent::ConnectFailed {
+                        attempt,
+                        error: e.to_string(),
+                    });
+                    if self.options.max_attempts.is_some_and(|max| attempt >= max) {
+                        return Err(
+                            e.context(format!("Could not connect after {attempt} attempts"))
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// The current client, if it is still connected
+    async fn current_client(&self) -> Option<Client> {
+        let mut client = self.client.lock().await;
+        match client.as_ref() {
+            Some(c) if !c.is_closed() => Some(c.clone()),
+            Some(_) => {
+                *client = None;
+                drop(client);
+                self.emit(SessionEvent::Disconnected);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Whether the session currently has an open connection
+    pub async fn is_connected(&self) -> bool {
+        self.client
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|c| !c.is_closed())
+    }
+
+    /// Close the connection (it is re-established on next use)
+    ///
+    /// Running reconnection attempts are cancelled.
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.disconnects
+            .send_modify(|disconnects| *disconnects += 1);
+        let client = self.client.lock().await.take();
+        match client {
+            Some(client) => client.disconnect().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Report a failed command as [`SessionEvent::CommandFailed`] and pass the result on
+    pub(crate) fn report<T>(&self, command: &str, res: Result<T, Error>) -> Result<T, Error> {
+        if let Err(e) = &res {
+            self.emit(SessionEvent::CommandFailed {
+                command: command.to_string(),
+                error: e.to_string(),
+            });
+        }
+        res
+    }
+
+    fn emit(&self, event: SessionEvent) {
+        if let Some(handler) = &self.event_handler {
+            (handler.0)(event);
+        }
+    }
+
+    fn ssh_config(&self) -> Config {
+        Config {
+            keepalive_interval: Some(self.options.keepalive_interval),
+            keepalive_max: self.options.keepalive_max,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        ssh::{ReconnectOptions, ResilientSession, SessionEvent, SessionEventHandler},
+        ConnectionConfig,
+    };
+
+    #[test]
+    fn test_backoff() {
+        let options = ReconnectOptions {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let delays: Vec<_> = (1..=6).map(|i| options.backoff(i).as_secs()).collect();
+        assert_eq!(delays, vec![0, 1, 2, 4, 8, 10]);
+        assert_eq!(options.backoff(1000), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up() {
+        // Nothing listens on port 1, so all connection attempts fail
+        let cfg = ConnectionConfig::default().with_host((String::from("127.0.0.1"), 1));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler_events = Arc::clone(&events);
+        let session = ResilientSession::new(cfg)
+            .with_options(ReconnectOptions {
+                initial_backoff: Duration::from_millis(1),
+                max_attempts: Some(2),
+                ..Default::default()
+            })
+            .with_event_handler(SessionEventHandler::new(move |e| {
+                handler_events.lock().unwrap().push(e)
+            }));
+        assert!(session.client().await.is_err());
+        assert!(!session.is_connected().await);
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[2],
+            SessionEvent::Connecting {
+                attempt: 2,
+                delay_ms: 1
+            }
+        );
+        assert!(matches!(
+            events[3],
+            SessionEvent::ConnectFailed { attempt: 2, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_while_reconnecting() {
+        let cfg = ConnectionConfig::default().with_host((String::from("127.0.0.1"), 1));
+        let session = ResilientSession::new(cfg).with_options(ReconnectOptions {
+            initial_backoff: Duration::from_secs(60),
+            max_attempts: None,
+            ..Default::default()
+        });
+        let reconnecting = tokio::spawn({
+            let session = session.clone();
+            async move { session.client().await }
+        });
+        // Wait until the first attempt failed and the session waits for the next one
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let timeout = Duration::from_secs(5);
+        assert!(!tokio::time::timeout(timeout, session.is_connected())
+            .await
+            .unwrap());
+        tokio::time::timeout(timeout, session.disconnect())
+            .await
+            .unwrap()
+            .unwrap();
+        let res = tokio::time::timeout(timeout, reconnecting).await.unwrap();
+        assert!(res.unwrap().is_err());
+    }
+}
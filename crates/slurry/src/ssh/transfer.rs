@@ -0,0 +1,1046 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Error;
+use russh_sftp::{
+    client::SftpSession,
+    protocol::{FileAttributes, OpenFlags},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{executor::CommandExecutor, job_management::shell_quote, Client};
+
+use super::{ResilientSession, SessionPool};
+
+/// Size of the chunks read and written during transfers (progress is reported after every chunk)
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Suffix of files which are still being transferred (renamed to their destination once completed)
+const PART_SUFFIX: &str = ".part";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// How to decide whether a file already present at the destination is unchanged (and can be skipped)
+pub enum SyncCompare {
+    /// Always transfer all files
+    Always,
+    /// Skip files with the same size and modification time (in seconds)
+    #[default]
+    SizeAndMtime,
+    /// Skip files with the same size and SHA-256 checksum (the remote checksum is computed using `sha256sum`)
+    Checksum,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Progress of a running transfer
+pub struct TransferProgress {
+    /// The file currently transferred (relative to the transferred directory)
+    pub path: String,
+    /// Bytes of the current file already present at the destination
+    pub file_bytes: u64,
+    /// Size of the current file
+    pub file_size: u64,
+    /// Bytes transferred so far (of all files)
+    pub total_bytes: u64,
+    /// Bytes which need to be transferred (of all files, excluding skipped files and already transferred parts)
+    pub total_size: u64,
+}
+
+type TransferProgressCallbackFn = dyn Fn(TransferProgress) + Send + Sync;
+
+#[derive(Clone)]
+/// Callback for the [`TransferProgress`] of a transfer (e.g., to show a progress bar)
+pub struct TransferProgressCallback(Arc<TransferProgressCallbackFn>);
+
+impl TransferProgressCallback {
+    /// Create a new progress callback
+    pub fn new<F: Fn(TransferProgress) + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for TransferProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TransferProgressCallback").finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Options for syncing files or directories (see [`Client::sync_upload`] and [`Client::sync_download`])
+pub struct SyncOptions {
+    /// How to detect unchanged files
+    pub compare: SyncCompare,
+    /// Whether to continue interrupted transfers
+    ///
+    /// Files are transferred into `{destination}.part`, which is only renamed to the destination once completed.
+    /// An interrupted transfer is continued from its `.part` file, unless the source was modified since.
+    pub resume: bool,
+    /// Callback called with the progress of the transfer
+    pub progress: Option<TransferProgressCallback>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            compare: SyncCompare::default(),
+            resume: true,
+            progress: None,
+        }
+    }
+}
+
+impl SyncOptions {
+    /// Create new default sync options (comparing size and modification time, resuming interrupted transfers)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the passed method to detect unchanged files
+    pub fn with_compare(mut self, compare: SyncCompare) -> Self {
+        self.compare = compare;
+        self
+    }
+
+    /// Set whether interrupted transfers should be continued
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Report the progress of the transfer to the given callback
+    pub fn with_progress_callback(mut self, callback: TransferProgressCallback) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Summary of a completed sync
+pub struct SyncReport {
+    /// Transferred files (relative to the transferred directory)
+    pub transferred: Vec<String>,
+    /// Transferred files which were continued from an interrupted transfer (also included in `transferred`)
+    pub resumed: Vec<String>,
+    /// Files skipped because they were unchanged
+    pub skipped: Vec<String>,
+    /// Number of bytes transferred
+    pub bytes_transferred: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Size, modification time (in seconds since the Unix epoch) and permissions (if known) of a file
+struct FileState {
+    size: u64,
+    mtime: u64,
+    mode: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What to do with a single file
+enum Plan {
+    Skip,
+    Transfer,
+    /// Skip the file if the checksums of source and destination match
+    CompareChecksum,
+}
+
+fn plan(options: &SyncOptions, source: FileState, destination: Option<FileState>) -> Plan {
+    let Some(destination) = destination else {
+        return Plan::Transfer;
+    };
+    if options.compare == SyncCompare::Always || destination.size != source.size {
+        return Plan::Transfer;
+    }
+    match options.compare {
+        SyncCompare::Checksum => Plan::CompareChecksum,
+        _ if destination.mtime == source.mtime => Plan::Skip,
+        _ => Plan::Transfer,
+    }
+}
+
+/// Bytes of an interrupted transfer (i.e., of its `.part` file) which can be kept
+fn resume_offset(source: FileState, partial: Option<FileState>) -> u64 {
+    match partial {
+        // The source must not have been modified since the transfer was interrupted
+        Some(partial) if partial.size <= source.size && partial.mtime >= source.mtime => {
+            partial.size
+        }
+        _ => 0,
+    }
+}
+
+/// A file to sync
+#[derive(Debug)]
+struct SyncFile {
+    /// Path relative to the synced directory (used in reports)
+    rel_path: String,
+    local_path: PathBuf,
+    remote_path: String,
+    /// State of the source file
+    source: FileState,
+    /// Bytes already present at the destination (of an interrupted transfer)
+    offset: u64,
+    /// Whether the destination already exists (and is replaced once the transfer is completed)
+    replace: bool,
+}
+
+fn join(base: &str, rel_path: &str) -> String {
+    match (base, rel_path) {
+        (base, "") => base.to_string(),
+        ("", rel_path) => rel_path.to_string(),
+        (base, rel_path) => format!("{}/{rel_path}", base.trim_end_matches('/')),
+    }
+}
+
+/// Path of the (incomplete) `.part` file while transferring to `path`
+fn local_part_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(PART_SUFFIX);
+    PathBuf::from(path)
+}
+
+fn local_state(path: &Path) -> Result<FileState, Error> {
+    let metadata = fs::metadata(path)?;
+    Ok(FileState {
+        size: metadata.len(),
+        mtime: metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        mode: local_mode(&metadata),
+    })
+}
+
+#[cfg(unix)]
+fn local_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn local_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_local_mode(path: &Path, mode: u32) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_local_mode(_path: &Path, _mode: u32) -> Result<(), Error> {
+    Ok(())
+}
+
+fn remote_state(metadata: &FileAttributes) -> FileState {
+    FileState {
+        size: metadata.size.unwrap_or_default(),
+        mtime: metadata.mtime.unwrap_or_default().into(),
+        // Without the file type bits
+        mode: metadata.permissions.map(|mode| mode & 0o7777),
+    }
+}
+
+/// Collect all files below `dir` (recursively), with their paths relative to `dir`
+fn local_files(
+    dir: &Path,
+    rel_dir: &str,
+    files: &mut Vec<(String, FileState)>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let rel_path = join(rel_dir, &entry.file_name().to_string_lossy());
+        // Follow symlinks
+        if fs::metadata(entry.path())?.is_dir() {
+            local_files(&entry.path(), &rel_path, files)?;
+        } else {
+            files.push((rel_path, local_state(&entry.path())?));
+        }
+    }
+    Ok(())
+}
+
+/// Collect all files below the remote directory `dir` (recursively), with their paths relative to `dir`
+async fn remote_files(sftp: &SftpSession, dir: &str) -> Result<Vec<(String, FileState)>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![String::new()];
+    while let Some(rel_dir) = dirs.pop() {
+        for entry in sftp.read_dir(join(dir, &rel_dir)).await? {
+            let rel_path = join(&rel_dir, &entry.file_name());
+            let mut metadata = entry.metadata();
+            // Follow symlinks
+            if metadata.is_symlink() {
+                metadata = sftp.metadata(join(dir, &rel_path)).await?;
+            }
+            if metadata.is_dir() {
+                dirs.push(rel_path);
+            } else {
+                files.push((rel_path, remote_state(&metadata)));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Create the remote directory `path` and all of its missing parents (like `mkdir -p`)
+async fn create_remote_dir_all(sftp: &SftpSession, path: &str) -> Result<(), Error> {
+    let mut current = String::from(if path.starts_with('/') { "/" } else { "" });
+    for part in path.split('/').filter(|p| !p.is_empty()) {
+        current = join(&current, part);
+        match sftp.metadata(current.clone()).await {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => return Err(Error::msg(format!("{current} is not a directory"))),
+            Err(_) => sftp.create_dir(current.clone()).await?,
+        }
+    }
+    Ok(())
+}
+
+fn local_checksum(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+async fn remote_checksum<E: CommandExecutor>(executor: &E, path: &str) -> Result<String, Error> {
+    let out = executor
+        .execute(&format!("sha256sum -- {}", shell_quote(path)))
+        .await?;
+    match out.stdout.split_whitespace().next() {
+        Some(checksum) if out.success() => Ok(checksum.to_string()),
+        _ => Err(Error::msg(format!(
+            "Could not compute checksum of {path}: {}",
+            out.stderr.trim()
+        ))),
+    }
+}
+
+/// Tracks the overall progress of a sync and reports it to the callback of the [`SyncOptions`]
+struct Progress<'a> {
+    callback: Option<&'a TransferProgressCallback>,
+    total_bytes: u64,
+    total_size: u64,
+}
+
+impl Progress<'_> {
+    /// Copy everything from `reader` to `writer`, reporting the progress for `file` (of which `offset` bytes were already transferred)
+    async fn copy<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+        file: &SyncFile,
+    ) -> Result<u64, Error> {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut file_bytes = file.offset;
+        self.report(file, file_bytes);
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n]).await?;
+            file_bytes += n as u64;
+            self.total_bytes += n as u64;
+            self.report(file, file_bytes);
+        }
+        writer.shutdown().await?;
+        Ok(file_bytes - file.offset)
+    }
+
+    fn report(&self, file: &SyncFile, file_bytes: u64) {
+        if let Some(callback) = self.callback {
+            (callback.0)(TransferProgress {
+                path: file.rel_path.clone(),
+                file_bytes,
+                file_size: file.source.size,
+                total_bytes: self.total_bytes,
+                total_size: self.total_size,
+            });
+        }
+    }
+}
+
+impl Client {
+    /// Upload a local file or directory (recursively) to the given remote path using SFTP, skipping unchanged files
+    ///
+    /// Missing remote directories are created.
+    /// Uploaded files get the modification time and permissions of the local file, so they are recognized as unchanged by later syncs.
+    /// If an upload was interrupted (e.g., by a lost connection), calling this again continues where it stopped (see [`SyncOptions::resume`]).
+    pub async fn sync_upload(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let files = upload_files(local_path, remote_path)?;
+        let sftp = self.sftp().await?;
+        let res = sync(self, &sftp, files, options, true).await;
+        sftp.close().await?;
+        res
+    }
+
+    /// Download a remote file or directory (recursively) to the given local path using SFTP, skipping unchanged files
+    ///
+    /// Missing local directories are created.
+    /// Downloaded files get the modification time and permissions of the remote file, so they are recognized as unchanged by later syncs.
+    /// If a download was interrupted (e.g., by a lost connection), calling this again continues where it stopped (see [`SyncOptions::resume`]).
+    pub async fn sync_download(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let sftp = self.sftp().await?;
+        let res = async {
+            let files = download_files(&sftp, remote_path, local_path).await?;
+            sync(self, &sftp, files, options, false).await
+        }
+        .await;
+        sftp.close().await?;
+        res
+    }
+}
+
+/// All files to upload from the local file or directory `local_path`
+fn upload_files(local_path: &Path, remote_path: &str) -> Result<Vec<SyncFile>, Error> {
+    if !fs::metadata(local_path)?.is_dir() {
+        return Ok(vec![SyncFile {
+            rel_path: file_name(local_path),
+            local_path: local_path.to_path_buf(),
+            remote_path: remote_path.to_string(),
+            source: local_state(local_path)?,
+            offset: 0,
+            replace: false,
+        }]);
+    }
+    let mut files = Vec::new();
+    local_files(local_path, "", &mut files)?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files
+        .into_iter()
+        .map(|(rel_path, source)| SyncFile {
+            local_path: local_path.join(&rel_path),
+            remote_path: join(remote_path, &rel_path),
+            rel_path,
+            source,
+            offset: 0,
+            replace: false,
+        })
+        .collect())
+}
+
+/// All files to download from the remote file or directory `remote_path`
+async fn download_files(
+    sftp: &SftpSession,
+    remote_path: &str,
+    local_path: &Path,
+) -> Result<Vec<SyncFile>, Error> {
+    let metadata = sftp.metadata(remote_path).await?;
+    if !metadata.is_dir() {
+        return Ok(vec![SyncFile {
+            rel_path: file_name(local_path),
+            local_path: local_path.to_path_buf(),
+            remote_path: remote_path.to_string(),
+            source: remote_state(&metadata),
+            offset: 0,
+            replace: false,
+        }]);
+    }
+    Ok(remote_files(sftp, remote_path)
+        .await?
+        .into_iter()
+        .map(|(rel_path, source)| SyncFile {
+            local_path: local_path.join(&rel_path),
+            remote_path: join(remote_path, &rel_path),
+            rel_path,
+            source,
+            offset: 0,
+            replace: false,
+        })
+        .collect())
+}
+
+/// Transfer all changed `files` (uploading or downloading them), computing remote checksums using `executor`
+async fn sync<E: CommandExecutor>(
+    executor: &E,
+    sftp: &SftpSession,
+    files: Vec<SyncFile>,
+    options: &SyncOptions,
+    upload: bool,
+) -> Result<SyncReport, Error> {
+    let mut report = SyncReport::default();
+    let mut to_transfer = Vec::new();
+    for mut file in files {
+        let remote_part_path = format!("{}{PART_SUFFIX}", file.remote_path);
+        let destination = if upload {
+            let metadata = sftp.metadata(file.remote_path.clone()).await.ok();
+            metadata.as_ref().map(remote_state)
+        } else {
+            local_state(&file.local_path).ok()
+        };
+        let mut plan = plan(options, file.source, destination);
+        if plan == Plan::CompareChecksum {
+            let local_path = file.local_path.clone();
+            let local = tokio::task::spawn_blocking(move || local_checksum(&local_path)).await??;
+            plan = if local == remote_checksum(executor, &file.remote_path).await? {
+                Plan::Skip
+            } else {
+                Plan::Transfer
+            };
+        }
+        if plan == Plan::Skip {
+            report.skipped.push(file.rel_path);
+            continue;
+        }
+        file.replace = destination.is_some();
+        if options.resume {
+            let partial = if upload {
+                let metadata = sftp.metadata(remote_part_path).await.ok();
+                metadata.as_ref().map(remote_state)
+            } else {
+                local_state(&local_part_path(&file.local_path)).ok()
+            };
+            file.offset = resume_offset(file.source, partial);
+        }
+        to_transfer.push(file);
+    }
+
+    let mut progress = Progress {
+        callback: options.progress.as_ref(),
+        total_bytes: 0,
+        total_size: to_transfer.iter().map(|f| f.source.size - f.offset).sum(),
+    };
+    let mut remote_dirs = HashSet::new();
+    for file in to_transfer {
+        if upload {
+            if let Some((parent, _)) = file.remote_path.rsplit_once('/') {
+                if remote_dirs.insert(parent.to_string()) {
+                    create_remote_dir_all(sftp, parent).await?;
+                }
+            }
+        } else if let Some(parent) = file.local_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let res = match upload {
+            true => upload_file(sftp, &file, &mut progress).await,
+            false => download_file(sftp, &file, &mut progress).await,
+        };
+        report.bytes_transferred += res.map_err(|e| {
+            e.context(format!(
+                "Could not {} {}",
+                if upload { "upload" } else { "download" },
+                file.rel_path
+            ))
+        })?;
+        if file.offset > 0 {
+            report.resumed.push(file.rel_path.clone());
+        }
+        report.transferred.push(file.rel_path);
+    }
+    Ok(report)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Upload a single file into its `.part` file and rename it once completed, returning the number of transferred bytes
+async fn upload_file(
+    sftp: &SftpSession,
+    file: &SyncFile,
+    progress: &mut Progress<'_>,
+) -> Result<u64, Error> {
+    let part_path = format!("{}{PART_SUFFIX}", file.remote_path);
+    let mut reader = tokio::fs::File::open(&file.local_path).await?;
+    let mut writer = if file.offset > 0 {
+        reader.seek(SeekFrom::Start(file.offset)).await?;
+        let mut writer = sftp
+            .open_with_flags(part_path.clone(), OpenFlags::WRITE)
+            .await?;
+        writer.seek(SeekFrom::Start(file.offset)).await?;
+        writer
+    } else {
+        sftp.create(part_path.clone()).await?
+    };
+    let transferred = progress.copy(&mut reader, &mut writer, file).await?;
+    let mtime = u32::try_from(file.source.mtime).unwrap_or(u32::MAX);
+    sftp.set_metadata(
+        part_path.clone(),
+        FileAttributes {
+            size: None,
+            uid: None,
+            user: None,
+            gid: None,
+            group: None,
+            permissions: file.source.mode,
+            atime: Some(mtime),
+            mtime: Some(mtime),
+        },
+    )
+    .await?;
+    // Renaming onto an existing file fails on most servers (e.g., OpenSSH)
+    if file.replace {
+        sftp.remove_file(file.remote_path.clone()).await?;
+    }
+    sftp.rename(part_path, file.remote_path.clone()).await?;
+    Ok(transferred)
+}
+
+/// Download a single file into its `.part` file and rename it once completed, returning the number of transferred bytes
+async fn download_file(
+    sftp: &SftpSession,
+    file: &SyncFile,
+    progress: &mut Progress<'_>,
+) -> Result<u64, Error> {
+    let part_path = local_part_path(&file.local_path);
+    let mut reader = sftp.open(file.remote_path.clone()).await?;
+    let mut writer = if file.offset > 0 {
+        reader.seek(SeekFrom::Start(file.offset)).await?;
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await?
+    } else {
+        tokio::fs::File::create(&part_path).await?
+    };
+    let transferred = progress.copy(&mut reader, &mut writer, file).await?;
+    writer
+        .into_std()
+        .await
+        .set_modified(UNIX_EPOCH + Duration::from_secs(file.source.mtime))?;
+    if let Some(mode) = file.source.mode {
+        set_local_mode(&part_path, mode)?;
+    }
+    fs::rename(&part_path, &file.local_path)?;
+    Ok(transferred)
+}
+
+impl ResilientSession {
+    /// Upload a local file or directory, skipping unchanged files (see [`Client::sync_upload`])
+    pub async fn sync_upload(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let res = async {
+            self.client()
+                .await?
+                .sync_upload(local_path, remote_path, options)
+                .await
+        }
+        .await;
+        self.report(&format!("upload {remote_path}"), res)
+    }
+
+    /// Download a remote file or directory, skipping unchanged files (see [`Client::sync_download`])
+    pub async fn sync_download(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let res = async {
+            self.client()
+                .await?
+                .sync_download(remote_path, local_path, options)
+                .await
+        }
+        .await;
+        self.report(&format!("download {remote_path}"), res)
+    }
+}
+
+impl SessionPool {
+    /// Upload a local file or directory, skipping unchanged files (see [`Client::sync_upload`])
+    pub async fn sync_upload(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled
+            .session
+            .sync_upload(local_path, remote_path, options)
+            .await;
+        drop(pooled);
+        res
+    }
+
+    /// Download a remote file or directory, skipping unchanged files (see [`Client::sync_download`])
+    pub async fn sync_download(
+        &self,
+        remote_path: &str,
+        local_path: &Path,
+        options: &SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let pooled = self.acquire().await?;
+        let res = pooled
+            .session
+            .sync_download(remote_path, local_path, options)
+            .await;
+        drop(pooled);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use russh_sftp::{
+        client::SftpSession,
+        protocol::{Attrs, Data, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode},
+        server::Handler,
+    };
+
+    use crate::{
+        executor::LocalExecutor,
+        ssh::{SyncCompare, SyncOptions, SyncReport},
+    };
+
+    use super::{join, plan, resume_offset, sync, upload_files, FileState, Plan};
+
+    #[test]
+    fn test_plan_transfers() {
+        let source = FileState {
+            size: 100,
+            mtime: 1_700_000_000,
+            mode: None,
+        };
+        let options = SyncOptions::default();
+        assert_eq!(plan(&options, source, None), Plan::Transfer);
+        assert_eq!(plan(&options, source, Some(source)), Plan::Skip);
+        // Changed files are transferred again
+        let changed = FileState {
+            size: 100,
+            mtime: 1,
+            mode: None,
+        };
+        assert_eq!(plan(&options, source, Some(changed)), Plan::Transfer);
+        // Interrupted transfers are resumed from their `.part` file, unless the source was modified since
+        let partial = FileState {
+            size: 40,
+            mtime: 1_800_000_000,
+            mode: None,
+        };
+        assert_eq!(resume_offset(source, Some(partial)), 40);
+        assert_eq!(resume_offset(source, None), 0);
+        let outdated = FileState {
+            mtime: 1,
+            ..partial
+        };
+        assert_eq!(resume_offset(source, Some(outdated)), 0);
+
+        let options = SyncOptions::new().with_compare(SyncCompare::Checksum);
+        assert_eq!(plan(&options, source, Some(changed)), Plan::CompareChecksum);
+        let options = SyncOptions::new().with_compare(SyncCompare::Always);
+        assert_eq!(plan(&options, source, Some(source)), Plan::Transfer);
+
+        assert_eq!(join("/data/", "bin/app"), "/data/bin/app");
+        assert_eq!(join("", "app"), "app");
+    }
+
+    /// Minimal SFTP server on the local file system, so that syncs can be tested without a SSH server
+    #[cfg(unix)]
+    #[derive(Default)]
+    struct LocalSftp {
+        files: HashMap<String, fs::File>,
+        dirs: HashMap<String, Option<Vec<russh_sftp::protocol::File>>>,
+        next_handle: usize,
+    }
+
+    #[cfg(unix)]
+    impl LocalSftp {
+        fn handle(&mut self) -> String {
+            self.next_handle += 1;
+            self.next_handle.to_string()
+        }
+
+        fn attrs(metadata: &fs::Metadata) -> FileAttributes {
+            use std::os::unix::fs::MetadataExt;
+            FileAttributes {
+                size: Some(metadata.len()),
+                uid: None,
+                user: None,
+                gid: None,
+                group: None,
+                permissions: Some(metadata.mode()),
+                atime: None,
+                mtime: u32::try_from(metadata.mtime()).ok(),
+            }
+        }
+
+        fn ok(id: u32) -> Status {
+            Status {
+                id,
+                status_code: StatusCode::Ok,
+                error_message: String::from("Ok"),
+                language_tag: String::from("en-US"),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn io_status(e: std::io::Error) -> StatusCode {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => StatusCode::NoSuchFile,
+            _ => StatusCode::Failure,
+        }
+    }
+
+    #[cfg(unix)]
+    #[async_trait::async_trait]
+    impl Handler for LocalSftp {
+        type Error = StatusCode;
+
+        fn unimplemented(&self) -> Self::Error {
+            StatusCode::OpUnsupported
+        }
+
+        async fn open(
+            &mut self,
+            id: u32,
+            filename: String,
+            pflags: OpenFlags,
+            _attrs: FileAttributes,
+        ) -> Result<Handle, Self::Error> {
+            let file = fs::OpenOptions::new()
+                .read(pflags.contains(OpenFlags::READ))
+                .write(pflags.contains(OpenFlags::WRITE))
+                .create(pflags.contains(OpenFlags::CREATE))
+                .truncate(pflags.contains(OpenFlags::TRUNCATE))
+                .open(filename)
+                .map_err(io_status)?;
+            let handle = self.handle();
+            self.files.insert(handle.clone(), file);
+            Ok(Handle { id, handle })
+        }
+
+        async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+            self.files.remove(&handle);
+            self.dirs.remove(&handle);
+            Ok(Self::ok(id))
+        }
+
+        async fn read(
+            &mut self,
+            id: u32,
+            handle: String,
+            offset: u64,
+            len: u32,
+        ) -> Result<Data, Self::Error> {
+            use std::os::unix::fs::FileExt;
+            let file = self.files.get(&handle).ok_or(StatusCode::Failure)?;
+            let mut data = vec![0; len as usize];
+            let n = file.read_at(&mut data, offset).map_err(io_status)?;
+            if n == 0 {
+                return Err(StatusCode::Eof);
+            }
+            data.truncate(n);
+            Ok(Data { id, data })
+        }
+
+        async fn write(
+            &mut self,
+            id: u32,
+            handle: String,
+            offset: u64,
+            data: Vec<u8>,
+        ) -> Result<Status, Self::Error> {
+            use std::os::unix::fs::FileExt;
+            let file = self.files.get(&handle).ok_or(StatusCode::Failure)?;
+            file.write_all_at(&data, offset).map_err(io_status)?;
+            Ok(Self::ok(id))
+        }
+
+        async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+            let metadata = fs::metadata(path).map_err(io_status)?;
+            Ok(Attrs {
+                id,
+                attrs: Self::attrs(&metadata),
+            })
+        }
+
+        async fn setstat(
+            &mut self,
+            id: u32,
+            path: String,
+            attrs: FileAttributes,
+        ) -> Result<Status, Self::Error> {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mtime) = attrs.mtime {
+                let file = fs::OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .map_err(io_status)?;
+                file.set_modified(
+                    std::time::UNIX_EPOCH + std::time::Duration::from_secs(mtime.into()),
+                )
+                .map_err(io_status)?;
+            }
+            if let Some(mode) = attrs.permissions {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode)).map_err(io_status)?;
+            }
+            Ok(Self::ok(id))
+        }
+
+        async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+            let mut files = Vec::new();
+            for entry in fs::read_dir(path).map_err(io_status)? {
+                let entry = entry.map_err(io_status)?;
+                let metadata = entry.metadata().map_err(io_status)?;
+                files.push(russh_sftp::protocol::File::new(
+                    entry.file_name().to_string_lossy(),
+                    Self::attrs(&metadata),
+                ));
+            }
+            let handle = self.handle();
+            self.dirs.insert(handle.clone(), Some(files));
+            Ok(Handle { id, handle })
+        }
+
+        async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+            // All entries are returned at once
+            match self.dirs.get_mut(&handle).and_then(Option::take) {
+                Some(files) => Ok(Name { id, files }),
+                None => Err(StatusCode::Eof),
+            }
+        }
+
+        async fn mkdir(
+            &mut self,
+            id: u32,
+            path: String,
+            _attrs: FileAttributes,
+        ) -> Result<Status, Self::Error> {
+            fs::create_dir(path).map_err(io_status)?;
+            Ok(Self::ok(id))
+        }
+
+        async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+            fs::remove_file(filename).map_err(io_status)?;
+            Ok(Self::ok(id))
+        }
+
+        async fn rename(
+            &mut self,
+            id: u32,
+            oldpath: String,
+            newpath: String,
+        ) -> Result<Status, Self::Error> {
+            // Like OpenSSH, do not replace existing files
+            if Path::new(&newpath).exists() {
+                return Err(StatusCode::Failure);
+            }
+            fs::rename(oldpath, newpath).map_err(io_status)?;
+            Ok(Self::ok(id))
+        }
+    }
+
+    #[cfg(unix)]
+    async fn upload(sftp: &SftpSession, local: &Path, remote: &Path) -> SyncReport {
+        let files = upload_files(local, remote.to_str().unwrap()).unwrap();
+        sync(&LocalExecutor, sftp, files, &SyncOptions::default(), true)
+            .await
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    fn set_mtime(path: &Path, time: std::time::SystemTime) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sync() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+
+        let path = std::env::temp_dir().join("slurry_test_sync");
+        // Remove leftovers of previous (failed) runs
+        let _ = fs::remove_dir_all(&path);
+        let (local, remote) = (path.join("local"), path.join("remote"));
+        fs::create_dir_all(local.join("data")).unwrap();
+        fs::write(local.join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(local.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(local.join("data/input.txt"), "0123456789").unwrap();
+
+        let (client_stream, server_stream) = tokio::io::duplex(1 << 20);
+        russh_sftp::server::run(server_stream, LocalSftp::default()).await;
+        let sftp = SftpSession::new(client_stream).await.unwrap();
+
+        let report = upload(&sftp, &local, &remote).await;
+        assert_eq!(report.transferred, vec!["data/input.txt", "run.sh"]);
+        assert_eq!(report.bytes_transferred, 20);
+        let mode = fs::metadata(remote.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(!remote.join("run.sh.part").exists());
+
+        // Unchanged files are skipped
+        let report = upload(&sftp, &local, &remote).await;
+        assert!(report.transferred.is_empty());
+        assert_eq!(report.skipped.len(), 2);
+
+        // An older, shorter but complete destination is replaced (and not appended to)
+        let input = local.join("data/input.txt");
+        fs::write(&input, "abcdefghijklmnop").unwrap();
+        set_mtime(&input, SystemTime::now() + Duration::from_secs(10));
+        let report = upload(&sftp, &local, &remote).await;
+        assert_eq!(report.transferred, vec!["data/input.txt"]);
+        assert!(report.resumed.is_empty());
+        let content = fs::read_to_string(remote.join("data/input.txt")).unwrap();
+        assert_eq!(content, "abcdefghijklmnop");
+
+        // Interrupted transfers are continued from their `.part` file
+        fs::write(&input, "resumed content").unwrap();
+        set_mtime(&input, SystemTime::now() - Duration::from_secs(100));
+        fs::write(remote.join("data/input.txt.part"), "resumed ").unwrap();
+        let report = upload(&sftp, &local, &remote).await;
+        assert_eq!(report.resumed, vec!["data/input.txt"]);
+        assert_eq!(report.bytes_transferred, 7);
+        let content = fs::read_to_string(remote.join("data/input.txt")).unwrap();
+        assert_eq!(content, "resumed content");
+        assert!(!remote.join("data/input.txt.part").exists());
+
+        // Downloads keep the permissions as well
+        let download = path.join("download");
+        let files = super::download_files(&sftp, remote.to_str().unwrap(), &download)
+            .await
+            .unwrap();
+        let report = sync(&LocalExecutor, &sftp, files, &SyncOptions::default(), false)
+            .await
+            .unwrap();
+        assert_eq!(report.transferred.len(), 2);
+        let content = fs::read_to_string(download.join("data/input.txt")).unwrap();
+        assert_eq!(content, "resumed content");
+        let mode = fs::metadata(download.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        fs::remove_dir_all(path).unwrap();
+    }
+}
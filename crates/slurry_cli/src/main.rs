@@ -1,40 +1,184 @@
-use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
-};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
-use slurry::data_extraction::{get_squeue_res_locally, squeue_diff, SqueueMode};
+use clap::{Parser, ValueEnum};
+#[cfg(feature = "archive")]
+use slurry::data_extraction::ArchiveStore;
+#[cfg(feature = "sqlite")]
+use slurry::data_extraction::SqliteStore;
+use slurry::{
+    data_extraction::{
+        detect_output_format, get_squeue_res_with_format, record_finished_jobs, squeue_diff,
+        DirectoryStore, SnapshotStore, SqueueDiffState, SqueueMode,
+    },
+    executor::{CommandExecutor, LocalExecutor},
+};
+#[cfg(feature = "ssh")]
+use slurry::{
+    ssh::{
+        HostKeyCallback, KeyboardInteractiveCallback, ResilientSession, SessionEvent,
+        SessionEventHandler,
+    },
+    ConnectionConfig,
+};
 
 /// Run squeue loop and save delta data
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Folder path where to save the results
+    /// Folder path (or database file when using `--store sqlite`) where to save the results
     #[arg(short, long)]
     path: PathBuf,
 
+    /// How to save the results
+    #[arg(long, value_enum, default_value_t = StoreKind::Directory)]
+    store: StoreKind,
+
     /// Number of seconds to wait in between calls
     #[arg(short, long, default_value_t = 5)]
     delay: u64,
+
+    /// Look up the final state of jobs which left the queue using sacct
+    #[arg(long)]
+    sacct: bool,
+
+    /// Host alias of the SSH config (e.g., `~/.ssh/config`) to run squeue on, instead of running it locally
+    #[cfg(feature = "ssh")]
+    #[arg(long)]
+    ssh_host: Option<String>,
+
+    /// Instead of running the loop, export the results saved in the folder `--path` as Parquet tables into the given folder
+    #[cfg(feature = "parquet")]
+    #[arg(long)]
+    export_parquet: Option<PathBuf>,
+
+    /// Instead of running the loop, copy the results saved in the folder `--path` into a new store (see `--store`) at the given path
+    #[arg(long)]
+    convert_to: Option<PathBuf>,
+}
+
+/// Storage backend for the results
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StoreKind {
+    /// One JSON file per job change
+    Directory,
+    /// A single SQLite database file
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    /// Daily zstd-compressed segment files in a folder
+    #[cfg(feature = "archive")]
+    Archive,
+}
+
+fn open_store(kind: StoreKind, path: &Path) -> Box<dyn SnapshotStore> {
+    match kind {
+        StoreKind::Directory => Box::new(DirectoryStore::new(path)),
+        #[cfg(feature = "sqlite")]
+        StoreKind::Sqlite => {
+            Box::new(SqliteStore::open(path).expect("Could not open SQLite database"))
+        }
+        #[cfg(feature = "archive")]
+        StoreKind::Archive => Box::new(ArchiveStore::open(path).expect("Could not open archive")),
+    }
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let args = Args::parse();
-    let mut known_jobs = HashMap::default();
-    let mut all_ids = HashSet::default();
+    #[cfg(feature = "parquet")]
+    if let Some(dest) = &args.export_parquet {
+        let report = slurry::data_extraction::export_parquet(&args.path, dest)
+            .expect("Could not export results");
+        println!("Exported {report:?}");
+        return;
+    }
+    if let Some(dest) = &args.convert_to {
+        let mut store = open_store(args.store, dest);
+        let snapshots = DirectoryStore::new(&args.path)
+            .copy_to(store.as_mut())
+            .expect("Could not convert results");
+        println!("Converted {snapshots} snapshots");
+        return;
+    }
+    #[cfg(feature = "ssh")]
+    if let Some(alias) = &args.ssh_host {
+        return run_ssh_loop(&args, alias).await;
+    }
+    run_loop(&args, &LocalExecutor).await
+}
+
+#[cfg(feature = "ssh")]
+async fn run_ssh_loop(args: &Args, alias: &str) {
+    // Ask for unknown login prompts (e.g., MFA codes) on the terminal
+    let cfg = ConnectionConfig::from_ssh_config(alias)
+        .expect("Could not read SSH config")
+        .with_keyboard_interactive_callback(KeyboardInteractiveCallback::new(|prompt| async move {
+            tokio::task::spawn_blocking(move || {
+                if !prompt.instructions.is_empty() {
+                    eprintln!("{}", prompt.instructions);
+                }
+                eprint!("{}", prompt.prompt);
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).ok()?;
+                Some(answer.trim_end().to_string())
+            })
+            .await
+            .ok()
+            .flatten()
+        }))
+        // Ask whether to trust unknown host keys on the terminal (as OpenSSH does)
+        .with_host_key_callback(HostKeyCallback::new(|info| async move {
+            tokio::task::spawn_blocking(move || {
+                eprintln!(
+                    "The authenticity of host {}:{} can't be established.",
+                    info.host, info.port
+                );
+                eprintln!(
+                    "{} key fingerprint is {}.",
+                    info.algorithm, info.fingerprint
+                );
+                eprint!("Are you sure you want to continue connecting (yes/no)? ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).is_ok() && answer.trim() == "yes"
+            })
+            .await
+            .unwrap_or(false)
+        }));
+    let session = ResilientSession::new(cfg).with_event_handler(SessionEventHandler::new(
+        |event| match event {
+            SessionEvent::Connected { .. } => println!("Connected"),
+            event => eprintln!("SSH session: {event:?}"),
+        },
+    ));
+    session.connect().await.expect("Could not connect");
+    run_loop(args, &session).await
+}
+
+async fn run_loop<E: CommandExecutor>(args: &Args, executor: &E) {
+    let mut store = open_store(args.store, &args.path);
+    let mut state = SqueueDiffState::default();
     let mut i = 0;
+    let format = detect_output_format(executor).await;
+    println!("Using {:?} output format", format);
     loop {
-        squeue_diff(
-            || get_squeue_res_locally(&SqueueMode::ALL),
-            &args.path,
-            &mut known_jobs,
-            &mut all_ids,
+        // Failures (e.g., a lost connection) are only reported, the next iteration tries again
+        match squeue_diff(
+            || get_squeue_res_with_format(&SqueueMode::ALL, format, executor),
+            store.as_mut(),
+            &mut state,
         )
         .await
-        .unwrap();
-        i += 1;
+        {
+            Ok(_) => {
+                i += 1;
+                if args.sacct {
+                    if let Err(e) = record_finished_jobs(executor, store.as_mut(), &mut state).await
+                    {
+                        eprintln!("Failed to run sacct: {e:?}");
+                    }
+                }
+            }
+            Err(e) => eprintln!("Failed to run squeue: {e:?}"),
+        }
         println!("Ran for {} iterations, sleeping...", i);
         tokio::time::sleep(tokio::time::Duration::from_secs(args.delay)).await;
     }
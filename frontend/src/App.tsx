@@ -3,7 +3,7 @@ import clsx from "clsx";
 import { Folder, LogOut } from "lucide-react";
 import { useCallback, useContext, useEffect, useState } from "react";
 import toast, { Toaster } from "react-hot-toast";
-import { AppContext, AppContextType } from "./AppContext";
+import { AppContext, AppContextType, SnapshotStoreKind } from "./AppContext";
 import ConnectionConfigForm from "./components/ConnectionConfigForm";
 import JobsOverview from "./components/JobsOverview";
 import OCELExtractor from "./components/OCELExtractor";
@@ -12,6 +12,7 @@ import { Button } from "./components/ui/button";
 import { Input } from "./components/ui/input";
 import { Label } from "./components/ui/label";
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "./components/ui/tabs";
+import { ToggleGroup, ToggleGroupItem } from "./components/ui/toggle-group";
 
 let toastID: string | undefined = undefined
 export default function App({ context }: { context: AppContextType }) {
@@ -34,6 +35,36 @@ export default function App({ context }: { context: AppContextType }) {
       setLoggedInStatus("initial");
     })
   }, [])
+  useEffect(() => {
+    const unlisten = context.listenHostKeyPrompt((info) => {
+      const trusted = window.confirm(`The authenticity of host ${info.host}:${info.port} can't be established.\n${info.algorithm} key fingerprint is ${info.fingerprint}.\nAre you sure you want to continue connecting?`);
+      context.answerHostKeyPrompt(info.host, info.port, trusted);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    }
+  }, [])
+  useEffect(() => {
+    const unlisten = context.listenSessionEvents((event) => {
+      if (event.type === "Disconnected") {
+        toast.error("Lost connection, reconnecting...");
+      } else if (event.type === "Connected" && event.attempts > 1) {
+        toast.success("Reconnected!");
+      }
+    });
+    return () => {
+      unlisten.then((f) => f());
+    }
+  }, [])
+  useEffect(() => {
+    const unlisten = context.listenAuthPrompt((prompt) => {
+      const answer = window.prompt([prompt.instructions, prompt.prompt].filter((s) => s !== "").join("\n"));
+      context.answerAuthPrompt(prompt.host, answer);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    }
+  }, [])
   return (
     <AppContext.Provider value={context}>
       <main className="h-screen">
@@ -157,7 +188,7 @@ function DataCollection() {
   const context = useContext(AppContext);
   const [loopInfo, setLoopInfo] = useState<{ secondInterval: number, runningSince: string, path: string }>();
   const [loading, setLoading] = useState(false);
-  const [loopConfig, setLoopConfig] = useState<{ secondInterval: number }>({ secondInterval: 5 });
+  const [loopConfig, setLoopConfig] = useState<{ secondInterval: number, store: SnapshotStoreKind }>({ secondInterval: 5, store: "directory" });
   const updateLoopInfo = useCallback(() => {
     context.getLoopInfo().then((li) => {
       setLoopInfo(li)
@@ -181,11 +212,18 @@ function DataCollection() {
           <Label className="font-semibold">Second Interval</Label>
           <Input className="w-[14ch]" type="number" step={1} min={3} value={loopConfig.secondInterval} onChange={(ev) => setLoopConfig({ ...loopConfig, secondInterval: ev.currentTarget.valueAsNumber })} />
         </div>
+        <div className="flex flex-col items-center gap-0.5">
+          <Label className="font-semibold">Save As</Label>
+          <ToggleGroup variant="outline" type="single" value={loopConfig.store} onValueChange={(e) => setLoopConfig({ ...loopConfig, store: e === "sqlite" ? "sqlite" : "directory" })}>
+            <ToggleGroupItem value="directory">JSON Folder</ToggleGroupItem>
+            <ToggleGroupItem value="sqlite">SQLite Database</ToggleGroupItem>
+          </ToggleGroup>
+        </div>
         <Button className="mx-auto block" disabled={loading || loopInfo !== undefined} onClick={() => {
           if (Number.isInteger(loopConfig.secondInterval) && loopConfig.secondInterval >= 3) {
 
             setLoading(true);
-            toast.promise(context.startSqueueLoop(loopConfig.secondInterval), { loading: "Starting loop....", error: "Failed to start loop!", success: "Started loop!" }).catch(e => console.error(e)).then(() => updateLoopInfo()).finally(() => setLoading(false))
+            toast.promise(context.startSqueueLoop(loopConfig.secondInterval, loopConfig.store), { loading: "Starting loop....", error: "Failed to start loop!", success: "Started loop!" }).catch(e => console.error(e)).then(() => updateLoopInfo()).finally(() => setLoading(false))
           } else {
             toast("Please enter a valid interval of at least 3 seconds.");
           }
@@ -27,22 +27,38 @@ export const connectionFormSchema = z.object({
       path: z.string(),
       passcode: z.string().optional(),
     }),
+    z.object({
+      mode: z.literal("agent"),
+    }),
   ]),
 });
 
 export type SqueueRow = {account: string, state: string}
+export type HostKeyInfo = {host: string, port: number, algorithm: string, fingerprint: string, key: string}
+export type SessionEvent = {type: "Connecting", attempt: number, delay_ms: number} | {type: "ConnectFailed", attempt: number, error: string} | {type: "Connected", attempts: number} | {type: "Disconnected"} | {type: "CommandFailed", command: string, error: string}
+export type AuthPromptInfo = {host: string, name: string, instructions: string, prompt: string, echo: boolean}
+export type SnapshotStoreKind = "directory" | "sqlite"
 export type AppContextType = {
   runSqueue: () => Promise<string>;
-  startSqueueLoop: (second_interval: number) => Promise<string>;
+  startSqueueLoop: (second_interval: number, store: SnapshotStoreKind) => Promise<string>;
   stopSqueueLoop: () => Promise<string>,
   getLoopInfo: () => Promise<{secondInterval: number, runningSince: string, path: string}>,
   getSqueue: () => Promise<[string,SqueueRow[]]>,
   extractOCEL: () => Promise<string>;
   login: (cfg: z.infer<typeof connectionFormSchema>) => Promise<string>;
+  // Return unlisten function (to de-register)
+  listenHostKeyPrompt: (a: (info: HostKeyInfo) => unknown) => Promise<() => unknown>,
+  answerHostKeyPrompt: (host: string, port: number, trusted: boolean) => Promise<void>,
+  // Return unlisten function (to de-register)
+  listenAuthPrompt: (a: (prompt: AuthPromptInfo) => unknown) => Promise<() => unknown>,
+  // Answer with null to abort the login
+  answerAuthPrompt: (host: string, answer: string | null) => Promise<void>,
   logout: () => Promise<string>,
   isLoggedIn: () => Promise<boolean>,
   // Return unlisten function (to de-register)
   listenSqueue: (a: (timeAndRows: [string,SqueueRow[]]) => unknown) => Promise<() => unknown>,
+  // Return unlisten function (to de-register)
+  listenSessionEvents: (a: (event: SessionEvent) => unknown) => Promise<() => unknown>,
   startTestJob: () => Promise<string>,
   checkJobStatus: (jobID: string) => Promise<{status: "PENDING", start_time: String|undefined} |{status: "RUNNING", start_time: String|undefined, end_time: String|undefined} | {status: "ENDED", state: string}  | {status: "NOT_FOUND"}>,
 };
@@ -58,9 +74,14 @@ export const DEFAULT_NO_CONTEXT: AppContextType = {
   getLoopInfo: throwNoContext,
   extractOCEL: throwNoContext,
   login: throwNoContext,
+  listenHostKeyPrompt: throwNoContext,
+  answerHostKeyPrompt: throwNoContext,
+  listenAuthPrompt: throwNoContext,
+  answerAuthPrompt: throwNoContext,
   logout: throwNoContext,
   isLoggedIn: throwNoContext,
   listenSqueue: throwNoContext,
+  listenSessionEvents: throwNoContext,
   startTestJob: throwNoContext,
   checkJobStatus: throwNoContext
 };
@@ -133,6 +133,8 @@ export default function ConnectionConfigForm({ onSubmit, disabled }: { disabled?
                           mfaCode: "",
                         };
                         field.onChange(newVal);
+                      } else if (newMode === "agent") {
+                        field.onChange({ mode: "agent" });
                       } else {
                         const newVal: z.infer<
                           typeof connectionFormSchema
@@ -152,6 +154,11 @@ export default function ConnectionConfigForm({ onSubmit, disabled }: { disabled?
                       >
                         Private SSH Keyfile
                       </TabsTrigger>
+                      <TabsTrigger disabled={disabled}
+                        value="agent"
+                      >
+                        SSH Agent
+                      </TabsTrigger>
                     </TabsList>
                     <div className="text-left ml-4">
                       <TabsContent value="password-mfa">
@@ -233,6 +240,9 @@ export default function ConnectionConfigForm({ onSubmit, disabled }: { disabled?
                           )}
                         />
                       </TabsContent>
+                      <TabsContent value="agent">
+                        Login using the keys of the running SSH agent.
+                      </TabsContent>
                     </div>
                   </Tabs>
                 </FormControl>